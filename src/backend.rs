@@ -0,0 +1,436 @@
+use crate::capture::{OutputPipes, OutputReader, OutputReaders};
+use crate::poll::{check_poll_support, record_poll, spawn_poll, PollChild, PollFields, PollPeriod};
+use crate::record::Recording;
+use crate::trace::{TraceError, TraceEvent};
+use crossbeam::channel::{SendError, Sender};
+use indexmap::IndexMap;
+use nix::unistd::Pid;
+use std::ffi::{OsStr, OsString};
+use std::ops::ControlFlow;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone)]
+pub enum Backend {
+    /// Periodically poll the process tree, and the selected data of each process.
+    Poll { period: PollPeriod, fields: PollFields },
+    /// Trace all fork/exec syscalls with ptrace, optionally also reporting failed exec attempts.
+    #[cfg(target_os = "linux")]
+    Ptrace { exec_failures: bool },
+}
+
+/// How to run the traced command, besides its path and arguments.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    /// The working directory of the command, instead of our own.
+    pub cwd: Option<PathBuf>,
+    /// Environment variables to set for the command, on top of our own environment.
+    pub env: Vec<(OsString, OsString)>,
+    /// Record the stdout and stderr of the command as [TraceEvent::Output],
+    /// they are still passed through to our own stdout and stderr.
+    pub capture_output: bool,
+}
+
+/// A backend that is ready to trace its command, see [`Backend::start`].
+pub enum StartedBackend {
+    Poll {
+        child: PollChild,
+        period: PollPeriod,
+        fields: PollFields,
+        output: Option<OutputReaders>,
+    },
+    #[cfg(target_os = "linux")]
+    Ptrace {
+        child: crate::trace::TraceChild,
+        exec_failures: bool,
+        output: Option<OutputReaders>,
+    },
+    /// Report a fixed list of events instead of tracing a command, see [`crate::trace::replay`].
+    Replay(Vec<TraceEvent>),
+}
+
+impl Backend {
+    /// Prepare to trace the command, this already spawns the command in its own process group.
+    /// For ptrace the child waits before executing the command until tracing starts.
+    /// Both are safe from any thread.
+    pub fn start(
+        self,
+        child_path: &OsStr,
+        child_argv: &[OsString],
+        options: &CommandOptions,
+    ) -> Result<StartedBackend, TraceError> {
+        let output = match options.capture_output {
+            true => Some(OutputPipes::new().map_err(|e| TraceError::from_io(TraceError::Fork, e))?),
+            false => None,
+        };
+
+        match self {
+            Backend::Poll { period, fields } => {
+                check_poll_support().map_err(TraceError::Poll)?;
+
+                let mut command = Command::new(child_path);
+                if let Some((child_argv_0, child_argv_rest)) = child_argv.split_first() {
+                    command.arg0(child_argv_0);
+                    command.args(child_argv_rest);
+                }
+                if let Some(cwd) = &options.cwd {
+                    command.current_dir(cwd);
+                }
+                command.envs(options.env.iter().map(|(key, value)| (key, value)));
+
+                let output = output.map(|output| {
+                    command.stdout(output.stdout).stderr(output.stderr);
+                    output.readers
+                });
+                Ok(StartedBackend::Poll {
+                    child: spawn_poll(command)?,
+                    period,
+                    fields,
+                    output,
+                })
+            }
+            #[cfg(target_os = "linux")]
+            Backend::Ptrace { exec_failures } => {
+                use crate::trace::start_trace;
+                use std::ffi::CString;
+                use std::os::unix::ffi::OsStrExt;
+
+                let to_cstring = |s: &OsStr| CString::new(s.as_bytes()).expect("Failed to convert command to CString");
+                let child_path = to_cstring(child_path);
+                let child_argv: Vec<CString> = child_argv.iter().map(|s| to_cstring(s)).collect();
+                let child_envp: Option<Vec<CString>> = (!options.env.is_empty()).then(|| {
+                    options
+                        .merged_env()
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let mut entry = key;
+                            entry.push("=");
+                            entry.push(value);
+                            to_cstring(&entry)
+                        })
+                        .collect()
+                });
+                let cwd = options.cwd.as_ref().map(|cwd| to_cstring(cwd.as_os_str()));
+
+                // the write ends are only needed by the child, drop them here so the pipes close once it's done
+                let stdio = output.as_ref().map(|output| (&output.stdout, &output.stderr));
+                let child =
+                    unsafe { start_trace(&child_path, &child_argv, child_envp.as_deref(), cwd.as_deref(), stdio)? };
+                Ok(StartedBackend::Ptrace {
+                    child,
+                    exec_failures,
+                    output: output.map(|output| output.readers),
+                })
+            }
+        }
+    }
+}
+
+/// Trace the command of the started backend on a new thread, sending all events to `event_tx`.
+/// The backend stops when `stop` is set or when the receiver is dropped.
+///
+/// Heartbeat events are only forwarded at most once per `heartbeat_period`, or never if it is `None`,
+/// all other events are always forwarded immediately.
+///
+/// The thread returns an error if the command could not be started or traced.
+pub fn spawn_backend(
+    backend: StartedBackend,
+    stop: Arc<AtomicBool>,
+    event_tx: Sender<TraceEvent>,
+    heartbeat_period: Option<Duration>,
+) -> JoinHandle<Result<(), TraceError>> {
+    let mut backend = backend;
+    let output_reader = backend
+        .take_output()
+        .map(|readers| OutputReader::spawn(readers, event_tx.clone()));
+
+    let mut last_heartbeat = Instant::now();
+    let mut callback = move |event| {
+        if stop.load(Ordering::Relaxed) {
+            return ControlFlow::Break(());
+        }
+
+        if let TraceEvent::None = event {
+            match heartbeat_period {
+                Some(period) if last_heartbeat.elapsed() >= period => last_heartbeat = Instant::now(),
+                _ => return ControlFlow::Continue(()),
+            }
+        }
+
+        match event_tx.send(event) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(SendError(_)) => ControlFlow::Break(()),
+        }
+    };
+
+    std::thread::spawn(move || {
+        let callback = |event| {
+            // output times are relative to the start of the trace
+            if let TraceEvent::TraceStart { time, .. } = event
+                && let Some(output_reader) = &output_reader
+            {
+                let _ = output_reader.time_start.set(time);
+            }
+            callback(event)
+        };
+
+        let result = match backend {
+            StartedBackend::Poll {
+                child,
+                period,
+                fields,
+                output: _,
+            } => record_poll(child, period, fields, callback).map(|_| ()),
+            #[cfg(target_os = "linux")]
+            StartedBackend::Ptrace {
+                child,
+                exec_failures,
+                output: _,
+            } => crate::trace::run_trace(child, exec_failures, callback),
+            StartedBackend::Replay(events) => crate::trace::replay(events, callback),
+        };
+
+        if let Some(output_reader) = output_reader {
+            output_reader.finish();
+        }
+        result
+    })
+}
+
+impl CommandOptions {
+    /// Our own environment with [Self::env] applied on top, later entries override earlier ones.
+    fn merged_env(&self) -> Vec<(OsString, OsString)> {
+        let mut env: IndexMap<OsString, OsString> = std::env::vars_os().collect();
+        env.extend(self.env.iter().cloned());
+        env.into_iter().collect()
+    }
+}
+
+impl StartedBackend {
+    /// The process group of the command, the root process is its leader.
+    pub fn pgid(&self) -> Option<Pid> {
+        match self {
+            StartedBackend::Poll { child, .. } => Some(child.pid()),
+            #[cfg(target_os = "linux")]
+            StartedBackend::Ptrace { child, .. } => Some(child.pid()),
+            StartedBackend::Replay(_) => None,
+        }
+    }
+
+    /// Take the read ends of the captured output, if any.
+    fn take_output(&mut self) -> Option<OutputReaders> {
+        match self {
+            StartedBackend::Poll { output, .. } => output.take(),
+            #[cfg(target_os = "linux")]
+            StartedBackend::Ptrace { output, .. } => output.take(),
+            StartedBackend::Replay(_) => None,
+        }
+    }
+}
+
+/// Record the command with the given backend without any GUI, and return the final recording once it finishes.
+/// Setting `stop` stops recording early, the partial recording is still returned.
+/// The invocation of the recording is set to `child_argv`.
+pub fn record_to_recording(
+    child_path: &OsStr,
+    child_argv: &[OsString],
+    backend: Backend,
+    stop: Arc<AtomicBool>,
+) -> Result<Recording, TraceError> {
+    let started = backend.start(child_path, child_argv, &CommandOptions::default())?;
+    let mut recording = Recording::new();
+    recording.invocation = child_argv.to_vec();
+    let recording = Mutex::new(recording);
+    record_started(started, stop, &recording)?;
+    Ok(recording.into_inner().unwrap())
+}
+
+/// Record the command of the started backend into `recording` until it finishes, without any GUI.
+/// The lock is only held while reporting each batch of events, so others can read the recording in the meantime.
+pub fn record_started(
+    backend: StartedBackend,
+    stop: Arc<AtomicBool>,
+    recording: &Mutex<Recording>,
+) -> Result<(), TraceError> {
+    record_started_with(backend, stop, recording, |_| {})
+}
+
+/// Like [record_started], but calls `after_batch` with the recording after each batch of events, while the lock is held.
+/// This can be used to keep the recording bounded, eg. by evicting finished processes.
+pub fn record_started_with(
+    backend: StartedBackend,
+    stop: Arc<AtomicBool>,
+    recording: &Mutex<Recording>,
+    mut after_batch: impl FnMut(&mut Recording),
+) -> Result<(), TraceError> {
+    let (event_tx, event_rx) = crossbeam::channel::unbounded::<TraceEvent>();
+    let handle = spawn_backend(backend, stop, event_tx, None);
+
+    // the channel disconnects once the backend is done
+    for event in &event_rx {
+        let mut recording = recording.lock().unwrap();
+        recording.report(event);
+        for event in event_rx.try_iter() {
+            recording.report(event);
+        }
+        after_batch(&mut recording);
+    }
+
+    handle.join().expect("backend thread panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll_backend() -> Backend {
+        Backend::Poll {
+            period: PollPeriod::Fixed(Duration::from_millis(10)),
+            fields: PollFields {
+                exec: true,
+                exec_always: false,
+                cpu_time: false,
+                fds: false,
+                group: false,
+                namespace: false,
+                memory: false,
+                credentials: false,
+            },
+        }
+    }
+
+    #[test]
+    fn record_command_without_gui() {
+        let mut backends = vec![poll_backend()];
+        #[cfg(target_os = "linux")]
+        if crate::trace::check_ptrace_support().is_ok() {
+            backends.push(Backend::Ptrace { exec_failures: false });
+        }
+
+        for backend in backends {
+            let is_poll = matches!(backend, Backend::Poll { .. });
+            let argv: Vec<OsString> = vec!["/bin/true".into()];
+            let rec = record_to_recording(
+                OsStr::new("/bin/true"),
+                &argv,
+                backend,
+                Arc::new(AtomicBool::new(false)),
+            )
+            .unwrap();
+
+            assert_eq!(rec.invocation, argv);
+            assert_eq!(rec.processes().len(), 1);
+            let root = &rec.processes()[&rec.root_pid.unwrap()];
+            assert_eq!(root.exit_code, Some(0));
+            // only polling reports the end of the trace, but it usually misses the exec of such a short command
+            if is_poll {
+                assert!(rec.time_end.is_some());
+            } else {
+                assert_eq!(root.execs.last().unwrap().argv, argv);
+            }
+        }
+    }
+
+    #[test]
+    fn child_environment_and_fds() {
+        let mut backends = vec![poll_backend()];
+        #[cfg(target_os = "linux")]
+        if crate::trace::check_ptrace_support().is_ok() {
+            backends.push(Backend::Ptrace { exec_failures: false });
+        }
+
+        for backend in backends {
+            let options = CommandOptions {
+                cwd: Some("/tmp".into()),
+                env: vec![("WTF_TEST".into(), "value".into())],
+                capture_output: true,
+            };
+            let argv: Vec<OsString> = vec![
+                "/bin/sh".into(),
+                "-c".into(),
+                "echo $WTF_TEST; pwd; ls /proc/$$/fd".into(),
+            ];
+            let started = backend.start(&argv[0], &argv, &options).unwrap();
+            let recording = Mutex::new(Recording::new());
+            record_started(started, Arc::new(AtomicBool::new(false)), &recording).unwrap();
+            let recording = recording.into_inner().unwrap();
+
+            let stdout = recording
+                .output
+                .iter()
+                .filter(|chunk| chunk.stream == crate::record::OutputStream::Stdout)
+                .map(|chunk| chunk.text.to_str().unwrap())
+                .collect::<String>();
+            let mut lines = stdout.lines();
+            assert_eq!(lines.next(), Some("value"), "{:?}", backend);
+            assert_eq!(lines.next(), Some("/tmp"), "{:?}", backend);
+
+            // the only extra fds are the ones we deliberately don't close on exec, eg. a jobserver
+            let inherited = |fd: i32| {
+                let flags = unsafe { nix::libc::fcntl(fd, nix::libc::F_GETFD) };
+                flags >= 0 && flags & nix::libc::FD_CLOEXEC == 0
+            };
+            for fd in lines.map(|line| line.parse::<i32>().unwrap()) {
+                assert!(fd <= 2 || inherited(fd), "leaked fd {} with {:?}", fd, backend);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_fixture_layout() {
+        let recording = Mutex::new(Recording::new());
+        let backend = StartedBackend::Replay(crate::trace::fixtures::build());
+        record_started(backend, Arc::new(AtomicBool::new(false)), &recording).unwrap();
+        let recording = recording.into_inner().unwrap();
+        assert_eq!(recording.processes().len(), 5);
+
+        // the compilers overlap and need separate rows, the linker reuses the row of the first one
+        let placed = crate::layout::place_processes(&recording, false, Default::default()).unwrap();
+        let expected = serde_json::json!({
+            "pid": 100, "row_offset": 0, "row_height": 4,
+            "children": [{
+                "pid": 101, "row_offset": 1, "row_height": 3,
+                "children": [
+                    { "pid": 102, "row_offset": 1, "row_height": 1 },
+                    { "pid": 103, "row_offset": 2, "row_height": 1 },
+                    { "pid": 104, "row_offset": 1, "row_height": 1 },
+                ],
+            }],
+        });
+        assert_eq!(serde_json::to_value(placed.to_debug_tree()).unwrap(), expected);
+    }
+
+    #[test]
+    fn record_missing_command_fails() {
+        let argv: Vec<OsString> = vec!["/nonexistent/wtf".into()];
+        let result = record_to_recording(&argv[0], &argv, poll_backend(), Arc::new(AtomicBool::new(false)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn heartbeats_are_throttled() {
+        let run = |heartbeat_period| {
+            let mut events = crate::trace::fixtures::build();
+            let real_events = events.len();
+            // a busy tracer reports a heartbeat for every syscall
+            events.extend(std::iter::repeat_with(|| TraceEvent::None).take(10_000));
+
+            let (event_tx, event_rx) = crossbeam::channel::unbounded();
+            let stop = Arc::new(AtomicBool::new(false));
+            spawn_backend(StartedBackend::Replay(events), stop, event_tx, heartbeat_period)
+                .join()
+                .unwrap()
+                .unwrap();
+            event_rx.try_iter().count() - real_events
+        };
+
+        assert_eq!(run(None), 0);
+        assert!(run(Some(Duration::from_secs(60))) <= 1);
+        assert_eq!(run(Some(Duration::ZERO)), 10_000);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::record::OutputStream;
+use crate::trace::TraceEvent;
+use crossbeam::channel::Sender;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc;
+use std::io;
+use std::io::Write;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+const POLL_TIMEOUT_MS: libc::c_int = 100;
+
+/// Pipes that replace the stdout and stderr of the traced command, so its output can be recorded.
+pub struct OutputPipes {
+    pub readers: OutputReaders,
+    /// The write ends, to be used as the stdout and stderr of the command.
+    pub stdout: OwnedFd,
+    pub stderr: OwnedFd,
+}
+
+/// The read ends of [OutputPipes].
+pub struct OutputReaders {
+    stdout: OwnedFd,
+    stderr: OwnedFd,
+}
+
+impl OutputPipes {
+    pub fn new() -> io::Result<Self> {
+        // the write ends are only inherited by the command through dup2, which clears the flag again
+        let (stdout_rx, stdout_tx) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (stderr_rx, stderr_tx) = nix::unistd::pipe2(OFlag::O_CLOEXEC)?;
+        Ok(OutputPipes {
+            readers: OutputReaders {
+                stdout: stdout_rx,
+                stderr: stderr_rx,
+            },
+            stdout: stdout_tx,
+            stderr: stderr_tx,
+        })
+    }
+}
+
+/// A thread that passes the captured output through to our own stdout and stderr,
+/// and reports it as [TraceEvent::Output].
+pub(crate) struct OutputReader {
+    handle: JoinHandle<()>,
+    done: Arc<AtomicBool>,
+    /// The start of the trace, output times are relative to this. Set once the backend reports it.
+    pub time_start: Arc<OnceLock<Instant>>,
+}
+
+impl OutputReader {
+    pub fn spawn(readers: OutputReaders, event_tx: Sender<TraceEvent>) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let time_start = Arc::new(OnceLock::new());
+        let handle = {
+            let done = done.clone();
+            let time_start = time_start.clone();
+            std::thread::spawn(move || read_output(readers, &done, &time_start, &event_tx))
+        };
+        OutputReader {
+            handle,
+            done,
+            time_start,
+        }
+    }
+
+    /// Read the output that is still buffered and stop.
+    /// Background processes can keep the pipes open, their later output is not recorded.
+    pub fn finish(self) {
+        self.done.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+fn read_output(
+    readers: OutputReaders,
+    done: &AtomicBool,
+    time_start: &OnceLock<Instant>,
+    event_tx: &Sender<TraceEvent>,
+) {
+    let mut open = vec![
+        (OutputStream::Stdout, readers.stdout),
+        (OutputStream::Stderr, readers.stderr),
+    ];
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+
+    while !open.is_empty() {
+        // once done, only drain what is already available
+        let finishing = done.load(Ordering::Relaxed);
+        let mut fds = open
+            .iter()
+            .map(|(_, fd)| libc::pollfd {
+                fd: fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect::<Vec<_>>();
+        let timeout = if finishing { 0 } else { POLL_TIMEOUT_MS };
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        match ready {
+            ..0 if Errno::last() == Errno::EINTR => continue,
+            ..0 => return,
+            0 if finishing => return,
+            _ => {}
+        }
+
+        let mut closed = vec![];
+        for (index, fd) in fds.iter().enumerate() {
+            if fd.revents == 0 {
+                continue;
+            }
+            let (stream, fd) = &open[index];
+            let count = match nix::unistd::read(fd, &mut buffer) {
+                Ok(0) => {
+                    closed.push(index);
+                    continue;
+                }
+                Ok(count) => count,
+                Err(Errno::EINTR) => continue,
+                Err(_) => {
+                    closed.push(index);
+                    continue;
+                }
+            };
+            let bytes = &buffer[..count];
+
+            // pass the output through, failing to do so shouldn't stop the recording
+            let _ = match stream {
+                OutputStream::Stdout => io::stdout().lock().write_all(bytes).and_then(|()| io::stdout().flush()),
+                OutputStream::Stderr => io::stderr().lock().write_all(bytes),
+            };
+
+            let event = TraceEvent::Output {
+                time: time_start.get().map_or(0.0, |start| start.elapsed().as_secs_f64()),
+                stream: *stream,
+                bytes: bytes.to_vec(),
+            };
+            if event_tx.send(event).is_err() {
+                return;
+            }
+        }
+        for index in closed.into_iter().rev() {
+            open.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn output_is_reported_with_time() {
+        let pipes = OutputPipes::new().unwrap();
+        let (event_tx, event_rx) = crossbeam::channel::unbounded();
+        let reader = OutputReader::spawn(pipes.readers, event_tx);
+        let time_start = Instant::now() - Duration::from_secs(1);
+        reader.time_start.set(time_start).unwrap();
+
+        nix::unistd::write(&pipes.stdout, b"hello").unwrap();
+        drop((pipes.stdout, pipes.stderr));
+        reader.finish();
+
+        let events = event_rx.try_iter().collect::<Vec<_>>();
+        let [TraceEvent::Output { time, stream, bytes }] = events.as_slice() else {
+            panic!("expected a single output event, got {:?}", events);
+        };
+        assert_eq!(*stream, OutputStream::Stdout);
+        assert_eq!(bytes, b"hello");
+        assert!(*time >= 1.0 && *time < time_start.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,9 @@
+pub mod critical_path;
+pub mod csv;
+pub mod events;
+pub mod otlp;
+#[cfg(feature = "prost")]
+pub mod perfetto;
+pub mod redact;
+#[cfg(feature = "rusqlite")]
+pub mod sqlite;
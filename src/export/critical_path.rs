@@ -0,0 +1,131 @@
+use crate::record::Recording;
+use crate::swriteln;
+use itertools::Itertools;
+use nix::unistd::Pid;
+use serde_json::{json, Value};
+use std::io;
+use std::io::Write;
+
+/// A single process on the critical path, see [critical_path_steps].
+#[derive(Debug, Clone)]
+pub struct CriticalPathStep {
+    pub pid: Pid,
+    pub command: String,
+    pub duration: f64,
+    /// The part of `duration` not covered by the next step, ie. the time this process itself is on the critical path.
+    pub self_time: f64,
+    /// The sum of `self_time` of this and all previous steps.
+    pub cumulative: f64,
+}
+
+/// The steps of [Recording::critical_path], starting from the root.
+pub fn critical_path_steps(rec: &Recording) -> Vec<CriticalPathStep> {
+    let path = rec.critical_path();
+
+    let durations = path
+        .iter()
+        .map(|pid| {
+            let time = &rec.processes()[pid].time;
+            let end = time.end.or(rec.time_end).unwrap_or(time.start);
+            end - time.start
+        })
+        .collect_vec();
+
+    let mut cumulative = 0.0;
+    path.iter()
+        .enumerate()
+        .map(|(i, &pid)| {
+            let info = &rec.processes()[&pid];
+            let command = match info.execs.last() {
+                Some(exec) => exec.argv.iter().map(|arg| arg.to_string_lossy()).join(" "),
+                None => info.name().to_string_lossy().into_owned(),
+            };
+
+            let duration = durations[i];
+            let self_time = (duration - durations.get(i + 1).copied().unwrap_or(0.0)).max(0.0);
+            cumulative += self_time;
+
+            CriticalPathStep {
+                pid,
+                command,
+                duration,
+                self_time,
+                cumulative,
+            }
+        })
+        .collect()
+}
+
+/// Write the critical path as a report, either as plain text or as a JSON array with one object per step.
+pub fn write_critical_path(rec: &Recording, mut w: impl Write, as_json: bool) -> io::Result<()> {
+    let steps = critical_path_steps(rec);
+
+    if as_json {
+        let steps: Vec<Value> = steps
+            .iter()
+            .map(|step| {
+                json!({
+                    "pid": step.pid.as_raw(),
+                    "command": step.command,
+                    "duration": step.duration,
+                    "self_time": step.self_time,
+                    "cumulative": step.cumulative,
+                })
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut w, &steps)?;
+        writeln!(w)
+    } else {
+        let mut text = String::new();
+        swriteln!(
+            text,
+            "{:>8} {:>10} {:>10} {:>10}  command",
+            "pid",
+            "duration",
+            "self",
+            "cumulative"
+        );
+        for step in &steps {
+            swriteln!(
+                text,
+                "{:>8} {:>9.3}s {:>9.3}s {:>9.3}s  {}",
+                step.pid,
+                step.duration,
+                step.self_time,
+                step.cumulative,
+                step.command
+            );
+        }
+        w.write_all(text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    #[test]
+    fn exported_chain_matches_critical_path() {
+        let rec = Recording::from_events(fixtures::build());
+        let mut json = vec![];
+        write_critical_path(&rec, &mut json, true).unwrap();
+        let steps: Vec<Value> = serde_json::from_slice(&json).unwrap();
+
+        let pids = steps
+            .iter()
+            .map(|step| step["pid"].as_i64().unwrap() as i32)
+            .collect_vec();
+        let expected = rec.critical_path().iter().map(|pid| pid.as_raw()).collect_vec();
+        assert_eq!(pids, expected);
+        assert_eq!(steps[2]["command"], "ld a.o b.o -o main");
+
+        // the self times add up to the duration of the root
+        let cumulative = steps
+            .iter()
+            .map(|step| step["cumulative"].as_f64().unwrap())
+            .collect_vec();
+        assert!(cumulative.windows(2).all(|w| w[0] <= w[1]));
+        assert!((cumulative[2] - 2.2).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::record::Recording;
+use itertools::Itertools;
+use std::ffi::OsString;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+
+const HEADER: &[&str] = &[
+    "pid",
+    "parent_pid",
+    "start",
+    "end",
+    "duration",
+    "exec_path",
+    "argv",
+    "exit_code",
+    "invocation",
+];
+
+/// Write one row per process, with the exec info of the last exec.
+pub fn write_csv(rec: &Recording, mut w: impl Write) -> io::Result<()> {
+    write_row(&mut w, HEADER.iter().map(|s| s.as_bytes().to_vec()))?;
+
+    for info in rec.processes().values() {
+        let exec = info.execs.last();
+        let fmt_opt = |x: Option<String>| x.unwrap_or_default().into_bytes();
+
+        let row = [
+            info.pid.to_string().into_bytes(),
+            fmt_opt(info.parent.map(|p| p.to_string())),
+            info.time.start.to_string().into_bytes(),
+            fmt_opt(info.time.end.map(|t| t.to_string())),
+            fmt_opt(info.time.duration().map(|t| t.to_string())),
+            exec.map(|exec| exec.path.as_bytes().to_vec()).unwrap_or_default(),
+            exec.map(|exec| join_args(&exec.argv)).unwrap_or_default(),
+            fmt_opt(info.exit_code.map(|c| c.to_string())),
+            if rec.root_pid == Some(info.pid) {
+                join_args(&rec.invocation)
+            } else {
+                vec![]
+            },
+        ];
+        write_row(&mut w, row.into_iter())?;
+    }
+
+    Ok(())
+}
+
+fn join_args(args: &[OsString]) -> Vec<u8> {
+    args.iter().map(|arg| arg.as_bytes()).collect_vec().join(&b' ')
+}
+
+fn write_row(w: &mut impl Write, fields: impl Iterator<Item = Vec<u8>>) -> io::Result<()> {
+    let line = fields.map(escape_field).collect_vec().join(&b',');
+    w.write_all(&line)?;
+    w.write_all(b"\n")
+}
+
+fn escape_field(field: Vec<u8>) -> Vec<u8> {
+    if field.iter().any(|b| b",\"\n\r".contains(b)) {
+        let mut escaped = vec![b'"'];
+        for b in field {
+            if b == b'"' {
+                escaped.push(b'"');
+            }
+            escaped.push(b);
+        }
+        escaped.push(b'"');
+        escaped
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    #[test]
+    fn one_row_per_process() {
+        let mut rec = Recording::from_events(fixtures::build());
+        rec.invocation = vec!["wtf".into(), "sh".into()];
+
+        let mut out = vec![];
+        write_csv(&rec, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect_vec();
+
+        assert_eq!(lines.len(), 1 + 5);
+        assert_eq!(lines[0], HEADER.join(","));
+        assert_eq!(lines[1], "100,,0,2.2,2.2,/bin/sh,sh -c make,0,wtf sh");
+        assert_eq!(lines[3], "102,101,0.2,1.2,1,/usr/bin/cc,cc -c a.c,0,");
+    }
+
+    #[test]
+    fn fields_are_escaped() {
+        assert_eq!(escape_field(b"plain".to_vec()), b"plain");
+        assert_eq!(escape_field(b"a,b".to_vec()), b"\"a,b\"");
+        assert_eq!(escape_field(b"say \"hi\"".to_vec()), b"\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,36 @@
+use crate::trace::TraceEvent;
+use std::io;
+use std::io::Write;
+
+/// Write the events as a single JSON array, in the given order.
+pub fn write_events_json(events: &[TraceEvent], w: impl Write) -> io::Result<()> {
+    serde_json::to_writer(w, events)?;
+    Ok(())
+}
+
+/// Write the events as newline-delimited json, one event per line, which can be loaded with [crate::record::Recording::from_jsonl].
+pub fn write_events_jsonl(events: &[TraceEvent], mut w: impl Write) -> io::Result<()> {
+    for event in events {
+        serde_json::to_writer(&mut w, event)?;
+        w.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    #[test]
+    fn json_array_keeps_all_events() {
+        let events = fixtures::build();
+        let mut json = vec![];
+        write_events_json(&events, &mut json).unwrap();
+
+        let parsed: Vec<TraceEvent> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed.len(), events.len());
+        let times = |events: &[TraceEvent]| events.iter().map(TraceEvent::time).collect::<Vec<_>>();
+        assert_eq!(times(&parsed), times(&events));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::record::{ProcessInfo, Recording};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use nix::unistd::Pid;
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Write the recording as OTLP/JSON, with one span per process. The output can be sent to an OTLP collector.
+pub fn write_otlp_json(rec: &Recording, w: impl Write) -> io::Result<()> {
+    let trace_id = trace_id(rec);
+    let spans: Vec<Value> = rec
+        .processes()
+        .values()
+        .map(|info| process_span(rec, &trace_id, info))
+        .collect();
+
+    let root = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute_string("service.name", "wtf")],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "wtf" },
+                "spans": spans,
+            }],
+        }],
+    });
+
+    serde_json::to_writer(w, &root)?;
+    Ok(())
+}
+
+/// Send the recording as OTLP/JSON to an OTLP/HTTP collector, eg. `http://localhost:4318`.
+#[cfg(feature = "ureq")]
+pub fn send_otlp_http(rec: &Recording, endpoint: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut body = vec![];
+    write_otlp_json(rec, &mut body)?;
+
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    ureq::post(&url)
+        .header("Content-Type", "application/json")
+        .send(&body[..])?;
+    Ok(())
+}
+
+fn process_span(rec: &Recording, trace_id: &str, info: &ProcessInfo) -> Value {
+    // unfinished processes end at the end of the recording
+    let end = info.time.end.or(rec.time_end).unwrap_or(info.time.start);
+
+    let mut attributes = vec![attribute_int("process.pid", info.pid.as_raw() as i64)];
+    if let Some(exec) = info.execs.last() {
+        attributes.push(json!({ "key": "process.executable.path", "value": os_string_value(&exec.path) }));
+        attributes.push(json!({
+            "key": "process.command_args",
+            "value": { "arrayValue": { "values": exec.argv.iter().map(|arg| os_string_value(arg)).collect::<Vec<_>>() } },
+        }));
+    }
+    if let Some(exit_code) = info.exit_code {
+        attributes.push(attribute_int("process.exit.code", exit_code as i64));
+    }
+
+    let mut span = json!({
+        "traceId": trace_id,
+        "spanId": span_id(info.pid),
+        "name": info.name().to_string_lossy(),
+        // SPAN_KIND_INTERNAL
+        "kind": 1,
+        "startTimeUnixNano": unix_nanos(rec, info.time.start).to_string(),
+        "endTimeUnixNano": unix_nanos(rec, end).to_string(),
+        "attributes": attributes,
+    });
+    if let Some(parent) = info.parent {
+        span["parentSpanId"] = Value::from(span_id(parent));
+    }
+    span
+}
+
+// a trace id derived from the recording start, so exporting the same recording twice gives the same id
+fn trace_id(rec: &Recording) -> String {
+    let hash_with = |salt: u64| {
+        let mut hasher = DefaultHasher::new();
+        salt.hash(&mut hasher);
+        rec.time_start_wall.hash(&mut hasher);
+        rec.root_pid.map(Pid::as_raw).hash(&mut hasher);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", hash_with(0), hash_with(1))
+}
+
+// the span id of a process, the pid with an extra bit set to make sure it's never zero
+fn span_id(pid: Pid) -> String {
+    format!("{:016x}", (1 << 32) | (pid.as_raw() as u32 as u64))
+}
+
+fn unix_nanos(rec: &Recording, time: f64) -> u128 {
+    let wall_time = rec
+        .wall_time(time)
+        .unwrap_or_else(|| UNIX_EPOCH + Duration::from_secs_f64(time.max(0.0)));
+    wall_time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn attribute_string(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+// a string value if it's valid UTF-8, otherwise the raw bytes
+fn os_string_value(s: &OsStr) -> Value {
+    match s.to_str() {
+        Some(s) => json!({ "stringValue": s }),
+        None => json!({ "bytesValue": BASE64_STANDARD.encode(s.as_bytes()) }),
+    }
+}
+
+fn attribute_int(key: &str, value: i64) -> Value {
+    // OTLP/JSON encodes 64-bit integers as strings
+    json!({ "key": key, "value": { "intValue": value.to_string() } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+    use std::collections::HashMap;
+
+    #[test]
+    fn span_parents_match_process_tree() {
+        let rec = Recording::from_events(fixtures::build());
+        let mut out = vec![];
+        write_otlp_json(&rec, &mut out).unwrap();
+        let root: Value = serde_json::from_slice(&out).unwrap();
+
+        let spans = root["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), rec.processes().len());
+        let pid_of_span: HashMap<&str, i64> = spans
+            .iter()
+            .map(|span| {
+                let pid = span["attributes"][0]["value"]["intValue"].as_str().unwrap();
+                (span["spanId"].as_str().unwrap(), pid.parse().unwrap())
+            })
+            .collect();
+
+        for span in spans {
+            assert_eq!(span["traceId"], spans[0]["traceId"]);
+            let pid = pid_of_span[span["spanId"].as_str().unwrap()];
+            let parent = span["parentSpanId"].as_str().map(|parent| pid_of_span[parent]);
+            let expected = rec.processes()[&Pid::from_raw(pid as i32)]
+                .parent
+                .map(|p| p.as_raw() as i64);
+            assert_eq!(parent, expected, "parent of {}", pid);
+        }
+        assert!(spans.iter().any(|span| span["parentSpanId"].is_null()));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::record::{LabelMode, ProcessKind, Recording};
+use nix::unistd::Pid;
+use prost::Message;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+// the sequence id shared by all packets, perfetto drops track events without one
+const SEQUENCE_ID: u32 = 1;
+
+/// Write the recording as a native Perfetto trace protobuf, which is much smaller than JSON for large traces.
+pub fn write_perfetto(rec: &Recording, mut w: impl Write) -> io::Result<()> {
+    w.write_all(&perfetto_trace(rec).encode_to_vec())
+}
+
+/// Build the trace written by [write_perfetto].
+pub fn perfetto_trace(rec: &Recording) -> Trace {
+    let time_end = rec.time_end.or(rec.time_latest).unwrap_or(0.0);
+
+    // the kind of each process, as seen from its parent
+    let kinds: HashMap<Pid, ProcessKind> = rec
+        .processes()
+        .values()
+        .flat_map(|info| info.children.iter().map(|&(kind, child)| (child, kind)))
+        .collect();
+    let kind = |pid: Pid| kinds.get(&pid).copied().unwrap_or(ProcessKind::Process);
+    // pids can be reused, so tracks are identified by their index instead
+    let uuid = |pid: Pid| rec.processes().get_index_of(&pid).map(|index| index as u64 + 1);
+
+    let mut descriptors = vec![];
+    let mut events = vec![];
+    for info in rec.processes().values() {
+        let name = info.label(LabelMode::Basename).to_string_lossy().into_owned();
+        let track_uuid = uuid(info.pid).unwrap();
+
+        let descriptor = match kind(info.pid) {
+            ProcessKind::Process => TrackDescriptor {
+                uuid: Some(track_uuid),
+                parent_uuid: None,
+                name: None,
+                process: Some(ProcessDescriptor {
+                    pid: Some(info.pid.as_raw()),
+                    cmdline: info.execs.last().map_or(vec![], |exec| {
+                        exec.argv.iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+                    }),
+                    process_name: Some(name.clone()),
+                }),
+                thread: None,
+            },
+            ProcessKind::Thread => {
+                // find the process this thread belongs to
+                let mut process = info.parent;
+                while let Some(pid) = process
+                    && kind(pid) == ProcessKind::Thread
+                {
+                    process = rec.processes().get(&pid).and_then(|info| info.parent);
+                }
+
+                TrackDescriptor {
+                    uuid: Some(track_uuid),
+                    // the process is not always part of the recording, then the thread is shown on its own
+                    parent_uuid: process.and_then(uuid),
+                    name: None,
+                    process: None,
+                    thread: Some(ThreadDescriptor {
+                        pid: Some(process.map_or(info.pid.as_raw(), |pid| pid.as_raw())),
+                        tid: Some(info.pid.as_raw()),
+                        thread_name: Some(name.clone()),
+                    }),
+                }
+            }
+        };
+        descriptors.push(TracePacket {
+            track_descriptor: Some(descriptor),
+            ..TracePacket::default()
+        });
+
+        let end = info.time.end.unwrap_or(time_end).max(info.time.start);
+        events.push((
+            info.time.start,
+            track_event(track_uuid, TrackEventType::SliceBegin, Some(name)),
+        ));
+        events.push((end, track_event(track_uuid, TrackEventType::SliceEnd, None)));
+    }
+
+    // perfetto expects the events of a sequence in timestamp order
+    events.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let events = events.into_iter().map(|(time, event)| TracePacket {
+        timestamp: Some(time_to_ns(time)),
+        track_event: Some(event),
+        trusted_packet_sequence_id: Some(SEQUENCE_ID),
+        ..TracePacket::default()
+    });
+
+    Trace {
+        packet: descriptors.into_iter().chain(events).collect(),
+    }
+}
+
+fn track_event(track_uuid: u64, ty: TrackEventType, name: Option<String>) -> TrackEvent {
+    TrackEvent {
+        r#type: Some(ty as i32),
+        track_uuid: Some(track_uuid),
+        name,
+    }
+}
+
+fn time_to_ns(time: f64) -> u64 {
+    (time.max(0.0) * 1e9) as u64
+}
+
+// The subset of the perfetto trace format we use, see `protos/perfetto/trace` in the perfetto repository.
+// Field numbers must match the upstream definitions.
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Trace {
+    #[prost(message, repeated, tag = "1")]
+    pub packet: Vec<TracePacket>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TracePacket {
+    #[prost(uint64, optional, tag = "8")]
+    pub timestamp: Option<u64>,
+    #[prost(uint32, optional, tag = "10")]
+    pub trusted_packet_sequence_id: Option<u32>,
+    #[prost(message, optional, tag = "11")]
+    pub track_event: Option<TrackEvent>,
+    #[prost(message, optional, tag = "60")]
+    pub track_descriptor: Option<TrackDescriptor>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TrackEvent {
+    #[prost(enumeration = "TrackEventType", optional, tag = "9")]
+    pub r#type: Option<i32>,
+    #[prost(uint64, optional, tag = "11")]
+    pub track_uuid: Option<u64>,
+    #[prost(string, optional, tag = "23")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, prost::Enumeration)]
+#[repr(i32)]
+pub enum TrackEventType {
+    Unspecified = 0,
+    SliceBegin = 1,
+    SliceEnd = 2,
+    Instant = 3,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TrackDescriptor {
+    #[prost(uint64, optional, tag = "1")]
+    pub uuid: Option<u64>,
+    #[prost(string, optional, tag = "2")]
+    pub name: Option<String>,
+    #[prost(message, optional, tag = "3")]
+    pub process: Option<ProcessDescriptor>,
+    #[prost(message, optional, tag = "4")]
+    pub thread: Option<ThreadDescriptor>,
+    #[prost(uint64, optional, tag = "5")]
+    pub parent_uuid: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ProcessDescriptor {
+    #[prost(int32, optional, tag = "1")]
+    pub pid: Option<i32>,
+    #[prost(string, repeated, tag = "2")]
+    pub cmdline: Vec<String>,
+    #[prost(string, optional, tag = "6")]
+    pub process_name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ThreadDescriptor {
+    #[prost(int32, optional, tag = "1")]
+    pub pid: Option<i32>,
+    #[prost(int32, optional, tag = "2")]
+    pub tid: Option<i32>,
+    #[prost(string, optional, tag = "5")]
+    pub thread_name: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    #[test]
+    fn one_slice_per_process() {
+        let rec = Recording::from_events(fixtures::threads());
+        let mut bytes = vec![];
+        write_perfetto(&rec, &mut bytes).unwrap();
+        let trace = Trace::decode(bytes.as_slice()).unwrap();
+
+        let count = |ty: TrackEventType| {
+            let events = trace.packet.iter().filter_map(|packet| packet.track_event.as_ref());
+            events.filter(|event| event.r#type == Some(ty as i32)).count()
+        };
+        assert_eq!(count(TrackEventType::SliceBegin), rec.processes().len());
+        assert_eq!(count(TrackEventType::SliceEnd), rec.processes().len());
+
+        // threads are tracks under their process
+        let descriptors = trace
+            .packet
+            .iter()
+            .filter_map(|packet| packet.track_descriptor.as_ref())
+            .collect::<Vec<_>>();
+        let process = descriptors.iter().find(|d| d.process.is_some()).unwrap();
+        let threads = descriptors.iter().filter(|d| d.thread.is_some()).collect::<Vec<_>>();
+        assert_eq!(threads.len(), 3);
+        assert!(threads.iter().all(|d| d.parent_uuid == process.uuid));
+    }
+
+    #[test]
+    fn thread_without_recorded_process() {
+        let mut rec = Recording::from_events(fixtures::threads());
+        let thread = Pid::from_raw(201);
+        rec.processes_mut()[&thread].parent = Some(Pid::from_raw(999));
+
+        let trace = perfetto_trace(&rec);
+        let descriptor = trace
+            .packet
+            .iter()
+            .filter_map(|packet| packet.track_descriptor.as_ref())
+            .find(|d| d.thread.as_ref().is_some_and(|t| t.tid == Some(thread.as_raw())))
+            .unwrap();
+        assert_eq!(descriptor.parent_uuid, None);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::record::{ProcessExec, Recording};
+use regex::bytes::Regex;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// Options for [Recording::redact], to sanitize a recording before sharing it.
+#[derive(Debug, Clone, Default)]
+pub struct RedactOptions {
+    /// The home directory to replace with `~` in paths and arguments.
+    pub home: Option<OsString>,
+    /// What to do with the arguments after `argv[0]`.
+    pub args: RedactArgs,
+    /// Additional `(regex, replacement)` rules, applied in order to paths and arguments after the home directory.
+    pub rules: Vec<(Regex, String)>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, clap::ValueEnum)]
+pub enum RedactArgs {
+    /// Keep the arguments, only apply the home directory and rule replacements.
+    #[default]
+    Keep,
+    /// Replace each argument by a hash, equal arguments still get equal hashes.
+    Hash,
+    /// Remove all arguments.
+    Strip,
+}
+
+impl RedactOptions {
+    /// Replace the home directory of the current user, and keep the arguments.
+    pub fn current_home() -> Self {
+        RedactOptions {
+            home: std::env::home_dir().map(|home| home.into_os_string()),
+            args: RedactArgs::Keep,
+            rules: vec![],
+        }
+    }
+}
+
+impl Recording {
+    /// A copy of this recording with paths and arguments redacted according to `opts`.
+    pub fn redact(&self, opts: RedactOptions) -> Recording {
+        let home_rule = opts
+            .home
+            .as_deref()
+            .map(|home| {
+                let home = home.as_bytes();
+                // strip trailing slashes
+                home.iter()
+                    .rposition(|&b| b != b'/')
+                    .map_or(&home[..0], |last| &home[..=last])
+            })
+            .filter(|home| !home.is_empty())
+            .map(|home| {
+                // only match the full home directory, not eg. `/home/user2` for `/home/user`
+                let regex = Regex::new(&format!(r"{}(/|$|[^\w.-])", escape_bytes(home))).unwrap();
+                (regex, "~$1".to_owned())
+            });
+        let rules: Vec<&(Regex, String)> = home_rule.iter().chain(&opts.rules).collect();
+
+        let redact_str = |s: &OsString| {
+            let mut s = s.as_bytes().to_vec();
+            for (regex, replacement) in &rules {
+                s = regex.replace_all(&s, replacement.as_bytes()).into_owned();
+            }
+            OsString::from_vec(s)
+        };
+
+        let redact_argv = |argv: &mut Vec<OsString>| {
+            let mut args = std::mem::take(argv).into_iter();
+            argv.extend(args.next().as_ref().map(redact_str));
+            match opts.args {
+                RedactArgs::Keep => argv.extend(args.map(|arg| redact_str(&arg))),
+                RedactArgs::Hash => argv.extend(args.map(|arg| hash_arg(&arg))),
+                RedactArgs::Strip => {}
+            }
+        };
+
+        let mut result = self.clone();
+        redact_argv(&mut result.invocation);
+        for chunk in &mut result.output {
+            chunk.text = redact_str(&chunk.text);
+        }
+        for info in result.processes_mut().values_mut() {
+            for exec in &mut info.execs {
+                let ProcessExec {
+                    time: _,
+                    cwd,
+                    path,
+                    argv,
+                    rss_start: _,
+                    rss_peak: _,
+                } = exec;
+
+                *cwd = cwd.as_ref().map(redact_str);
+                *path = redact_str(path);
+                redact_argv(argv);
+            }
+            for failure in &mut info.exec_failures {
+                failure.path = redact_str(&failure.path);
+            }
+        }
+        result
+    }
+}
+
+fn hash_arg(arg: &OsStr) -> OsString {
+    let mut hasher = DefaultHasher::new();
+    arg.hash(&mut hasher);
+    format!("<{:08x}>", hasher.finish() as u32).into()
+}
+
+// escape arbitrary bytes into a pattern that matches them literally, including bytes that are not valid UTF-8
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut pattern = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b == b'/' {
+            pattern.push(b as char);
+        } else {
+            write!(pattern, r"(?-u:\x{:02x})", b).unwrap();
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+    use nix::unistd::Pid;
+
+    fn redact_exec(opts: RedactOptions) -> ProcessExec {
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(
+            None,
+            1,
+            0.0,
+            "/home/alice/bin/build",
+            &["build", "--out=/home/alice/out", "/home/alice2/x", "secret"],
+        ));
+        let rec = Recording::from_events(events).redact(opts);
+        rec.processes()[&Pid::from_raw(1)].execs[0].clone()
+    }
+
+    #[test]
+    fn home_is_replaced() {
+        let exec = redact_exec(RedactOptions {
+            home: Some("/home/alice/".into()),
+            args: RedactArgs::Keep,
+            rules: vec![],
+        });
+        assert_eq!(exec.path, "~/bin/build");
+        // other users with the same prefix are left alone
+        assert_eq!(exec.argv, ["build", "--out=~/out", "/home/alice2/x", "secret"]);
+    }
+
+    #[test]
+    fn args_are_stripped_or_hashed() {
+        let opts = |args| RedactOptions {
+            home: Some("/home/alice".into()),
+            args,
+            rules: vec![(Regex::new("build").unwrap(), "tool".to_owned())],
+        };
+
+        let exec = redact_exec(opts(RedactArgs::Strip));
+        assert_eq!(exec.path, "~/bin/tool");
+        assert_eq!(exec.argv, ["tool"]);
+
+        let exec = redact_exec(opts(RedactArgs::Hash));
+        assert_eq!(exec.argv.len(), 4);
+        assert_eq!(exec.argv[3], hash_arg(OsStr::new("secret")));
+        assert!(exec.argv[1..]
+            .iter()
+            .all(|arg| !arg.as_bytes().windows(5).any(|w| w == b"alice")));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::record::{ProcessKind, Recording};
+use crate::util::OsStringAs;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection};
+use serde_with::ser::SerializeAsWrap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+const SCHEMA: &str = "
+CREATE TABLE processes (
+    pid INTEGER PRIMARY KEY,
+    start REAL NOT NULL,
+    end REAL,
+    exit INTEGER
+);
+CREATE TABLE execs (
+    pid INTEGER NOT NULL REFERENCES processes(pid),
+    time REAL NOT NULL,
+    cwd TEXT,
+    path TEXT NOT NULL,
+    argv TEXT NOT NULL
+);
+CREATE TABLE children (
+    parent INTEGER NOT NULL REFERENCES processes(pid),
+    child INTEGER NOT NULL,
+    kind TEXT NOT NULL
+);
+";
+
+/// Write the recording into the `processes`, `execs` and `children` tables, which must not exist yet.
+pub fn write_sqlite(rec: &Recording, conn: &mut Connection) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    {
+        let mut insert_process = tx.prepare("INSERT INTO processes (pid, start, end, exit) VALUES (?1, ?2, ?3, ?4)")?;
+        let mut insert_exec =
+            tx.prepare("INSERT INTO execs (pid, time, cwd, path, argv) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut insert_child = tx.prepare("INSERT INTO children (parent, child, kind) VALUES (?1, ?2, ?3)")?;
+
+        for info in rec.processes().values() {
+            let pid = info.pid.as_raw();
+            insert_process.execute(params![pid, info.time.start, info.time.end, info.exit_code])?;
+
+            for exec in &info.execs {
+                let argv = serde_json::to_string(&SerializeAsWrap::<_, Vec<OsStringAs>>::new(&exec.argv)).unwrap();
+                let cwd = exec.cwd.as_deref().map(os_str_sql);
+                insert_exec.execute(params![pid, exec.time, cwd, os_str_sql(&exec.path), argv])?;
+            }
+
+            for &(kind, child) in &info.children {
+                let kind = match kind {
+                    ProcessKind::Process => "process",
+                    ProcessKind::Thread => "thread",
+                };
+                insert_child.execute(params![pid, child.as_raw(), kind])?;
+            }
+        }
+    }
+
+    tx.commit()
+}
+
+fn os_str_sql(s: &OsStr) -> ToSqlOutput<'_> {
+    match s.to_str() {
+        Some(s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes())),
+        None => ToSqlOutput::Borrowed(ValueRef::Blob(s.as_bytes())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    #[test]
+    fn tables_can_be_queried() {
+        let rec = Recording::from_events(fixtures::build());
+        let mut conn = Connection::open_in_memory().unwrap();
+        write_sqlite(&rec, &mut conn).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM processes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 5);
+
+        let argv: String = conn
+            .query_row("SELECT argv FROM execs WHERE pid = 102", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(argv, r#"["cc","-c","a.c"]"#);
+
+        let children: i64 = conn
+            .query_row("SELECT COUNT(*) FROM children WHERE parent = 101", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(children, 3);
+    }
+}
@@ -1,23 +1,44 @@
-use crate::layout::PlacedProcess;
-use crate::record::{Recording, TimeRange};
-use crate::swriteln;
-use crossbeam::channel::Sender;
+use crate::layout::{
+    place_processes, place_processes_incremental, LayoutCache, LayoutMode, LayoutSettings, PlacedProcess,
+};
+use crate::record::{LabelMode, OutputStream, ProcessExec, ProcessKind, Recording, TimeRange};
+use crate::stats::{
+    argv_diff, concurrency_over_time, executable_stats, idle_gaps, previous_invocation, process_groups,
+    process_tree_line, process_tree_text, selection_summary, self_time, thread_process_time, ArgDiff, Concurrency,
+    DiffKind, ExecutableStats, TraceDiff,
+};
+use crate::{swrite, swriteln};
+use crossbeam::channel::{Receiver, Sender};
 use eframe::egui;
+use eframe::egui::collapsing_header::CollapsingState;
 use eframe::egui::ecolor::Hsva;
 use eframe::egui::scroll_area::{ScrollBarVisibility, ScrollSource};
 use eframe::egui::style::ScrollAnimation;
-use eframe::egui::{CentralPanel, Context, Key, PointerButton, ScrollArea, Sense, SidePanel, Vec2};
-use eframe::emath::{Pos2, Rect};
-use eframe::epaint::{Color32, CornerRadiusF32, FontId, Stroke, StrokeKind};
+use eframe::egui::{
+    Align, CentralPanel, CollapsingHeader, Context, Grid, Key, Layout, Modifiers, PointerButton, ScrollArea, Sense,
+    SidePanel, Theme, TopBottomPanel, Vec2,
+};
+use eframe::emath::{Align2, Pos2, Rect};
+use eframe::epaint::{Color32, CornerRadiusF32, FontId, Galley, Stroke, StrokeKind};
 use eframe::Frame;
 use egui_theme_switch::global_theme_switch;
-use itertools::enumerate;
+use indexmap::IndexSet;
+use itertools::{enumerate, Itertools};
 use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::ops::ControlFlow;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct GuiHandle {
     pub data_to_gui: Arc<Mutex<Option<DataToGui>>>,
+    /// The settings to use for the layout, configured from the GUI.
+    pub layout_settings: Arc<Mutex<LayoutSettings>>,
+    /// Receives a message every time the user asks to re-run the command, disconnects once the GUI is closed.
+    pub rerun_rx: Receiver<()>,
     pub ctx: Context,
 }
 
@@ -26,9 +47,69 @@ pub struct DataToGui {
 
     pub placed_threads_no: Option<PlacedProcess>,
     pub placed_threads_yes: Option<PlacedProcess>,
+
+    /// The error the backend stopped with, shown when there are no processes to display.
+    pub error: Option<String>,
+    /// The comparison between the two loaded recordings, if they were loaded with `--compare`.
+    pub diff: Option<TraceDiff>,
+}
+
+/// Layout caches for both thread modes, to only place the changed parts of a growing recording.
+#[derive(Debug, Default)]
+pub struct DataLayoutCache {
+    threads_no: LayoutCache,
+    threads_yes: LayoutCache,
+}
+
+impl DataToGui {
+    pub fn new(recording: Recording, layout_settings: LayoutSettings) -> Self {
+        let mut data = DataToGui {
+            recording,
+            placed_threads_no: None,
+            placed_threads_yes: None,
+            error: None,
+            diff: None,
+        };
+        data.relayout(layout_settings);
+        data
+    }
+
+    /// Same as [DataToGui::new], but reuse the layout of the previous recording placed with the same cache.
+    pub fn new_incremental(recording: Recording, layout_settings: LayoutSettings, cache: &mut DataLayoutCache) -> Self {
+        let placed_threads_no = place_processes_incremental(&recording, &mut cache.threads_no, false, layout_settings);
+        let placed_threads_yes = place_processes_incremental(&recording, &mut cache.threads_yes, true, layout_settings);
+        DataToGui {
+            recording,
+            placed_threads_no,
+            placed_threads_yes,
+            error: None,
+            diff: None,
+        }
+    }
+
+    fn relayout(&mut self, layout_settings: LayoutSettings) {
+        self.placed_threads_no = place_processes(&self.recording, false, layout_settings);
+        self.placed_threads_yes = place_processes(&self.recording, true, layout_settings);
+    }
+
+    fn placed(&self, show_threads: bool) -> Option<(&Recording, &PlacedProcess)> {
+        let root_placed = if show_threads {
+            &self.placed_threads_yes
+        } else {
+            &self.placed_threads_no
+        };
+        Some((&self.recording, root_placed.as_ref()?))
+    }
 }
 
-pub fn main_gui(channel: Sender<GuiHandle>) -> eframe::Result<()> {
+/// Run the GUI until it's closed, starting with the given process notes.
+pub fn main_gui(
+    channel: Sender<GuiHandle>,
+    layout_settings: LayoutSettings,
+    notes: HashMap<Pid, String>,
+    can_rerun: bool,
+    self_pid: Option<Pid>,
+) -> eframe::Result<HashMap<Pid, String>> {
     // TODO add icon
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -36,14 +117,35 @@ pub fn main_gui(channel: Sender<GuiHandle>) -> eframe::Result<()> {
             .with_maximized(true),
         ..Default::default()
     };
+    let notes = Arc::new(Mutex::new(notes));
+    let app_notes = notes.clone();
     eframe::run_native(
         "wtf",
         native_options,
         Box::new(|ctx| {
-            let app = App::new();
+            let panel_state = ctx
+                .storage
+                .and_then(|storage| eframe::get_value(storage, PANEL_STATE_KEY))
+                .unwrap_or_default();
+            let zoom_settings = ctx
+                .storage
+                .and_then(|storage| eframe::get_value(storage, ZOOM_SETTINGS_KEY))
+                .unwrap_or_default();
+            let (rerun_tx, rerun_rx) = crossbeam::channel::unbounded();
+            let rerun_tx = can_rerun.then_some(rerun_tx);
+            let app = App::new(
+                layout_settings,
+                panel_state,
+                zoom_settings,
+                app_notes,
+                rerun_tx,
+                self_pid,
+            );
 
             let interact = GuiHandle {
                 data_to_gui: app.data_to_gui.clone(),
+                layout_settings: app.layout_settings.clone(),
+                rerun_rx,
                 ctx: ctx.egui_ctx.clone(),
             };
             let _ = channel.send(interact);
@@ -51,73 +153,736 @@ pub fn main_gui(channel: Sender<GuiHandle>) -> eframe::Result<()> {
 
             Ok(Box::new(app))
         }),
-    )
+    )?;
+    Ok(std::mem::take(&mut *notes.lock().unwrap()))
+}
+
+// gui layout state that is remembered across runs
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+struct PanelState {
+    side_panel_open: bool,
+    side_panel_width: f32,
+}
+
+impl Default for PanelState {
+    fn default() -> Self {
+        Self {
+            side_panel_open: true,
+            side_panel_width: DEFAULT_SIDE_PANEL_WIDTH,
+        }
+    }
 }
 
 struct App {
     data_to_gui: Arc<Mutex<Option<DataToGui>>>,
     data: Option<DataToGui>,
+    panel_state: PanelState,
+    layout_settings: Arc<Mutex<LayoutSettings>>,
+    layout_mode: LayoutMode,
+    label_mode: LabelMode,
+    min_duration_ms: f64,
+    max_depth_enabled: bool,
+    max_depth: usize,
+    stats: Vec<ExecutableStats>,
+    critical_path: IndexSet<Pid>,
+    // the children of the root, each of their subtrees gets its own color when coloring by subtree
+    top_level_children: IndexSet<Pid>,
+    // all process group ids, each group gets its own tint if enabled
+    process_groups: IndexSet<Pid>,
+    concurrency: Concurrency,
+    // all processes sorted by start time, and the filter for the process list
+    process_list: Vec<Pid>,
+    process_filter: String,
+    // the recording revision the data above was derived from
+    derived_revision: Option<u64>,
+    // the selected process that the process tree was last expanded to
+    process_tree_revealed: Option<Pid>,
 
     color_settings: ColorSettings,
     show_threads: bool,
+    highlight_same_executable: bool,
+    show_absolute_times: bool,
+    follow_tail: bool,
+    highlight_critical_path: bool,
+    // dim everything except the subtree of the last selected process
+    focus_selected: bool,
+    tint_process_groups: bool,
+    show_concurrency: bool,
+    show_idle_gaps: bool,
+    min_bar_width: f32,
+
+    // replay of finished recordings, the current time to show and whether it advances automatically
+    scrub_enabled: bool,
+    scrub_time: f64,
+    scrub_playing: bool,
 
     zoom_linear: Vec2,
+    zoom_settings: ZoomSettings,
     zoom_auto_hor: bool,
 
-    selected_pid: Option<Pid>,
+    // the timeline viewport of the previous frame, and a scroll offset to apply in the next frame
+    timeline_viewport: Rect,
+    timeline_scroll_target: Option<Vec2>,
+    // the screen position of the timeline content origin in the previous frame
+    timeline_origin: Pos2,
+    // the horizontal scroll offset requested in the previous frame when following the live tail
+    follow_tail_offset: Option<f32>,
+
+    // selected processes, in the order they were selected
+    selected_pids: IndexSet<Pid>,
     hovered_pid: Option<Pid>,
+
+    // exec highlighted in the timeline, by (pid, exec index)
+    selected_exec: Option<(Pid, usize)>,
+    hovered_exec: Option<(Pid, usize)>,
+
+    // notes attached to processes, shared with the caller of main_gui, and the note currently being edited
+    notes: Arc<Mutex<HashMap<Pid, String>>>,
+    note_edit: Option<(Pid, String)>,
+
+    // requests to re-run the command, if that's possible
+    rerun_tx: Option<Sender<()>>,
+    // our own process, shown as a bar above the timeline if set
+    self_pid: Option<Pid>,
+
+    // the command palette, if it's open
+    palette: Option<PaletteState>,
+}
+
+// the query and highlighted entry of the open command palette
+#[derive(Debug, Default)]
+struct PaletteState {
+    query: String,
+    selected: usize,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(
+        layout_settings: LayoutSettings,
+        panel_state: PanelState,
+        zoom_settings: ZoomSettings,
+        notes: Arc<Mutex<HashMap<Pid, String>>>,
+        rerun_tx: Option<Sender<()>>,
+        self_pid: Option<Pid>,
+    ) -> Self {
         Self {
             data_to_gui: Arc::new(Mutex::new(None)),
             data: None,
+            panel_state,
+            layout_settings: Arc::new(Mutex::new(layout_settings)),
+            layout_mode: layout_settings.mode,
+            label_mode: LabelMode::default(),
+            min_duration_ms: layout_settings.min_duration * 1000.0,
+            max_depth_enabled: layout_settings.max_depth.is_some(),
+            max_depth: layout_settings.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+            stats: vec![],
+            critical_path: IndexSet::new(),
+            top_level_children: IndexSet::new(),
+            process_groups: IndexSet::new(),
+            concurrency: Concurrency::default(),
+            process_list: vec![],
+            process_filter: String::new(),
+            derived_revision: None,
+            process_tree_revealed: None,
             color_settings: ColorSettings::new(),
             zoom_linear: Vec2::ZERO,
+            zoom_settings,
             zoom_auto_hor: true,
+            timeline_viewport: Rect::NOTHING,
+            timeline_origin: Pos2::ZERO,
+            timeline_scroll_target: None,
             show_threads: false,
-            selected_pid: None,
+            highlight_same_executable: false,
+            show_absolute_times: false,
+            follow_tail: false,
+            highlight_critical_path: false,
+            focus_selected: false,
+            tint_process_groups: false,
+            show_concurrency: true,
+            show_idle_gaps: false,
+            min_bar_width: 0.0,
+            scrub_enabled: false,
+            scrub_time: 0.0,
+            scrub_playing: false,
+            follow_tail_offset: None,
+            selected_pids: IndexSet::new(),
             hovered_pid: None,
+            selected_exec: None,
+            hovered_exec: None,
+            notes,
+            note_edit: None,
+            rerun_tx,
+            self_pid,
+            palette: None,
+        }
+    }
+
+    // recompute everything derived from the recording itself
+    fn derive_data(&mut self, recording: &Recording) {
+        self.derived_revision = Some(recording.revision());
+        self.stats = executable_stats(recording, self.label_mode);
+        self.critical_path = recording.critical_path().into_iter().collect();
+        self.top_level_children = recording
+            .root_pid
+            .and_then(|root| recording.processes().get(&root))
+            .map(|root| root.children.iter().map(|&(_, child)| child).collect())
+            .unwrap_or_default();
+        self.process_groups = process_groups(recording).into_keys().collect();
+        self.concurrency = concurrency_over_time(recording);
+        self.process_list = recording
+            .processes()
+            .values()
+            .sorted_by(|a, b| a.time.start.total_cmp(&b.time.start))
+            .map(|info| info.pid)
+            .collect();
+    }
+
+    // ask to re-run the command, and forget everything that refers to processes of the current recording
+    fn rerun(&mut self) {
+        let Some(rerun_tx) = &self.rerun_tx else {
+            return;
+        };
+        if rerun_tx.send(()).is_err() {
+            return;
+        }
+
+        self.data = None;
+        self.selected_pids.clear();
+        self.hovered_pid = None;
+        self.selected_exec = None;
+        self.hovered_exec = None;
+        self.notes.lock().unwrap().clear();
+        self.note_edit = None;
+        self.scrub_enabled = false;
+        self.follow_tail_offset = None;
+    }
+
+    // whether the action currently does anything, unavailable actions are hidden from the command palette
+    fn palette_action_available(&self, action: PaletteAction) -> bool {
+        match action {
+            PaletteAction::Rerun => self.rerun_tx.is_some(),
+            PaletteAction::ToggleFocus | PaletteAction::ClearSelection => !self.selected_pids.is_empty(),
+            PaletteAction::ToggleReplay => self.finished_time_end().is_some(),
+            PaletteAction::JumpToPid(pid) => self
+                .data
+                .as_ref()
+                .is_some_and(|data| data.recording.processes().contains_key(&pid)),
+            _ => true,
+        }
+    }
+
+    // the actions shown in the command palette for the given query, best match first
+    fn palette_entries(&self, query: &str) -> Vec<PaletteAction> {
+        // a query that is a pid offers to jump to that process
+        let jump = query
+            .trim()
+            .parse()
+            .ok()
+            .map(|pid| PaletteAction::JumpToPid(Pid::from_raw(pid)));
+
+        let mut entries = PaletteAction::ALL
+            .iter()
+            .copied()
+            .filter(|&action| self.palette_action_available(action))
+            .filter_map(|action| Some((fuzzy_score(query, &action.name())?, action)))
+            .collect_vec();
+        entries.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+
+        jump.filter(|&action| self.palette_action_available(action))
+            .into_iter()
+            .chain(entries.into_iter().map(|(_, action)| action))
+            .collect()
+    }
+
+    fn run_palette_action(&mut self, ui: &egui::Ui, action: PaletteAction) {
+        match action {
+            PaletteAction::ToggleSidePanel => self.panel_state.side_panel_open = !self.panel_state.side_panel_open,
+            PaletteAction::ToggleTheme => toggle_theme(ui.ctx()),
+            PaletteAction::ToggleThreads => self.show_threads = !self.show_threads,
+            PaletteAction::ToggleCriticalPath => self.highlight_critical_path = !self.highlight_critical_path,
+            PaletteAction::ToggleFocus => self.focus_selected = !self.focus_selected,
+            PaletteAction::ToggleProcessGroups => self.tint_process_groups = !self.tint_process_groups,
+            PaletteAction::ToggleConcurrency => self.show_concurrency = !self.show_concurrency,
+            PaletteAction::ToggleAbsoluteTimes => self.show_absolute_times = !self.show_absolute_times,
+            PaletteAction::ToggleFollowTail => self.follow_tail = !self.follow_tail,
+            PaletteAction::ToggleIdleGaps => self.show_idle_gaps = !self.show_idle_gaps,
+            PaletteAction::ToggleReplay => self.scrub_enabled = !self.scrub_enabled,
+            PaletteAction::ResetZoom => {
+                self.zoom_linear = Vec2::ZERO;
+                self.zoom_auto_hor = true;
+            }
+            PaletteAction::ClearSelection => self.selected_pids.clear(),
+            PaletteAction::Rerun => self.rerun(),
+            PaletteAction::Layout(mode) => {
+                self.layout_mode = mode;
+                let mut layout_settings = self.layout_settings.lock().unwrap();
+                layout_settings.mode = mode;
+                if let Some(data) = &mut self.data {
+                    data.relayout(*layout_settings);
+                }
+            }
+            PaletteAction::Color(mode) => self.color_settings.mode = mode,
+            PaletteAction::JumpToPid(pid) => self.select_and_scroll_to(ui, pid),
+        }
+    }
+
+    // show the command palette if it's open: a filtered list of actions, navigated with the arrow keys
+    fn show_palette(&mut self, ctx: &Context) {
+        let Some(mut palette) = self.palette.take() else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new("Command palette")
+            .id(egui::Id::new("command_palette"))
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .anchor(Align2::CENTER_TOP, Vec2::new(0.0, PALETTE_OFFSET))
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut palette.query)
+                        .hint_text("Type a command or pid")
+                        .desired_width(PALETTE_WIDTH),
+                )
+                .request_focus();
+
+                let entries = self.palette_entries(&palette.query);
+                let (up, down, enter, escape) = ui.input(|input| {
+                    (
+                        input.key_pressed(Key::ArrowUp),
+                        input.key_pressed(Key::ArrowDown),
+                        input.key_pressed(Key::Enter),
+                        input.key_pressed(Key::Escape),
+                    )
+                });
+                if down {
+                    palette.selected += 1;
+                }
+                if up {
+                    palette.selected = palette.selected.saturating_sub(1);
+                }
+                palette.selected = palette.selected.min(entries.len().saturating_sub(1));
+
+                let mut chosen = None;
+                ScrollArea::vertical().max_height(PALETTE_LIST_HEIGHT).show(ui, |ui| {
+                    for (i, &action) in enumerate(&entries) {
+                        let response = ui.selectable_label(i == palette.selected, action.name());
+                        if i == palette.selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            chosen = Some(action);
+                        }
+                    }
+                });
+                if enter {
+                    chosen = chosen.or(entries.get(palette.selected).copied());
+                }
+
+                if let Some(action) = chosen {
+                    self.run_palette_action(ui, action);
+                }
+                if chosen.is_some() || escape {
+                    open = false;
+                }
+            });
+
+        if open {
+            self.palette = Some(palette);
         }
     }
 }
 
+// toggle between the dark and light theme
+fn toggle_theme(ctx: &Context) {
+    let theme = if ctx.style().visuals.dark_mode {
+        Theme::Light
+    } else {
+        Theme::Dark
+    };
+    ctx.set_theme(theme);
+}
+
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, PANEL_STATE_KEY, &self.panel_state);
+        eframe::set_value(storage, ZOOM_SETTINGS_KEY, &self.zoom_settings);
+    }
+
     fn update(&mut self, ctx: &Context, _: &mut Frame) {
         // try getting new data
-        if let Some(new_data) = self.data_to_gui.lock().unwrap().take() {
+        let new_data = self.data_to_gui.lock().unwrap().take();
+        if let Some(new_data) = new_data {
+            // new data arrives at the layout frequency, often without the recording having changed
+            if self.derived_revision != Some(new_data.recording.revision()) {
+                self.derive_data(&new_data.recording);
+            }
             self.data = Some(new_data);
         }
 
-        SidePanel::right("side_panel").show(ctx, |ui| {
-            ScrollArea::vertical().show(ui, |ui| {
-                ui.take_available_space();
+        // toggle between the dark and light theme, unless the key is meant for a text field
+        if !ctx.wants_keyboard_input() && ctx.input(|input| input.key_pressed(Key::T)) {
+            toggle_theme(ctx);
+        }
 
-                ui.heading("Settings");
-                global_theme_switch(ui);
-                ui.checkbox(&mut self.show_threads, "Show threads");
+        // open or close the command palette
+        if ctx.input_mut(|input| input.consume_key(Modifiers::COMMAND, Key::P)) {
+            self.palette = match self.palette {
+                None => Some(PaletteState::default()),
+                Some(_) => None,
+            };
+        }
 
-                ui.separator();
-                ui.heading("Colors");
-                ui.add(egui::Slider::new(&mut self.color_settings.hue_sat, 0.0..=1.0).text("Hue saturation"));
+        // advance the scrubber in real time
+        let finished_time_end = self.finished_time_end();
+        if finished_time_end.is_none() {
+            self.scrub_enabled = false;
+        }
+        if !self.scrub_enabled {
+            self.scrub_playing = false;
+        }
+        if self.scrub_playing
+            && let Some(time_end) = finished_time_end
+        {
+            self.scrub_time += ctx.input(|input| input.stable_dt) as f64;
+            if self.scrub_time >= time_end {
+                self.scrub_time = time_end;
+                self.scrub_playing = false;
+            }
+            ctx.request_repaint();
+        }
 
-                let mut add_value_sliders = |kind: &str, values: &mut ColorValues| {
-                    ui.add(egui::Slider::new(&mut values.header, 0.0..=1.0).text(format!("{kind } value header")));
-                    ui.add(
-                        egui::Slider::new(&mut values.background, 0.0..=1.0).text(format!("{kind } value background")),
-                    );
-                    ui.add(egui::Slider::new(&mut values.stroke, 0.0..=1.0).text(format!("{kind } value stroke")));
+        TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                let text = if self.panel_state.side_panel_open {
+                    "Hide side panel"
+                } else {
+                    "Show side panel"
                 };
-                add_value_sliders("Dark", &mut self.color_settings.val_dark);
-                add_value_sliders("Light", &mut self.color_settings.val_light);
-
-                ui.separator();
-                ui.heading("Selected process info");
-                ui.label(self.selected_pid_info());
+                if ui.button(text).clicked() {
+                    self.panel_state.side_panel_open = !self.panel_state.side_panel_open;
+                }
             });
         });
 
+        if self.panel_state.side_panel_open {
+            let response = SidePanel::right("side_panel")
+                .resizable(true)
+                .default_width(self.panel_state.side_panel_width)
+                .show(ctx, |ui| {
+                    ScrollArea::vertical().show(ui, |ui| {
+                        ui.take_available_space();
+
+                        ui.heading("Status");
+                        ui.label(self.status_text());
+                        if self.rerun_tx.is_some()
+                            && ui
+                                .button("Re-run")
+                                .on_hover_text("Stop the command if it's still running and start it again")
+                                .clicked()
+                        {
+                            self.rerun();
+                        }
+
+                        ui.separator();
+                        ui.heading("Settings");
+                        global_theme_switch(ui);
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut self.show_threads, false, "Processes");
+                            ui.selectable_value(&mut self.show_threads, true, "Processes + threads");
+                            if !self.show_threads
+                                && let Some(data) = &self.data
+                            {
+                                let hidden = data.recording.total_counts().threads;
+                                if hidden > 0 {
+                                    ui.weak(format!("+{hidden} threads hidden"));
+                                }
+                            }
+                        });
+                        ui.checkbox(
+                            &mut self.highlight_same_executable,
+                            "Highlight same executable on hover",
+                        );
+                        ui.checkbox(&mut self.show_absolute_times, "Show absolute times");
+                        ui.checkbox(&mut self.follow_tail, "Follow live tail");
+                        ui.checkbox(&mut self.highlight_critical_path, "Highlight critical path");
+                        // clearing the selection disables focus mode
+                        if self.selected_pids.is_empty() {
+                            self.focus_selected = false;
+                        }
+                        ui.add_enabled(
+                            !self.selected_pids.is_empty(),
+                            egui::Checkbox::new(&mut self.focus_selected, "Focus on selected subtree"),
+                        );
+                        ui.checkbox(&mut self.tint_process_groups, "Tint process groups");
+                        ui.checkbox(&mut self.show_concurrency, "Show concurrency chart");
+                        ui.checkbox(&mut self.show_idle_gaps, "Show idle gaps of selected process");
+                        ui.add(egui::Slider::new(&mut self.min_bar_width, 0.0..=8.0).text("Minimum bar width (px)"));
+
+                        // scrubbing is only possible once the recording is finished
+                        ui.add_enabled_ui(finished_time_end.is_some(), |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.scrub_enabled, "Replay");
+                                let play_text = if self.scrub_playing { "Pause" } else { "Play" };
+                                if ui
+                                    .add_enabled(self.scrub_enabled, egui::Button::new(play_text))
+                                    .clicked()
+                                {
+                                    // restart from the beginning if we're at the end
+                                    if !self.scrub_playing
+                                        && finished_time_end.is_some_and(|end| self.scrub_time >= end)
+                                    {
+                                        self.scrub_time = 0.0;
+                                    }
+                                    self.scrub_playing = !self.scrub_playing;
+                                }
+                            });
+                            let time_end = finished_time_end.unwrap_or(0.0);
+                            let slider =
+                                egui::Slider::new(&mut self.scrub_time, 0.0..=time_end).text("Replay time (s)");
+                            ui.add_enabled(self.scrub_enabled, slider);
+                        });
+
+                        let mut label_changed = false;
+                        egui::ComboBox::from_label("Process label")
+                            .selected_text(label_mode_name(self.label_mode))
+                            .show_ui(ui, |ui| {
+                                for mode in [LabelMode::Basename, LabelMode::FullPath, LabelMode::Argv0] {
+                                    let response =
+                                        ui.selectable_value(&mut self.label_mode, mode, label_mode_name(mode));
+                                    label_changed |= response.changed();
+                                }
+                            });
+                        if label_changed && let Some(data) = &self.data {
+                            self.stats = executable_stats(&data.recording, self.label_mode);
+                        }
+
+                        let mut layout_changed = false;
+                        egui::ComboBox::from_label("Layout")
+                            .selected_text(layout_mode_name(self.layout_mode))
+                            .show_ui(ui, |ui| {
+                                for mode in [LayoutMode::Packed, LayoutMode::Lanes, LayoutMode::Icicle] {
+                                    let response =
+                                        ui.selectable_value(&mut self.layout_mode, mode, layout_mode_name(mode));
+                                    layout_changed |= response.changed();
+                                }
+                            });
+                        let response = ui.add(
+                            egui::Slider::new(&mut self.min_duration_ms, 0.0..=10_000.0)
+                                .logarithmic(true)
+                                .text("Hide processes shorter than (ms)"),
+                        );
+                        layout_changed |= response.changed();
+                        ui.horizontal(|ui| {
+                            layout_changed |= ui.checkbox(&mut self.max_depth_enabled, "Limit depth").changed();
+                            let slider = egui::Slider::new(&mut self.max_depth, 1..=64).text("Max depth");
+                            layout_changed |= ui.add_enabled(self.max_depth_enabled, slider).changed();
+                        });
+
+                        if layout_changed {
+                            let layout_settings = LayoutSettings {
+                                min_duration: self.min_duration_ms / 1000.0,
+                                max_depth: self.max_depth_enabled.then_some(self.max_depth),
+                                mode: self.layout_mode,
+                            };
+                            *self.layout_settings.lock().unwrap() = layout_settings;
+                            if let Some(data) = &mut self.data {
+                                data.relayout(layout_settings);
+                            }
+                        }
+
+                        ui.separator();
+                        ui.heading("Colors");
+                        egui::ComboBox::from_label("Color by")
+                            .selected_text(color_mode_name(self.color_settings.mode))
+                            .show_ui(ui, |ui| {
+                                for mode in [ColorMode::Executable, ColorMode::CpuUtilization, ColorMode::Subtree] {
+                                    ui.selectable_value(&mut self.color_settings.mode, mode, color_mode_name(mode));
+                                }
+                            });
+                        if self.color_settings.mode == ColorMode::CpuUtilization {
+                            self.show_utilization_legend(ui);
+                        }
+                        ui.add(egui::Slider::new(&mut self.color_settings.hue_sat, 0.0..=1.0).text("Hue saturation"));
+
+                        let mut add_value_sliders = |kind: &str, values: &mut ColorValues| {
+                            ui.add(
+                                egui::Slider::new(&mut values.header, 0.0..=1.0).text(format!("{kind } value header")),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut values.background, 0.0..=1.0)
+                                    .text(format!("{kind } value background")),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut values.stroke, 0.0..=1.0).text(format!("{kind } value stroke")),
+                            );
+                        };
+                        add_value_sliders("Dark", &mut self.color_settings.val_dark);
+                        add_value_sliders("Light", &mut self.color_settings.val_light);
+
+                        ui.separator();
+                        CollapsingHeader::new("Advanced").show(ui, |ui| {
+                            let zoom = &mut self.zoom_settings;
+                            ui.horizontal(|ui| {
+                                ui.label("Mouse wheel");
+                                ui.selectable_value(&mut zoom.wheel_mode, WheelMode::Zoom, "Zoom");
+                                ui.selectable_value(&mut zoom.wheel_mode, WheelMode::Pan, "Pan");
+                            })
+                            .response
+                            .on_hover_text(
+                                "Hold Ctrl to zoom instead of pan, or to zoom vertically instead of horizontally",
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut zoom.hor, 10.0..=2000.0)
+                                    .logarithmic(true)
+                                    .text("Horizontal scale (px/s)"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut zoom.ver, 5.0..=100.0)
+                                    .logarithmic(true)
+                                    .text("Row height (px)"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut zoom.hor_exp, 10.0..=1000.0)
+                                    .logarithmic(true)
+                                    .text("Horizontal zoom sensitivity"),
+                            );
+                            ui.add(
+                                egui::Slider::new(&mut zoom.ver_exp, 10.0..=1000.0)
+                                    .logarithmic(true)
+                                    .text("Vertical zoom sensitivity"),
+                            );
+                            if ui.button("Reset").clicked() {
+                                *zoom = ZoomSettings::default();
+                            }
+                        });
+                        if self.data.as_ref().is_some_and(|data| data.diff.is_some()) {
+                            CollapsingHeader::new("Comparison").default_open(true).show(ui, |ui| {
+                                self.show_diff(ui);
+                            });
+                        }
+                        CollapsingHeader::new("Statistics").show(ui, |ui| {
+                            if let Some(data) = &self.data
+                                && !data.recording.invocation.is_empty()
+                            {
+                                let invocation = data
+                                    .recording
+                                    .invocation
+                                    .iter()
+                                    .map(|arg| arg.to_string_lossy())
+                                    .join(" ");
+                                ui.label(format!("command: {invocation}"));
+                            }
+                            if self.data.as_ref().is_some_and(|data| data.recording.truncated) {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    "⚠ truncated, processes past the limits were not recorded",
+                                );
+                            }
+                            Grid::new("stats_grid").striped(true).show(ui, |ui| {
+                                ui.strong("executable");
+                                ui.strong("count");
+                                ui.strong("time");
+                                ui.end_row();
+
+                                for stats in &self.stats {
+                                    ui.label(stats.name.to_string_lossy());
+                                    ui.label(stats.count.to_string());
+                                    ui.label(format!("{:.3}s", stats.total_time));
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                        CollapsingHeader::new("Critical path").show(ui, |ui| {
+                            let Some(data) = &self.data else {
+                                return;
+                            };
+                            Grid::new("critical_path_grid").striped(true).show(ui, |ui| {
+                                ui.strong("pid");
+                                ui.strong("executable");
+                                ui.strong("time");
+                                ui.end_row();
+
+                                for pid in &self.critical_path {
+                                    let Some(info) = data.recording.processes().get(pid) else {
+                                        continue;
+                                    };
+                                    ui.label(pid.to_string());
+                                    ui.label(info.label(self.label_mode).to_string_lossy());
+                                    ui.label(info.time.duration().map_or("?".to_owned(), |d| format!("{:.3}s", d)));
+                                    ui.end_row();
+                                }
+                            });
+                        });
+
+                        CollapsingHeader::new("Processes").show(ui, |ui| {
+                            self.show_process_list(ui);
+                        });
+                        CollapsingHeader::new("Process tree").show(ui, |ui| {
+                            self.show_process_tree(ui);
+                        });
+                        if self.data.as_ref().is_some_and(|data| !data.recording.output.is_empty()) {
+                            CollapsingHeader::new("Output").show(ui, |ui| {
+                                self.show_output(ui);
+                            });
+                        }
+
+                        if self.selected_pids.len() > 1 {
+                            ui.separator();
+                            ui.heading("Selection summary");
+                            ui.label(self.selection_summary_text());
+                        }
+
+                        ui.separator();
+                        ui.heading("Selected process info");
+                        self.show_ancestry(ui);
+                        self.show_exec_list(ui);
+                        CollapsingHeader::new("Argv diff").show(ui, |ui| {
+                            self.show_argv_diff(ui);
+                        });
+                        ui.label(self.selected_pid_info());
+                    });
+                });
+            self.panel_state.side_panel_width = response.response.rect.width();
+        }
+
+        TopBottomPanel::bottom("minimap")
+            .exact_height(MINIMAP_HEIGHT)
+            .show(ctx, |ui| {
+                if let Some((recording, root_placed)) = self.data.as_ref().and_then(|d| d.placed(self.show_threads))
+                    && let Some(target) = self.show_minimap(ui, recording, root_placed)
+                {
+                    self.timeline_scroll_target = Some(target);
+                }
+            });
+
+        if self.show_concurrency && self.layout_mode != LayoutMode::Icicle {
+            TopBottomPanel::top("concurrency")
+                .exact_height(CONCURRENCY_HEIGHT)
+                .show(ctx, |ui| {
+                    if let Some((recording, root_placed)) = self.data.as_ref().and_then(|d| d.placed(self.show_threads))
+                    {
+                        self.show_concurrency(ui, recording, root_placed);
+                    }
+                });
+        }
+        if let Some(self_pid) = self.self_pid
+            && self.layout_mode != LayoutMode::Icicle
+        {
+            TopBottomPanel::top("self_process")
+                .exact_height(SELF_BAR_HEIGHT)
+                .show(ctx, |ui| {
+                    if let Some((recording, root_placed)) = self.data.as_ref().and_then(|d| d.placed(self.show_threads))
+                    {
+                        self.show_self_bar(ui, recording, root_placed, self_pid);
+                    }
+                });
+        }
+
+        self.show_note_editor(ctx);
+        self.show_palette(ctx);
+
         CentralPanel::default().show(ctx, |ui| {
             ScrollArea::both()
                 .scroll_bar_visibility(ScrollBarVisibility::AlwaysVisible)
@@ -125,10 +890,19 @@ impl eframe::App for App {
                 .show_viewport(ui, |ui, viewport| {
                     ui.take_available_space();
 
+                    // apply scroll requested by the minimap
+                    self.timeline_viewport = viewport;
+                    self.timeline_origin = ui.min_rect().min;
+                    if let Some(offset) = self.timeline_scroll_target.take() {
+                        ui.scroll_with_delta_animation(viewport.min - offset.to_pos2(), ScrollAnimation::none());
+                    }
+
                     let Some(DataToGui {
                         recording,
                         placed_threads_no,
                         placed_threads_yes,
+                        error,
+                        diff: _,
                     }) = &self.data
                     else {
                         return;
@@ -139,16 +913,39 @@ impl eframe::App for App {
                         placed_threads_no
                     };
                     let Some(root_placed) = root_placed else {
+                        show_empty_state(ui, recording, error.as_deref());
                         return;
                     };
 
+                    // use the hover state of the previous frame for highlighting
+                    let hovered_name = self
+                        .hovered_pid
+                        .and_then(|pid| recording.processes().get(&pid))
+                        .map(|info| info.label(self.label_mode));
+
                     self.hovered_pid = None;
-                    if let Some(timeline_info) = self.show_timeline(ui, recording, root_placed) {
+                    let focus_pid = self.selected_pids.last().copied().filter(|_| self.focus_selected);
+                    if let Some(timeline_info) = self.show_timeline(ui, recording, root_placed, hovered_name, focus_pid)
+                    {
                         // handle hover/click
                         if let Some(pointer_pid_info) = timeline_info.pointer_pid_info {
                             self.hovered_pid = Some(pointer_pid_info.pid);
                             if pointer_pid_info.clicked {
-                                self.selected_pid = Some(pointer_pid_info.pid);
+                                // ctrl-click toggles the process, a normal click replaces the selection
+                                let pid = pointer_pid_info.pid;
+                                if ui.input(|input| input.modifiers.ctrl) {
+                                    if !self.selected_pids.shift_remove(&pid) {
+                                        self.selected_pids.insert(pid);
+                                    }
+                                } else {
+                                    self.selected_pids.clear();
+                                    self.selected_pids.insert(pid);
+                                }
+                            }
+                            if pointer_pid_info.secondary_clicked {
+                                let pid = pointer_pid_info.pid;
+                                let note = self.notes.lock().unwrap().get(&pid).cloned().unwrap_or_default();
+                                self.note_edit = Some((pid, note));
                             }
                         }
 
@@ -156,8 +953,18 @@ impl eframe::App for App {
                         if self.zoom_auto_hor {
                             let factor = viewport.width() / timeline_info.bounding_box.width();
                             if factor.is_finite() && (1.0 - factor).abs() > 0.0001 {
-                                self.zoom_linear.x += zoom_factor_to_linear(factor, true);
+                                self.zoom_linear.x += self.zoom_settings.factor_to_linear(factor, true);
+                            }
+                        }
+
+                        // handle live tail following
+                        let running = root_placed.time_bound.end.or(recording.time_end).is_none();
+                        if self.follow_tail && running {
+                            if let Some(delta) = self.follow_tail_delta(viewport, timeline_info.bounding_box.max.x) {
+                                ui.scroll_with_delta_animation(Vec2::new(delta, 0.0), ScrollAnimation::none());
                             }
+                        } else {
+                            self.follow_tail_offset = None;
                         }
                     }
 
@@ -173,20 +980,22 @@ impl eframe::App for App {
                             )
                         });
 
-                        // manual zoom
-                        let scroll_delta = if mod_ctrl {
-                            raw_scroll_delta
-                        } else {
-                            raw_scroll_delta.yx()
-                        };
+                        // manual zoom and pan
+                        let action = wheel_action(self.zoom_settings.wheel_mode, raw_scroll_delta, mod_ctrl);
+                        let scroll_delta = action.zoom;
+                        if action.pan != Vec2::ZERO {
+                            ui.scroll_with_delta_animation(action.pan, ScrollAnimation::none());
+                        }
                         let zoom_linear_before = self.zoom_linear.x;
                         self.zoom_linear += scroll_delta;
 
                         // pan to keep cursor centered
                         // (using some empirical formulas, reasoning about zoom/pan is hard)
-                        if let Some(pointer_pos) = pointer_pos {
-                            let zoom_factor_before = zoom_linear_to_factor(zoom_linear_before, true);
-                            let zoom_factor_after = zoom_linear_to_factor(self.zoom_linear.x, true);
+                        if let Some(pointer_pos) = pointer_pos
+                            && scroll_delta.x != 0.0
+                        {
+                            let zoom_factor_before = self.zoom_settings.linear_to_factor(zoom_linear_before, true);
+                            let zoom_factor_after = self.zoom_settings.linear_to_factor(self.zoom_linear.x, true);
 
                             let p_delta = (pointer_pos - ui.min_rect().min).x;
                             let p_delta_before = p_delta / zoom_factor_before;
@@ -218,66 +1027,137 @@ struct TimeLineInfo {
 struct PointerPidInfo {
     pid: Pid,
     clicked: bool,
+    secondary_clicked: bool,
 }
 
 impl App {
-    fn show_timeline(
+    fn timeline_rect_params(
         &self,
-        ui: &mut egui::Ui,
+        ui: &egui::Ui,
         recording: &Recording,
         root_placed: &PlacedProcess,
-    ) -> Option<TimeLineInfo> {
+    ) -> Option<(ProcRectParams, Rect)> {
         // decide current time, used to extend unfinished process ends
-        let total_time_end = match root_placed.time_bound.end.or(recording.time_end) {
+        let total_time_end = match self
+            .scrub_cursor()
+            .or(root_placed.time_bound.end)
+            .or(recording.time_end)
+        {
             Some(time_end) => time_end,
-            None => {
-                ui.ctx().request_repaint();
-
-                let time_start = recording.time_start?;
-                time_start.elapsed().as_secs_f32()
-            }
+            None => match recording.time_start {
+                // live recordings keep advancing even without new events
+                Some(time_start) => {
+                    ui.ctx().request_repaint();
+                    time_start.elapsed().as_secs_f64()
+                }
+                // loaded recordings that didn't finish end at the newest event
+                None => recording.time_latest?,
+            },
         };
 
-        // first pass: compute bounding box
-        let rect_params = ProcRectParams::new(total_time_end, self.zoom_linear);
+        // compute bounding box
+        let rect_params = ProcRectParams::new(total_time_end, self.zoom_linear, self.zoom_settings, self.min_bar_width);
         let mut bounding_box = Rect::NOTHING;
         root_placed.visit(
             |_, _| ControlFlow::Continue(()),
             |placed, row, ()| {
-                let proc_rect = rect_params.proc_rect(placed.time_bound, row, placed.row_height);
-                bounding_box |= proc_rect;
+                if let Some(time_bound) = self.scrub(placed.time_bound) {
+                    bounding_box |= rect_params.proc_rect(time_bound, row, placed.row_height);
+                }
             },
         );
 
+        Some((rect_params, bounding_box))
+    }
+
+    fn show_timeline(
+        &self,
+        ui: &mut egui::Ui,
+        recording: &Recording,
+        root_placed: &PlacedProcess,
+        hovered_name: Option<&OsStr>,
+        focus_pid: Option<Pid>,
+    ) -> Option<TimeLineInfo> {
+        // first pass: compute bounding box
+        let (rect_params, bounding_box) = self.timeline_rect_params(ui, recording, root_placed)?;
+
         // allocate space and create painter
         let (response, painter) = ui.allocate_painter(bounding_box.size(), Sense::click());
         let offset = response.rect.min.to_vec2();
 
-        // figure out a minimum text width to early-skip text layout
-        let text_font = &FontId::default();
+        // measure each unique label only once per frame, all headers use the same font
+        let text_font = &rect_params.header_font();
         let text_color = ui.visuals().text_color();
-        let text_min_char_width = painter
-            .layout_no_wrap("l".to_owned(), text_font.clone(), text_color)
-            .size()
-            .x;
+        let galleys: RefCell<HashMap<String, Arc<Galley>>> = RefCell::new(HashMap::new());
+        let layout_text = |text: &str| -> Arc<Galley> {
+            let mut galleys = galleys.borrow_mut();
+            if let Some(galley) = galleys.get(text) {
+                return galley.clone();
+            }
+            let galley = painter.layout_no_wrap(text.to_owned(), text_font.clone(), text_color);
+            galleys.insert(text.to_owned(), galley.clone());
+            galley
+        };
+        let text_gap = layout_text(" ").size().x;
 
         // second pass: actually paint (and collect click events)
+        let notes = self.notes.lock().unwrap();
+        let icicle = self.layout_mode == LayoutMode::Icicle;
         let mut pointer_pid_info = None;
         let stoken_width = 1.0;
 
+        // the periods in which the last selected process had no active descendants
+        let idle_gaps = match self.selected_pids.last() {
+            Some(&pid) if self.show_idle_gaps && !icicle => {
+                let gaps = idle_gaps(recording, pid, rect_params.total_time_end);
+                Some((pid, gaps.into_iter().filter_map(|gap| self.scrub(gap)).collect_vec()))
+            }
+            _ => None,
+        };
+
+        // draw the text if it fits in the rectangle, and the right-aligned suffix if both fit
+        let draw_text = |rect: Rect, text: &str, suffix: Option<&str>| {
+            let padding = Vec2::new(stoken_width * 2.0, 0.0);
+            let galley = layout_text(text);
+            let Some(rect_text) = label_rect(rect, galley.rect, padding) else {
+                return;
+            };
+            painter.galley(rect_text.min, galley, text_color);
+
+            if let Some(suffix) = suffix {
+                let galley = layout_text(suffix);
+                let rect_suffix = galley
+                    .rect
+                    .translate(rect.right_top().to_vec2() - padding - Vec2::new(galley.rect.width(), 0.0));
+                if rect.contains_rect(rect_suffix) && rect_suffix.min.x >= rect_text.max.x + text_gap {
+                    painter.galley(rect_suffix.min, galley, text_color);
+                }
+            }
+        };
+
         root_placed.visit(
             // before: draw background/header and handle interactions
             |placed, row| {
-                let proc = recording.processes.get(&placed.pid).unwrap();
+                let proc = recording.processes().get(&placed.pid).unwrap();
 
-                // calculate bounding rects and skip if not visible
+                // calculate bounding rects and skip if not visible (or not started yet when replaying)
+                // in icicle mode the time bound is an aggregated extent, not related to the process time
+                let proc_time = if icicle { placed.time_bound } else { proc.time };
+                let (Some(time_bound), Some(proc_time)) = (self.scrub(placed.time_bound), self.scrub(proc_time)) else {
+                    return ControlFlow::Break(());
+                };
                 let rect_full = rect_params
-                    .proc_rect(placed.time_bound, row, placed.row_height)
+                    .proc_rect(time_bound, row, placed.row_height)
                     .translate(offset);
                 if !ui.is_rect_visible(rect_full) || rect_full.width() < 0.5 {
                     return ControlFlow::Break(());
                 }
-                let rect_header = rect_params.proc_rect(proc.time, row, 1).translate(offset);
+                let mut rect_header = rect_params.proc_rect(proc_time, row, 1).translate(offset);
+
+                // threads get a thinner header to distinguish them from processes
+                if placed.kind == ProcessKind::Thread {
+                    rect_header.max.y = rect_header.min.y + rect_header.height() * THREAD_HEADER_FRACTION;
+                }
 
                 // handle hover/click
                 let pointer_in_rect = ui.rect_contains_pointer(rect_full);
@@ -285,19 +1165,43 @@ impl App {
                     pointer_pid_info = Some(PointerPidInfo {
                         pid: proc.pid,
                         clicked: response.clicked_by(PointerButton::Primary),
+                        secondary_clicked: response.clicked_by(PointerButton::Secondary),
                     });
                 }
 
                 // figure out text, it influences the color
-                let text = proc.execs.last().map(|exec| exec.path.as_str()).unwrap_or("?");
-                let text = text.rsplit_once("/").map(|(_, s)| s).unwrap_or(text);
+                let name = proc.label(self.label_mode);
+                let text = name.to_string_lossy();
+                let mut text_header = match placed.merged {
+                    1 => text.to_string(),
+                    merged => format!("{} ×{}", text, merged),
+                };
+                let killed_by = proc.exit_signal.filter(|_| !icicle);
+                if let Some(signal) = killed_by {
+                    swrite!(text_header, " [{}]", signal.name());
+                }
 
-                let colors = get_process_color(&self.color_settings, ui.visuals().dark_mode, text);
-                let stroke_color = if pointer_in_rect || self.selected_pid == Some(proc.pid) {
+                let colors = get_process_color(
+                    &self.color_settings,
+                    ui.visuals().dark_mode,
+                    &text,
+                    recording.cpu_utilization(proc.pid),
+                    self.subtree_index(recording, proc.pid),
+                );
+                let same_executable = self.is_same_executable(name, hovered_name);
+                let stroke_color = if pointer_in_rect || same_executable || self.selected_pids.contains(&proc.pid) {
                     text_color
+                } else if self.highlight_critical_path && self.critical_path.contains(&proc.pid) {
+                    CRITICAL_PATH_COLOR
                 } else {
                     colors.stroke
                 };
+                let dimmed = focus_pid.is_some_and(|focus_pid| !recording.is_descendant(proc.pid, focus_pid));
+                let stroke_color = if dimmed {
+                    stroke_color.gamma_multiply(1.0 - FOCUS_DIM_ALPHA)
+                } else {
+                    stroke_color
+                };
 
                 // draw rects
                 painter.rect(
@@ -307,6 +1211,13 @@ impl App {
                     Stroke::NONE,
                     StrokeKind::Inside,
                 );
+                if self.tint_process_groups
+                    && let Some(group_index) = proc.pgid.and_then(|pgid| self.process_groups.get_index_of(&pgid))
+                {
+                    let hue = get_subtree_hue(group_index);
+                    let tint = Color32::from(Hsva::new(hue, 1.0, 1.0, 1.0)).gamma_multiply(PROCESS_GROUP_TINT_ALPHA);
+                    painter.rect_filled(rect_full, CornerRadiusF32::ZERO, tint);
+                }
                 painter.rect(
                     rect_header,
                     CornerRadiusF32::ZERO,
@@ -315,21 +1226,135 @@ impl App {
                     StrokeKind::Inside,
                 );
 
-                // draw the text if it fits in the rectangle
-                if rect_header.width() >= text_min_char_width * (text.len() as f32) {
-                    let galley = painter.layout_no_wrap(text.to_owned(), text_font.clone(), text_color);
-                    let rect_text = galley
-                        .rect
-                        .translate(rect_header.min.to_vec2() + Vec2::new(stoken_width * 2.0, 0.0));
-                    if rect_header.contains_rect(rect_text) {
-                        painter.galley(rect_text.min, galley, text_color);
+                // shade the periods the process was suspended in vfork, so they don't look like it was busy itself
+                for &blocked in proc.vfork_blocked.iter().filter(|_| !icicle) {
+                    if let Some(blocked) = self.scrub(blocked) {
+                        let rect_blocked = rect_params.proc_rect(blocked, row, 1).translate(offset);
+                        painter.rect_filled(
+                            rect_blocked.intersect(rect_header),
+                            CornerRadiusF32::ZERO,
+                            VFORK_BLOCKED_COLOR.gamma_multiply(VFORK_BLOCKED_ALPHA),
+                        );
                     }
                 }
 
+                // draw exec dividers and highlight
+                for (exec_time, x) in rect_params.exec_dividers(&proc.execs).filter(|_| !icicle) {
+                    if self.scrub(exec_time).is_none() {
+                        continue;
+                    }
+                    painter.vline(
+                        x + offset.x,
+                        rect_header.y_range(),
+                        Stroke::new(stoken_width, colors.stroke),
+                    );
+                }
+                if let Some((exec_pid, exec_index)) = self.hovered_exec.or(self.selected_exec)
+                    && !icicle
+                    && exec_pid == proc.pid
+                    && let Some(exec_time) = self.scrub(proc.exec_time(exec_index))
+                {
+                    let rect_exec = rect_params.proc_rect(exec_time, row, 1).translate(offset);
+                    painter.rect_stroke(
+                        rect_exec,
+                        CornerRadiusF32::ZERO,
+                        Stroke::new(stoken_width * 2.0, text_color),
+                        StrokeKind::Inside,
+                    );
+                }
+
+                // outline processes killed by a signal, eg. crashes or the OOM killer
+                if killed_by.is_some() {
+                    painter.rect_stroke(
+                        rect_header,
+                        CornerRadiusF32::ZERO,
+                        Stroke::new(stoken_width * 2.0, SIGNAL_KILLED_COLOR),
+                        StrokeKind::Inside,
+                    );
+                }
+
+                // icicle headers span aggregated time, their width doesn't match the duration
+                let duration = proc_time.duration().filter(|_| !icicle).map(format_duration);
+                draw_text(rect_header, &text_header, duration.as_deref());
+
+                // mark processes that run in other namespaces than the root, eg. inside a container
+                if recording.in_other_namespace(proc.pid) {
+                    let marker = Rect::from_min_size(
+                        rect_header.min + Vec2::splat(stoken_width),
+                        Vec2::splat(NAMESPACE_MARKER_SIZE),
+                    );
+                    painter.rect(
+                        marker,
+                        CornerRadiusF32::ZERO,
+                        NAMESPACE_MARKER_COLOR,
+                        Stroke::new(stoken_width, text_color),
+                        StrokeKind::Inside,
+                    );
+                }
+
+                // warn about processes that changed credentials, eg. setuid binaries, their info might be incomplete
+                if recording.credentials_changed(proc.pid) {
+                    let pos = rect_header.min + Vec2::new(NAMESPACE_MARKER_SIZE + stoken_width * 2.0, 0.0);
+                    painter.text(
+                        pos,
+                        Align2::LEFT_TOP,
+                        "⚠",
+                        FontId::proportional(CREDENTIALS_MARKER_SIZE),
+                        CREDENTIALS_MARKER_COLOR,
+                    );
+                }
+
+                // mark processes that have a note
+                if notes.contains_key(&proc.pid) {
+                    let center = Pos2::new(
+                        rect_header.max.x - NOTE_MARKER_RADIUS - stoken_width,
+                        rect_header.min.y + NOTE_MARKER_RADIUS + stoken_width,
+                    );
+                    painter.circle(
+                        center,
+                        NOTE_MARKER_RADIUS,
+                        NOTE_MARKER_COLOR,
+                        Stroke::new(stoken_width, text_color),
+                    );
+                }
+
+                // draw a single bar for the descendants hidden by the depth limit
+                if let Some(folded) = &placed.folded
+                    && let Some(folded_time) = self.scrub(folded.time)
+                {
+                    let rect_folded = rect_params.proc_rect(folded_time, row + 1, 1).translate(offset);
+                    painter.rect(
+                        rect_folded,
+                        CornerRadiusF32::ZERO,
+                        colors.header,
+                        Stroke::new(stoken_width, colors.stroke),
+                        StrokeKind::Inside,
+                    );
+                    draw_text(rect_folded, &format!("+{} hidden", folded.count), None);
+                }
+
+                // dim processes outside the focused subtree, children are drawn later and dim themselves if needed
+                if dimmed {
+                    let dim_color = ui.visuals().panel_fill.gamma_multiply(FOCUS_DIM_ALPHA);
+                    painter.rect_filled(rect_full, CornerRadiusF32::ZERO, dim_color);
+                }
+
                 ControlFlow::Continue((rect_full, stroke_color))
             },
-            // after: draw background stroke, on top of any children
-            |_, _, (rect_full, stroke_color)| {
+            // after: draw idle gaps and background stroke, on top of any children
+            |placed, row, (rect_full, stroke_color)| {
+                if let Some((gaps_pid, gaps)) = &idle_gaps
+                    && *gaps_pid == placed.pid
+                {
+                    for &gap in gaps {
+                        let rect_gap = rect_params.proc_rect(gap, row, placed.row_height).translate(offset);
+                        painter.rect_filled(
+                            rect_gap,
+                            CornerRadiusF32::ZERO,
+                            IDLE_GAP_COLOR.gamma_multiply(IDLE_GAP_ALPHA),
+                        );
+                    }
+                }
                 painter.rect_stroke(
                     rect_full,
                     CornerRadiusF32::ZERO,
@@ -339,63 +1364,1063 @@ impl App {
             },
         );
 
+        // show the note of the hovered process, or otherwise the output written around the hovered time
+        if let Some(info) = &pointer_pid_info
+            && let Some(note) = notes.get(&info.pid)
+        {
+            response.on_hover_text(note);
+        } else if !recording.output.is_empty()
+            && let Some(pointer) = response.hover_pos()
+        {
+            let time = rect_params.time_at(pointer.x - offset.x);
+            let radius = rect_params.time_at(OUTPUT_HOVER_RADIUS);
+            let text = recording
+                .output
+                .iter()
+                .filter(|chunk| (chunk.time - time).abs() <= radius)
+                .map(|chunk| chunk.text.to_string_lossy())
+                .join("");
+            if !text.is_empty() {
+                response.on_hover_text(egui::RichText::new(text.trim_end()).monospace());
+            }
+        }
+
         Some(TimeLineInfo {
             bounding_box,
             pointer_pid_info,
         })
     }
 
-    fn selected_pid_info(&self) -> String {
-        // figure out which pid to show info for
-        let pid = self
-            .hovered_pid
-            .or(self.selected_pid)
-            .or_else(|| self.data.as_ref().and_then(|d| d.recording.root_pid));
-        let Some(pid) = pid else {
-            return "".to_owned();
+    // show the number of concurrently active processes and threads over time, aligned with the timeline
+    fn show_concurrency(&self, ui: &mut egui::Ui, recording: &Recording, root_placed: &PlacedProcess) {
+        let Some((rect_params, _)) = self.timeline_rect_params(ui, recording, root_placed) else {
+            return;
+        };
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+        let rect = response.rect;
+
+        let show_threads = self.show_threads;
+        let max_count = self
+            .concurrency
+            .processes
+            .iter()
+            .chain(show_threads.then_some(&self.concurrency.threads).into_iter().flatten())
+            .map(|&(_, count)| count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let time_to_x = |time: f64| {
+            let time = TimeRange {
+                start: time,
+                end: Some(time),
+            };
+            self.timeline_origin.x + rect_params.proc_rect(time, 0, 1).min.x
+        };
+        let count_to_y = |count: usize| rect.max.y - rect.height() * (count as f32 / max_count as f32);
+
+        // draw as steps, the last step extends until the current end time
+        let time_end = self
+            .scrub_cursor()
+            .or(root_placed.time_bound.end)
+            .unwrap_or(rect_params.total_time_end);
+        let step_rects = |steps: &[(f64, usize)]| {
+            let mut rects = vec![];
+            for (i, &(time, count)) in enumerate(steps) {
+                if time > time_end {
+                    break;
+                }
+                let next_time = steps.get(i + 1).map_or(time_end, |&(next, _)| next.min(time_end));
+                let step =
+                    Rect::from_x_y_ranges(time_to_x(time)..=time_to_x(next_time), count_to_y(count)..=rect.max.y);
+                if step.intersects(rect) {
+                    rects.push(step.intersect(rect));
+                }
+            }
+            rects
         };
 
-        // render info to string
-        const I: &str = "    ";
+        let color_processes = ui.visuals().selection.bg_fill;
+        for step in step_rects(&self.concurrency.processes) {
+            painter.rect_filled(step, CornerRadiusF32::ZERO, color_processes);
+        }
+        if show_threads {
+            let color_threads = ui.visuals().warn_fg_color;
+            for step in step_rects(&self.concurrency.threads) {
+                painter.hline(step.x_range(), step.min.y, Stroke::new(1.0, color_threads));
+            }
+        }
 
-        let mut text = String::new();
-        swriteln!(text, "pid: {}", pid);
+        painter.text(
+            rect.left_top(),
+            Align2::LEFT_TOP,
+            format!("max concurrency: {}", max_count),
+            FontId::default(),
+            ui.visuals().text_color(),
+        );
+    }
 
-        if let Some(data) = &self.data
-            && let Some(info) = data.recording.processes.get(&pid)
-        {
-            swriteln!(text, "time_start: {}", info.time.start);
-            swriteln!(text, "time_end: {:?}", info.time.end);
-            let duration = info.time.end.map(|time_end| time_end - info.time.start);
-            swriteln!(text, "duration: {:?}", duration);
+    // show our own process as a faint bar spanning the whole trace, aligned with the timeline below it
+    fn show_self_bar(&self, ui: &mut egui::Ui, recording: &Recording, root_placed: &PlacedProcess, self_pid: Pid) {
+        let Some((rect_params, _)) = self.timeline_rect_params(ui, recording, root_placed) else {
+            return;
+        };
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+        let rect = response.rect;
 
-            let child_counts = data.recording.child_counts(pid);
-            swriteln!(text, "children: {}", child_counts.processes);
-            swriteln!(text, "threads: {}", child_counts.threads);
+        let x_range = rect_params.trace_x_range();
+        let rect_bar = Rect::from_x_y_ranges(
+            (self.timeline_origin.x + x_range.min)..=(self.timeline_origin.x + x_range.max),
+            rect.y_range(),
+        );
 
-            swriteln!(text, "execs: {}", info.execs.len());
+        let text_color = ui.visuals().text_color();
+        painter.rect_filled(
+            rect_bar,
+            CornerRadiusF32::ZERO,
+            text_color.gamma_multiply(SELF_BAR_ALPHA),
+        );
 
-            for (i_exec, exec) in enumerate(&info.execs) {
-                swriteln!(text, "{I}{i_exec}");
+        let cpu_time = crate::poll::process_cpu_time(self_pid).map_or("?".to_owned(), format_duration);
+        painter.text(
+            Pos2::new(rect_bar.min.x.max(rect.min.x), rect.center().y),
+            Align2::LEFT_CENTER,
+            format!("wtf (pid {}, tracer overhead, cpu {})", self_pid, cpu_time),
+            FontId::default(),
+            ui.visuals().weak_text_color(),
+        );
+    }
 
-                swriteln!(text, "{I}{I}time: {}", exec.time);
-                swriteln!(text, "{I}{I}cwd: {}", exec.cwd.as_ref().map_or("?", String::as_str));
-                swriteln!(text, "{I}{I}path: {}", exec.path);
+    // show the entire timeline scaled to fit, with the current viewport as a rectangle on top
+    fn show_minimap(&self, ui: &mut egui::Ui, recording: &Recording, root_placed: &PlacedProcess) -> Option<Vec2> {
+        let (rect_params, bounding_box) = self.timeline_rect_params(ui, recording, root_placed)?;
 
-                swriteln!(text, "{I}{I}argv:");
-                for arg in &exec.argv {
-                    swriteln!(text, "{I}{I}{I}{}", arg);
-                }
-            }
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let transform = MinimapTransform {
+            content: Rect::from_min_max(Pos2::ZERO, bounding_box.max),
+            minimap: response.rect,
         };
 
-        text
-    }
-}
+        // draw simplified process rects
+        let dark_mode = ui.visuals().dark_mode;
+        root_placed.visit(
+            |placed, row| {
+                let Some(time_bound) = self.scrub(placed.time_bound) else {
+                    return ControlFlow::Break(());
+                };
+                let rect = transform.to_minimap(rect_params.proc_rect(time_bound, row, placed.row_height));
+                if rect.width() < 0.5 {
+                    return ControlFlow::Break(());
+                }
+
+                let name = recording.processes().get(&placed.pid).unwrap().label(self.label_mode);
+                let colors = get_process_color(
+                    &self.color_settings,
+                    dark_mode,
+                    &name.to_string_lossy(),
+                    recording.cpu_utilization(placed.pid),
+                    self.subtree_index(recording, placed.pid),
+                );
+                painter.rect_filled(rect, CornerRadiusF32::ZERO, colors.header);
+                ControlFlow::Continue(())
+            },
+            |_, _, ()| {},
+        );
+
+        // draw current viewport
+        painter.rect_stroke(
+            transform.to_minimap(self.timeline_viewport).intersect(response.rect),
+            CornerRadiusF32::ZERO,
+            Stroke::new(1.0, ui.visuals().text_color()),
+            StrokeKind::Inside,
+        );
+
+        // center the viewport on the clicked/dragged position
+        if response.clicked() || response.dragged() {
+            let pointer = response.interact_pointer_pos()?;
+            let target = transform.to_content(pointer);
+            let offset = target - self.timeline_viewport.size() / 2.0;
+            return Some(offset.to_vec2().max(Vec2::ZERO));
+        }
+        None
+    }
+
+    // the end time of the recording if it has finished
+    fn finished_time_end(&self) -> Option<f64> {
+        let (recording, root_placed) = self.data.as_ref()?.placed(self.show_threads)?;
+        root_placed.time_bound.end.or(recording.time_end)
+    }
+
+    // the time to replay the recording at, if replaying is enabled
+    fn scrub_cursor(&self) -> Option<f64> {
+        (self.scrub_enabled && self.layout_mode != LayoutMode::Icicle).then_some(self.scrub_time)
+    }
+
+    // the given time range as it looked at the current replay time, `None` if it hadn't started yet
+    fn scrub(&self, time: TimeRange) -> Option<TimeRange> {
+        match self.scrub_cursor() {
+            Some(cursor) => time.at_time(cursor),
+            None => Some(time),
+        }
+    }
+
+    // the index of the top-level subtree the given process belongs to, see `top_level_children`
+    fn subtree_index(&self, recording: &Recording, pid: Pid) -> Option<usize> {
+        let child = recording.top_level_child(pid)?;
+        self.top_level_children.get_index_of(&child)
+    }
+
+    // the horizontal scroll delta that keeps the live tail at `content_right` in view,
+    //   stops following instead if the user scrolled left since the previous frame
+    fn follow_tail_delta(&mut self, viewport: Rect, content_right: f32) -> Option<f32> {
+        let tail_offset = (content_right - viewport.width()).max(0.0);
+        if let Some(prev_offset) = self.follow_tail_offset
+            && viewport.min.x < prev_offset - 1.0
+        {
+            self.follow_tail = false;
+            self.follow_tail_offset = None;
+            None
+        } else {
+            self.follow_tail_offset = Some(tail_offset);
+            Some(viewport.min.x - tail_offset)
+        }
+    }
+
+    // whether a process with the given label is highlighted for running the same executable as the hovered process
+    fn is_same_executable(&self, name: &OsStr, hovered_name: Option<&OsStr>) -> bool {
+        self.highlight_same_executable && hovered_name == Some(name)
+    }
+
+    // the pid to show info for in the side panel
+    fn info_pid(&self) -> Option<Pid> {
+        self.hovered_pid
+            .or(self.selected_pids.last().copied())
+            .or_else(|| self.data.as_ref().and_then(|d| d.recording.root_pid))
+    }
+
+    // show the colors used for the range of CPU utilizations, as they appear in the bar headers
+
+    fn show_utilization_legend(&self, ui: &mut egui::Ui) {
+        let dark_mode = ui.visuals().dark_mode;
+        let size = Vec2::new(ui.available_width(), LEGEND_HEIGHT);
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+
+        let painter = ui.painter_at(rect);
+        for i in 0..LEGEND_STEPS {
+            let utilization = i as f64 / (LEGEND_STEPS - 1) as f64;
+            let colors = get_process_color(&self.color_settings, dark_mode, "", Some(utilization), None);
+            let step_width = rect.width() / LEGEND_STEPS as f32;
+            let step_rect = Rect::from_min_size(
+                rect.min + Vec2::new(i as f32 * step_width, 0.0),
+                Vec2::new(step_width, rect.height()),
+            );
+            painter.rect_filled(step_rect, CornerRadiusF32::ZERO, colors.header);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("idle");
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| ui.label("one core busy"));
+        });
+        ui.label("Gray bars have no CPU time, it's only measured when polling.");
+    }
+
+    // show a window to edit the note of the process that was right-clicked, if any
+    fn show_note_editor(&mut self, ctx: &Context) {
+        let Some((pid, text)) = &mut self.note_edit else {
+            return;
+        };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new(format!("Note for pid {}", pid))
+            .id(egui::Id::new("note_editor"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.text_edit_multiline(text).request_focus();
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        let mut notes = self.notes.lock().unwrap();
+                        if text.trim().is_empty() {
+                            notes.remove(pid);
+                        } else {
+                            notes.insert(*pid, text.clone());
+                        }
+                        done = true;
+                    }
+                    if ui.button("Remove").clicked() {
+                        self.notes.lock().unwrap().remove(pid);
+                        done = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = true;
+                    }
+                });
+            });
+
+        if !open || done {
+            self.note_edit = None;
+        }
+    }
+
+    // show which executables got slower or faster between the two compared recordings
+    fn show_diff(&self, ui: &mut egui::Ui) {
+        let Some(diff) = self.data.as_ref().and_then(|data| data.diff.as_ref()) else {
+            return;
+        };
+
+        ScrollArea::vertical()
+            .id_salt("diff_list")
+            .max_height(PROCESS_LIST_HEIGHT)
+            .show(ui, |ui| {
+                Grid::new("diff_grid").striped(true).show(ui, |ui| {
+                    ui.strong("executable");
+                    ui.strong("before");
+                    ui.strong("after");
+                    ui.strong("delta");
+                    ui.end_row();
+
+                    for exec in &diff.executables {
+                        ui.label(exec.name.to_string_lossy());
+                        ui.label(format!("{}x {:.3}s", exec.count_before, exec.time_before));
+                        ui.label(format!("{}x {:.3}s", exec.count_after, exec.time_after));
+                        let delta = format!("{:+.3}s", exec.delta);
+                        match exec.kind {
+                            DiffKind::Added | DiffKind::Slower => ui.colored_label(DIFF_SLOWER_COLOR, delta),
+                            DiffKind::Removed | DiffKind::Faster => ui.colored_label(DIFF_FASTER_COLOR, delta),
+                            DiffKind::Same => ui.label(delta),
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    // show the captured output of the command, with the time each chunk was written
+    fn show_output(&self, ui: &mut egui::Ui) {
+        let Some(data) = &self.data else {
+            return;
+        };
+        let recording = &data.recording;
+        if recording.output_truncated {
+            ui.label("(older output was dropped)");
+        }
+
+        ScrollArea::vertical()
+            .id_salt("output_list")
+            .max_height(OUTPUT_LIST_HEIGHT)
+            .show(ui, |ui| {
+                Grid::new("output_grid").striped(true).show(ui, |ui| {
+                    for chunk in &recording.output {
+                        ui.label(format!("{:.3}s", chunk.time));
+                        let text = egui::RichText::new(chunk.text.to_string_lossy().trim_end()).monospace();
+                        match chunk.stream {
+                            OutputStream::Stdout => ui.label(text),
+                            OutputStream::Stderr => ui.label(text.color(OUTPUT_STDERR_COLOR)),
+                        };
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    // show a filterable list of all processes, clicking one selects it and scrolls the timeline to it
+    fn show_process_list(&mut self, ui: &mut egui::Ui) {
+        let Some(data) = &self.data else {
+            return;
+        };
+
+        ui.add(egui::TextEdit::singleline(&mut self.process_filter).hint_text("Filter by name or pid"));
+
+        let mut clicked_pid = None;
+        ScrollArea::vertical()
+            .id_salt("process_list")
+            .max_height(PROCESS_LIST_HEIGHT)
+            .show(ui, |ui| {
+                for pid in &self.process_list {
+                    let Some(info) = data.recording.processes().get(pid) else {
+                        continue;
+                    };
+                    let name = info.label(self.label_mode).to_string_lossy();
+                    if !name.contains(self.process_filter.as_str()) && !pid.to_string().contains(&self.process_filter) {
+                        continue;
+                    }
+
+                    let duration = info.time.duration().map_or("?".to_owned(), |d| format!("{:.3}s", d));
+                    let selected = self.selected_pids.contains(pid);
+                    if ui
+                        .selectable_label(selected, format!("{} {} {}", pid, name, duration))
+                        .clicked()
+                    {
+                        clicked_pid = Some(*pid);
+                    }
+                }
+            });
+
+        if let Some(pid) = clicked_pid {
+            self.select_and_scroll_to(ui, pid);
+        }
+    }
+
+    // show the process tree as collapsible text, clicking a process selects it and scrolls the timeline to it
+    fn show_process_tree(&mut self, ui: &mut egui::Ui) {
+        let Some(data) = &self.data else {
+            return;
+        };
+        let recording = &data.recording;
+
+        if ui.button("Copy as text").clicked() {
+            ui.ctx()
+                .copy_text(process_tree_text(recording, self.label_mode, self.show_threads));
+        }
+
+        // expand the tree up to a newly selected process, so selecting in the timeline shows it here too
+        let selected = self.selected_pids.last().copied();
+        let reveal = selected.filter(|&pid| self.process_tree_revealed != Some(pid));
+        self.process_tree_revealed = selected;
+        if let Some(pid) = reveal {
+            for ancestor in recording.ancestry(pid) {
+                if ancestor != pid {
+                    let id = egui::Id::new(("process_tree", ancestor));
+                    let mut state = CollapsingState::load_with_default_open(ui.ctx(), id, false);
+                    state.set_open(true);
+                    state.store(ui.ctx());
+                }
+            }
+        }
+
+        let mut clicked_pid = None;
+        ScrollArea::both()
+            .id_salt("process_tree")
+            .max_height(PROCESS_LIST_HEIGHT)
+            .show(ui, |ui| {
+                if let Some(root) = recording.root_pid {
+                    let mut tree = ProcessTreeUi {
+                        recording,
+                        label_mode: self.label_mode,
+                        show_threads: self.show_threads,
+                        selected_pids: &self.selected_pids,
+                        visited: HashSet::new(),
+                        reveal,
+                        clicked_pid: &mut clicked_pid,
+                    };
+                    tree.show(ui, root);
+                }
+            });
+
+        if let Some(pid) = clicked_pid {
+            self.select_and_scroll_to(ui, pid);
+        }
+    }
+
+    // show the chain of ancestors of the process shown in the info panel, clicking one selects it
+    fn show_ancestry(&mut self, ui: &mut egui::Ui) {
+        let Some(pid) = self.info_pid() else {
+            return;
+        };
+        let Some(data) = &self.data else {
+            return;
+        };
+
+        let mut clicked_pid = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, ancestor) in enumerate(data.recording.ancestry(pid)) {
+                if i > 0 {
+                    ui.label("›");
+                }
+                let name = data.recording.processes()[&ancestor].name().to_string_lossy();
+                if ui.link(name).on_hover_text(format!("pid {}", ancestor)).clicked() {
+                    clicked_pid = Some(ancestor);
+                }
+            }
+        });
+
+        if let Some(pid) = clicked_pid {
+            self.select_and_scroll_to(ui, pid);
+        }
+    }
+
+    // replace the selection with the given process and scroll the timeline to it
+    fn select_and_scroll_to(&mut self, ui: &egui::Ui, pid: Pid) {
+        self.selected_pids.clear();
+        self.selected_pids.insert(pid);
+
+        if let Some((recording, root_placed)) = self.data.as_ref().and_then(|d| d.placed(self.show_threads))
+            && let Some((rect_params, _)) = self.timeline_rect_params(ui, recording, root_placed)
+            && let Some(rect) = pid_timeline_rect(
+                &rect_params,
+                recording,
+                root_placed,
+                pid,
+                self.layout_mode == LayoutMode::Icicle,
+            )
+        {
+            self.timeline_scroll_target = Some(scroll_target_for_rect(rect, self.timeline_viewport.size()));
+            self.follow_tail = false;
+        }
+    }
+
+    // show the execs of processes that have multiple, allowing them to be selected and highlighted in the timeline
+    fn show_exec_list(&mut self, ui: &mut egui::Ui) {
+        self.hovered_exec = None;
+
+        let Some(pid) = self.info_pid() else {
+            return;
+        };
+        let Some(info) = self.data.as_ref().and_then(|d| d.recording.processes().get(&pid)) else {
+            return;
+        };
+        if info.execs.len() <= 1 {
+            return;
+        }
+
+        for (i_exec, exec) in enumerate(&info.execs) {
+            let selected = self.selected_exec == Some((pid, i_exec));
+            let mut text = format!("exec {}: {}", i_exec, exec.label(self.label_mode).display());
+            if let Some(rss_peak) = exec.rss_peak {
+                swrite!(text, " ({} peak)", format_bytes(rss_peak));
+            }
+            let response = ui.selectable_label(selected, text);
+            if response.clicked() {
+                self.selected_exec = if selected { None } else { Some((pid, i_exec)) };
+            }
+            if response.hovered() {
+                self.hovered_exec = Some((pid, i_exec));
+            }
+        }
+    }
+
+    // show how the arguments of the process differ from the previous invocation of the same executable
+    fn show_argv_diff(&self, ui: &mut egui::Ui) {
+        let (Some(pid), Some(data)) = (self.info_pid(), &self.data) else {
+            return;
+        };
+        let recording = &data.recording;
+        let Some(other) = previous_invocation(recording, pid, self.label_mode) else {
+            ui.weak("no other invocation of this executable");
+            return;
+        };
+        let (Some(old), Some(new)) = (
+            recording.processes()[&other].execs.last(),
+            recording.processes()[&pid].execs.last(),
+        ) else {
+            return;
+        };
+
+        ui.label(format!("compared to pid {}", other));
+        let diff = argv_diff(&old.argv, &new.argv);
+        let prefix = diff.iter().take_while(|d| matches!(d, ArgDiff::Same(_))).count();
+        if prefix == diff.len() {
+            ui.weak("identical arguments");
+            return;
+        }
+        if prefix > 0 {
+            ui.weak(format!("({} identical leading arguments)", prefix));
+        }
+        for d in &diff[prefix..] {
+            match d {
+                ArgDiff::Same(arg) => ui.label(arg.to_string_lossy()),
+                ArgDiff::Removed(arg) => ui.colored_label(ARGV_REMOVED_COLOR, format!("- {}", arg.to_string_lossy())),
+                ArgDiff::Added(arg) => ui.colored_label(ARGV_ADDED_COLOR, format!("+ {}", arg.to_string_lossy())),
+            };
+        }
+    }
+
+    fn status_text(&self) -> String {
+        let Some(data) = &self.data else {
+            return "Waiting for data...".to_owned();
+        };
+        let recording = &data.recording;
+
+        let counts = recording.total_counts();
+        let elapsed = match recording.elapsed() {
+            Some(elapsed) => format!("{:.1}s", elapsed),
+            None => "?".to_owned(),
+        };
+
+        let mut text = String::new();
+        swriteln!(text, "processes: {}", counts.processes);
+        swriteln!(text, "threads: {}", counts.threads);
+        swriteln!(text, "active: {}", recording.active_count());
+        swrite!(text, "elapsed: {}", elapsed);
+        if let Some(overhead) = recording.spawn_overhead() {
+            swrite!(text, "\nspawn overhead: {:.1}ms", overhead * 1000.0);
+        }
+        text
+    }
+
+    fn selection_summary_text(&self) -> String {
+        let Some(data) = &self.data else {
+            return "".to_owned();
+        };
+        let summary = selection_summary(&data.recording, &self.selected_pids, self.label_mode);
+
+        let mut text = String::new();
+        swriteln!(text, "count: {}", summary.count);
+        swriteln!(text, "total time: {:.3}s", summary.total_time);
+        swriteln!(text, "executables:");
+        for stats in &summary.executables {
+            swriteln!(
+                text,
+                "    {}: {} ({:.3}s)",
+                stats.name.display(),
+                stats.count,
+                stats.total_time
+            );
+        }
+        text
+    }
+
+    fn selected_pid_info(&self) -> String {
+        // figure out which pid to show info for
+        let Some(pid) = self.info_pid() else {
+            return "".to_owned();
+        };
+
+        // render info to string
+        const I: &str = "    ";
+
+        let mut text = String::new();
+        swriteln!(text, "pid: {}", pid);
+        if let Some(note) = self.notes.lock().unwrap().get(&pid) {
+            swriteln!(text, "note: {}", note);
+        }
+
+        if let Some(data) = &self.data
+            && let Some(info) = data.recording.processes().get(&pid)
+        {
+            let fmt_time = |time: f64| match data.recording.wall_time(time) {
+                Some(wall_time) if self.show_absolute_times => format_wall_time(wall_time),
+                _ => time.to_string(),
+            };
+
+            swriteln!(text, "time_start: {}", fmt_time(info.time.start));
+            swriteln!(text, "time_end: {}", info.time.end.map_or("?".to_owned(), fmt_time));
+            swriteln!(text, "duration: {:?}", info.time.duration());
+            swriteln!(text, "self time: {:?}", self_time(&data.recording, pid));
+            let time_end = data
+                .recording
+                .time_end
+                .or(data.recording.time_latest)
+                .unwrap_or(info.time.start);
+            let thread_process = thread_process_time(&data.recording, pid, time_end);
+            swriteln!(
+                text,
+                "subtree time: {} in threads, {} in processes (ratio {})",
+                format_duration(thread_process.threads),
+                format_duration(thread_process.processes),
+                thread_process
+                    .ratio()
+                    .map_or("?".to_owned(), |ratio| format!("{:.2}", ratio))
+            );
+            swriteln!(text, "exit_code: {:?}", info.exit_code);
+            if let Some(signal) = info.exit_signal {
+                let core_dumped = if signal.core_dumped { " (core dumped)" } else { "" };
+                swriteln!(text, "killed by: {}{}", signal.name(), core_dumped);
+            }
+            if let (Some(pgid), Some(sid)) = (info.pgid, info.sid) {
+                swriteln!(text, "pgid: {}", pgid);
+                swriteln!(text, "sid: {}", sid);
+            }
+            if let Some(namespace_id) = info.namespace_id {
+                swriteln!(text, "pid_ns: {}", namespace_id.pid);
+                swriteln!(text, "mnt_ns: {}", namespace_id.mnt);
+                if data.recording.in_other_namespace(pid) {
+                    swriteln!(text, "(in other namespaces than the root)");
+                }
+            }
+            if let Some(credentials) = info.credentials {
+                swriteln!(text, "uid: {} (effective {})", credentials.uid, credentials.euid);
+                swriteln!(text, "gid: {} (effective {})", credentials.gid, credentials.egid);
+                if data.recording.credentials_changed(pid) {
+                    swriteln!(text, "(credentials changed, tracing info might be incomplete)");
+                }
+            }
+            if let Some(peak_fds) = info.peak_fds {
+                swriteln!(text, "peak_fds: {}", peak_fds);
+            }
+            if !info.vfork_blocked.is_empty() {
+                let blocked: f64 = info.vfork_blocked.iter().filter_map(|range| range.duration()).sum();
+                swriteln!(
+                    text,
+                    "vfork blocked: {} ({} times)",
+                    format_duration(blocked),
+                    info.vfork_blocked.len()
+                );
+            }
+            if !info.exec_failures.is_empty() {
+                swriteln!(text, "failed execs: {}", info.exec_failures.len());
+                for failure in &info.exec_failures {
+                    swriteln!(
+                        text,
+                        "{I}{}: {} ({})",
+                        fmt_time(failure.time),
+                        failure.path.display(),
+                        failure.error()
+                    );
+                }
+            }
+
+            let child_counts = data.recording.child_counts(pid);
+            swriteln!(text, "children: {}", child_counts.processes);
+            swriteln!(text, "threads: {}", child_counts.threads);
+
+            swriteln!(text, "execs: {}", info.execs.len());
+
+            for (i_exec, exec) in enumerate(&info.execs) {
+                swriteln!(text, "{I}{i_exec}");
+
+                swriteln!(text, "{I}{I}time: {}", fmt_time(exec.time));
+                swriteln!(
+                    text,
+                    "{I}{I}cwd: {}",
+                    exec.cwd.as_ref().map_or("?".into(), |cwd| cwd.to_string_lossy())
+                );
+                swriteln!(text, "{I}{I}path: {}", exec.path.display());
+                if let (Some(rss_start), Some(rss_peak)) = (exec.rss_start, exec.rss_peak) {
+                    swriteln!(
+                        text,
+                        "{I}{I}memory: {} at start, {} peak",
+                        format_bytes(rss_start),
+                        format_bytes(rss_peak)
+                    );
+                }
+
+                swriteln!(text, "{I}{I}argv:");
+                for arg in &exec.argv {
+                    swriteln!(text, "{I}{I}{I}{}", arg.display());
+                }
+            }
+        };
+
+        text
+    }
+}
+
+// explain why there is nothing to show when the recording has no root process
+fn show_empty_state(ui: &mut egui::Ui, recording: &Recording, error: Option<&str>) {
+    let (heading, detail) = empty_state_text(recording, error);
+    ui.vertical_centered(|ui| {
+        ui.add_space(EMPTY_STATE_MARGIN);
+        ui.heading(heading);
+        ui.label(detail);
+    });
+}
+
+// the heading and detail text of the empty state
+fn empty_state_text(recording: &Recording, error: Option<&str>) -> (&'static str, String) {
+    match error {
+        Some(error) => ("Tracing failed", error.to_owned()),
+        None if recording.time_end.is_some() => (
+            "No processes recorded",
+            "The recording finished without ever seeing the root process.".to_owned(),
+        ),
+        None => (
+            "No processes recorded yet",
+            "Waiting for the root process to start.".to_owned(),
+        ),
+    }
+}
+
+const MINIMAP_HEIGHT: f32 = 120.0;
+const EMPTY_STATE_MARGIN: f32 = 40.0;
+const PROCESS_LIST_HEIGHT: f32 = 300.0;
+const OUTPUT_LIST_HEIGHT: f32 = 300.0;
+const CONCURRENCY_HEIGHT: f32 = 60.0;
+const SELF_BAR_HEIGHT: f32 = 20.0;
+const SELF_BAR_ALPHA: f32 = 0.1;
+const DEFAULT_MAX_DEPTH: usize = 8;
+const DEFAULT_SIDE_PANEL_WIDTH: f32 = 200.0;
+const PANEL_STATE_KEY: &str = "panel_state";
+const ZOOM_SETTINGS_KEY: &str = "zoom_settings";
+const LEGEND_HEIGHT: f32 = 12.0;
+const LEGEND_STEPS: usize = 32;
+const PALETTE_WIDTH: f32 = 320.0;
+const PALETTE_OFFSET: f32 = 80.0;
+const PALETTE_LIST_HEIGHT: f32 = 300.0;
+
+// mapping between timeline content coordinates and the minimap screen rect
+struct MinimapTransform {
+    content: Rect,
+    minimap: Rect,
+}
+
+impl MinimapTransform {
+    fn scale(&self) -> Vec2 {
+        self.minimap.size() / self.content.size()
+    }
+
+    fn to_minimap(&self, rect: Rect) -> Rect {
+        Rect {
+            min: self.minimap.min + (rect.min - self.content.min) * self.scale(),
+            max: self.minimap.min + (rect.max - self.content.min) * self.scale(),
+        }
+    }
+
+    fn to_content(&self, pos: Pos2) -> Pos2 {
+        self.content.min + (pos - self.minimap.min) / self.scale()
+    }
+}
+
+// the state of a single pass over the process tree in [App::show_process_tree]
+struct ProcessTreeUi<'a> {
+    recording: &'a Recording,
+    label_mode: LabelMode,
+    show_threads: bool,
+    selected_pids: &'a IndexSet<Pid>,
+    // guards against cycles in broken recordings
+    visited: HashSet<Pid>,
+    // scroll to this process
+    reveal: Option<Pid>,
+    clicked_pid: &'a mut Option<Pid>,
+}
+
+impl ProcessTreeUi<'_> {
+    fn show(&mut self, ui: &mut egui::Ui, pid: Pid) {
+        if !self.visited.insert(pid) {
+            return;
+        }
+        let Some(info) = self.recording.processes().get(&pid) else {
+            return;
+        };
+
+        let children = info
+            .children
+            .iter()
+            .filter(|&&(kind, _)| self.show_threads || kind == ProcessKind::Process)
+            .map(|&(_, child)| child)
+            .collect_vec();
+        let label = |this: &mut Self, ui: &mut egui::Ui| {
+            let line = process_tree_line(info, this.label_mode);
+            let response = ui.selectable_label(this.selected_pids.contains(&pid), line);
+            if this.reveal == Some(pid) {
+                response.scroll_to_me(Some(Align::Center));
+            }
+            if response.clicked() {
+                *this.clicked_pid = Some(pid);
+            }
+        };
+
+        if children.is_empty() {
+            label(self, ui);
+            return;
+        }
+
+        // only the root is expanded initially, large trees would be overwhelming otherwise
+        let id = egui::Id::new(("process_tree", pid));
+        let default_open = Some(pid) == self.recording.root_pid;
+        CollapsingState::load_with_default_open(ui.ctx(), id, default_open)
+            .show_header(ui, |ui| label(self, ui))
+            .body(|ui| {
+                for child in children {
+                    self.show(ui, child);
+                }
+            });
+    }
+}
+
+// the header rect of the given process in timeline content coordinates, if it is part of the layout
+
+fn pid_timeline_rect(
+    rect_params: &ProcRectParams,
+    recording: &Recording,
+    root_placed: &PlacedProcess,
+    pid: Pid,
+    icicle: bool,
+) -> Option<Rect> {
+    let info = recording.processes().get(&pid)?;
+    let mut result = None;
+    root_placed.visit(
+        |placed, row| {
+            if placed.pid == pid {
+                let time = if icicle { placed.time_bound } else { info.time };
+                result = Some(rect_params.proc_rect(time, row, 1));
+            }
+            ControlFlow::Continue(())
+        },
+        |_, _, ()| {},
+    );
+    result
+}
+
+// the scroll offset that centers the viewport on the given rect, without scrolling past the start
+fn scroll_target_for_rect(rect: Rect, viewport_size: Vec2) -> Vec2 {
+    (rect.center().to_vec2() - viewport_size / 2.0).max(Vec2::ZERO)
+}
+
+fn layout_mode_name(mode: LayoutMode) -> &'static str {
+    match mode {
+        LayoutMode::Packed => "Packed",
+        LayoutMode::Lanes => "Lane per executable",
+        LayoutMode::Icicle => "Icicle (aggregated)",
+    }
+}
+
+fn color_mode_name(mode: ColorMode) -> &'static str {
+    match mode {
+        ColorMode::Executable => "Executable",
+        ColorMode::CpuUtilization => "CPU utilization",
+        ColorMode::Subtree => "Top-level subtree",
+    }
+}
+
+fn label_mode_name(mode: LabelMode) -> &'static str {
+    match mode {
+        LabelMode::Basename => "Basename",
+        LabelMode::FullPath => "Full path",
+        LabelMode::Argv0 => "argv[0]",
+    }
+}
+
+// actions that can be run from the command palette
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PaletteAction {
+    ToggleSidePanel,
+    ToggleTheme,
+    ToggleThreads,
+    ToggleCriticalPath,
+    ToggleFocus,
+    ToggleProcessGroups,
+    ToggleConcurrency,
+    ToggleAbsoluteTimes,
+    ToggleFollowTail,
+    ToggleIdleGaps,
+    ToggleReplay,
+    ResetZoom,
+    ClearSelection,
+    Rerun,
+    Layout(LayoutMode),
+    Color(ColorMode),
+    JumpToPid(Pid),
+}
+
+impl PaletteAction {
+    // all actions that don't take an argument, in the order they're listed when the query is empty
+    const ALL: &[PaletteAction] = &[
+        PaletteAction::ToggleSidePanel,
+        PaletteAction::ToggleTheme,
+        PaletteAction::ToggleThreads,
+        PaletteAction::ToggleCriticalPath,
+        PaletteAction::ToggleFocus,
+        PaletteAction::ToggleProcessGroups,
+        PaletteAction::ToggleConcurrency,
+        PaletteAction::ToggleAbsoluteTimes,
+        PaletteAction::ToggleFollowTail,
+        PaletteAction::ToggleIdleGaps,
+        PaletteAction::ToggleReplay,
+        PaletteAction::ResetZoom,
+        PaletteAction::ClearSelection,
+        PaletteAction::Rerun,
+        PaletteAction::Layout(LayoutMode::Packed),
+        PaletteAction::Layout(LayoutMode::Lanes),
+        PaletteAction::Layout(LayoutMode::Icicle),
+        PaletteAction::Color(ColorMode::Executable),
+        PaletteAction::Color(ColorMode::CpuUtilization),
+        PaletteAction::Color(ColorMode::Subtree),
+    ];
+
+    fn name(&self) -> String {
+        match *self {
+            PaletteAction::ToggleSidePanel => "Toggle side panel".to_owned(),
+            PaletteAction::ToggleTheme => "Toggle dark/light theme".to_owned(),
+            PaletteAction::ToggleThreads => "Toggle threads".to_owned(),
+            PaletteAction::ToggleCriticalPath => "Toggle critical path highlight".to_owned(),
+            PaletteAction::ToggleFocus => "Toggle focus on selected subtree".to_owned(),
+            PaletteAction::ToggleProcessGroups => "Toggle process group tint".to_owned(),
+            PaletteAction::ToggleConcurrency => "Toggle concurrency chart".to_owned(),
+            PaletteAction::ToggleAbsoluteTimes => "Toggle absolute times".to_owned(),
+            PaletteAction::ToggleFollowTail => "Toggle follow live tail".to_owned(),
+            PaletteAction::ToggleIdleGaps => "Toggle idle gaps".to_owned(),
+            PaletteAction::ToggleReplay => "Toggle replay".to_owned(),
+            PaletteAction::ResetZoom => "Reset zoom".to_owned(),
+            PaletteAction::ClearSelection => "Clear selection".to_owned(),
+            PaletteAction::Rerun => "Re-run command".to_owned(),
+            PaletteAction::Layout(mode) => format!("Layout: {}", layout_mode_name(mode)),
+            PaletteAction::Color(mode) => format!("Color by: {}", color_mode_name(mode)),
+            PaletteAction::JumpToPid(pid) => format!("Jump to pid {}", pid),
+        }
+    }
+}
+
+// score how well `query` matches `name`, or `None` if the characters of `query` don't all appear in order
+fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name = name.chars().flat_map(char::to_lowercase).collect_vec();
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut prev_match = None;
+    for q in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+    {
+        let index = next + name[next..].iter().position(|&c| c == q)?;
+
+        score += 1;
+        if prev_match.is_some_and(|prev| prev + 1 == index) {
+            score += 4;
+        } else if index == 0 || !name[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (index - next).min(3) as i32;
+
+        prev_match = Some(index);
+        next = index + 1;
+    }
+    Some(score)
+}
+
+// format a duration in seconds with a unit that fits its magnitude, eg. `850µs`, `12.5ms` or `1.2s`
+fn format_duration(seconds: f64) -> String {
+    if seconds < 1e-3 {
+        format!("{:.0}µs", seconds * 1e6)
+    } else if seconds < 1.0 {
+        format!("{:.1}ms", seconds * 1e3)
+    } else {
+        format!("{:.1}s", seconds)
+    }
+}
+
+// where to draw a label with the given layout rect inside `rect`, or None if it doesn't fit
+fn label_rect(rect: Rect, label: Rect, padding: Vec2) -> Option<Rect> {
+    let rect_label = label.translate(rect.min.to_vec2() + padding);
+    rect.contains_rect(rect_label).then_some(rect_label)
+}
+
+// format a number of bytes in binary units, eg. `12.3 MiB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// format as `HH:MM:SS.mmm` in UTC
+fn format_wall_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03} UTC",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
 
 struct ProcRectParams {
-    total_time_end: f32,
+    total_time_end: f64,
+    zoom_settings: ZoomSettings,
     zoom_factor: Vec2,
+    // rects are widened to at least this many pixels, so very short processes stay visible
+    min_width: f32,
 }
 
 const ZOOM_MULTIPLIER_HOR: f32 = 200.0;
@@ -403,43 +2428,157 @@ const ZOOM_MULTIPLIER_VER: f32 = 20.0;
 const ZOOM_MULTIPLIER_HOR_EXP: f32 = 100.0;
 const ZOOM_MULTIPLIER_VER_EXP: f32 = 200.0;
 
+// how zoom levels map to pixels, tunable for different screens and input devices. Remembered across runs
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ZoomSettings {
+    // pixels per second and pixels per row at the default zoom level
+    hor: f32,
+    ver: f32,
+    // the amount of scrolling needed to zoom in by a factor `e`, larger values make zooming less sensitive
+    hor_exp: f32,
+    ver_exp: f32,
+    // what the mouse wheel does in the timeline
+    wheel_mode: WheelMode,
+}
+
+// what the mouse wheel does in the timeline, see [wheel_action]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+enum WheelMode {
+    Zoom,
+    Pan,
+}
+
+// the change in zoom and scroll offset caused by a mouse wheel scroll
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct WheelAction {
+    zoom: Vec2,
+    pan: Vec2,
+}
+
+// map a mouse wheel scroll to a zoom or pan, depending on the wheel mode
+fn wheel_action(mode: WheelMode, raw_delta: Vec2, ctrl: bool) -> WheelAction {
+    let (zoom, pan) = match (mode, ctrl) {
+        (WheelMode::Zoom, false) => (raw_delta.yx(), Vec2::ZERO),
+        (WheelMode::Zoom, true) => (raw_delta, Vec2::ZERO),
+        (WheelMode::Pan, false) => (Vec2::ZERO, raw_delta.yx()),
+        (WheelMode::Pan, true) => (raw_delta.yx(), Vec2::ZERO),
+    };
+    WheelAction { zoom, pan }
+}
+
+impl Default for ZoomSettings {
+    fn default() -> Self {
+        Self {
+            hor: ZOOM_MULTIPLIER_HOR,
+            ver: ZOOM_MULTIPLIER_VER,
+            hor_exp: ZOOM_MULTIPLIER_HOR_EXP,
+            ver_exp: ZOOM_MULTIPLIER_VER_EXP,
+            wheel_mode: WheelMode::Zoom,
+        }
+    }
+}
+
+impl ZoomSettings {
+    fn linear_to_factor(&self, zoom_linear: f32, hor: bool) -> f32 {
+        (zoom_linear / self.multiplier_exp(hor)).exp()
+    }
+
+    fn factor_to_linear(&self, zoom_factor: f32, hor: bool) -> f32 {
+        zoom_factor.ln() * self.multiplier_exp(hor)
+    }
+
+    fn multiplier_exp(&self, hor: bool) -> f32 {
+        if hor {
+            self.hor_exp
+        } else {
+            self.ver_exp
+        }
+    }
+}
+
+const HEADER_FONT_SIZE_MIN: f32 = 8.0;
+const HEADER_FONT_SIZE_MAX: f32 = 48.0;
+const THREAD_HEADER_FRACTION: f32 = 0.4;
+const CRITICAL_PATH_COLOR: Color32 = Color32::from_rgb(255, 140, 0);
+const ARGV_REMOVED_COLOR: Color32 = Color32::from_rgb(220, 60, 60);
+const ARGV_ADDED_COLOR: Color32 = Color32::from_rgb(60, 170, 60);
+const PROCESS_GROUP_TINT_ALPHA: f32 = 0.25;
+const IDLE_GAP_COLOR: Color32 = Color32::from_rgb(220, 40, 160);
+const IDLE_GAP_ALPHA: f32 = 0.4;
+const FOCUS_DIM_ALPHA: f32 = 0.8;
+const NOTE_MARKER_COLOR: Color32 = Color32::from_rgb(255, 215, 0);
+const NOTE_MARKER_RADIUS: f32 = 3.0;
+const NAMESPACE_MARKER_COLOR: Color32 = Color32::from_rgb(0, 150, 220);
+const NAMESPACE_MARKER_SIZE: f32 = 6.0;
+const CREDENTIALS_MARKER_COLOR: Color32 = Color32::from_rgb(230, 120, 0);
+const CREDENTIALS_MARKER_SIZE: f32 = 10.0;
+const SIGNAL_KILLED_COLOR: Color32 = Color32::from_rgb(220, 30, 30);
+const VFORK_BLOCKED_COLOR: Color32 = Color32::from_gray(128);
+const VFORK_BLOCKED_ALPHA: f32 = 0.5;
+const DIFF_SLOWER_COLOR: Color32 = Color32::from_rgb(220, 60, 60);
+const DIFF_FASTER_COLOR: Color32 = Color32::from_rgb(60, 170, 60);
+const OUTPUT_STDERR_COLOR: Color32 = Color32::from_rgb(220, 60, 60);
+// output written within this many pixels of the pointer is shown when hovering the timeline
+const OUTPUT_HOVER_RADIUS: f32 = 8.0;
+
 impl ProcRectParams {
-    pub fn new(total_time_end: f32, zoom_linear: Vec2) -> Self {
+    pub fn new(total_time_end: f64, zoom_linear: Vec2, zoom_settings: ZoomSettings, min_width: f32) -> Self {
         let zoom_factor = Vec2::new(
-            zoom_linear_to_factor(zoom_linear.x, true),
-            zoom_linear_to_factor(zoom_linear.y, false),
+            zoom_settings.linear_to_factor(zoom_linear.x, true),
+            zoom_settings.linear_to_factor(zoom_linear.y, false),
         );
         ProcRectParams {
             total_time_end,
+            zoom_settings,
             zoom_factor,
+            min_width,
         }
     }
 
-    pub fn proc_rect(&self, time: TimeRange, row: usize, height: usize) -> Rect {
-        let time_end = time.end.unwrap_or(self.total_time_end);
-        let w = ZOOM_MULTIPLIER_HOR * self.zoom_factor.x;
-        let h = ZOOM_MULTIPLIER_VER * self.zoom_factor.y;
+    /// The font for header text, scaled with the vertical zoom so it grows with the rows.
+    pub fn header_font(&self) -> FontId {
+        let size = FontId::default().size * self.zoom_factor.y;
+        FontId::proportional(size.clamp(HEADER_FONT_SIZE_MIN, HEADER_FONT_SIZE_MAX))
+    }
 
-        Rect {
-            min: Pos2::new(w * time.start, h * (row as f32)),
-            max: Pos2::new(w * time_end, h * ((row + height) as f32)),
-        }
+    /// The horizontal extent of the entire trace, from the start until `total_time_end`.
+    pub fn trace_x_range(&self) -> eframe::emath::Rangef {
+        let time = TimeRange {
+            start: 0.0,
+            end: Some(self.total_time_end),
+        };
+        self.proc_rect(time, 0, 1).x_range()
     }
-}
 
-fn zoom_linear_to_factor(zoom_linear: f32, hor: bool) -> f32 {
-    (zoom_linear / zoom_multiplier_exp(hor)).exp()
-}
+    /// The times and horizontal positions of the dividers between the execs of a process.
+    pub fn exec_dividers<'a>(&'a self, execs: &'a [ProcessExec]) -> impl Iterator<Item = (TimeRange, f32)> + 'a {
+        execs.iter().skip(1).map(|exec| {
+            let exec_time = TimeRange {
+                start: exec.time,
+                end: Some(exec.time),
+            };
+            (exec_time, self.proc_rect(exec_time, 0, 1).min.x)
+        })
+    }
 
-fn zoom_factor_to_linear(zoom_factor: f32, hor: bool) -> f32 {
-    zoom_factor.ln() * zoom_multiplier_exp(hor)
-}
+    /// The time at horizontal position `x`, the inverse of [Self::proc_rect] without the minimum width.
+    pub fn time_at(&self, x: f32) -> f64 {
+        x as f64 / (self.zoom_settings.hor * self.zoom_factor.x) as f64
+    }
 
-fn zoom_multiplier_exp(hor: bool) -> f32 {
-    if hor {
-        ZOOM_MULTIPLIER_HOR_EXP
-    } else {
-        ZOOM_MULTIPLIER_VER_EXP
+    pub fn proc_rect(&self, time: TimeRange, row: usize, height: usize) -> Rect {
+        let time_end = time.end.unwrap_or(self.total_time_end);
+        let w = (self.zoom_settings.hor * self.zoom_factor.x) as f64;
+        let h = self.zoom_settings.ver * self.zoom_factor.y;
+
+        // times are only converted to f32 here, at the pixel level
+        let x_min = (w * time.start) as f32;
+        let x_max = ((w * time_end) as f32).max(x_min + self.min_width);
+        Rect {
+            min: Pos2::new(x_min, h * (row as f32)),
+            max: Pos2::new(x_max, h * ((row + height) as f32)),
+        }
     }
 }
 
@@ -450,6 +2589,7 @@ struct ProcessColors {
 }
 
 struct ColorSettings {
+    mode: ColorMode,
     hue_sat: f32,
     val_dark: ColorValues,
     val_light: ColorValues,
@@ -462,9 +2602,20 @@ struct ColorValues {
     stroke: f32,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ColorMode {
+    /// A fixed hue per kind of executable.
+    Executable,
+    /// Blend from a cold to a hot hue based on the CPU utilization, only available when polling.
+    CpuUtilization,
+    /// A hue per child of the root, inherited by all of its descendants.
+    Subtree,
+}
+
 impl ColorSettings {
     fn new() -> Self {
         Self {
+            mode: ColorMode::Executable,
             hue_sat: 0.8,
             val_dark: ColorValues {
                 header: 0.08,
@@ -480,8 +2631,19 @@ impl ColorSettings {
     }
 }
 
-fn get_process_color(settings: &ColorSettings, dark_mode: bool, name: &str) -> ProcessColors {
-    let (hue, sat) = match get_process_hue(name) {
+fn get_process_color(
+    settings: &ColorSettings,
+    dark_mode: bool,
+    name: &str,
+    cpu_utilization: Option<f64>,
+    subtree_index: Option<usize>,
+) -> ProcessColors {
+    let hue = match settings.mode {
+        ColorMode::Executable => get_process_hue(name),
+        ColorMode::CpuUtilization => cpu_utilization.map(get_utilization_hue),
+        ColorMode::Subtree => subtree_index.map(get_subtree_hue),
+    };
+    let (hue, sat) = match hue {
         Some(hue) => (hue, settings.hue_sat),
         None => (0.0, 0.0),
     };
@@ -499,6 +2661,21 @@ fn get_process_color(settings: &ColorSettings, dark_mode: bool, name: &str) -> P
     }
 }
 
+// blend from blue for idle processes to red for processes that kept (at least) one core busy
+fn get_utilization_hue(cpu_utilization: f64) -> f32 {
+    const HUE_IDLE: f32 = 220.0;
+    const HUE_BUSY: f32 = 0.0;
+
+    let ratio = cpu_utilization.clamp(0.0, 1.0) as f32;
+    (HUE_IDLE + (HUE_BUSY - HUE_IDLE) * ratio) / 360.0
+}
+
+// spread consecutive subtrees or groups around the color wheel using the golden angle, so neighbours are easy to tell apart
+fn get_subtree_hue(index: usize) -> f32 {
+    const GOLDEN_RATIO_CONJUGATE: f32 = 0.618034;
+    (index as f32 * GOLDEN_RATIO_CONJUGATE).fract()
+}
+
 fn get_process_hue(name: &str) -> Option<f32> {
     #[rustfmt::skip]
     let map: &[(&[&str], f32)] = &[
@@ -533,3 +2710,440 @@ fn get_process_hue(name: &str) -> Option<f32> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poll::{PollFields, PollPeriod};
+    use crate::trace::{fixtures, TraceEvent};
+    use std::ffi::OsString;
+    use std::time::{Duration, Instant};
+
+    fn test_app() -> App {
+        App::new(
+            LayoutSettings::default(),
+            PanelState::default(),
+            ZoomSettings::default(),
+            Arc::default(),
+            None,
+            None,
+        )
+    }
+
+    // a shell running three compilers and a linker one after the other
+    fn compile_recording() -> Recording {
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        for (i, pid) in (2..=4).enumerate() {
+            let start = i as f64;
+            events.extend(fixtures::process(Some(1), pid, start, "/usr/bin/cc", &["cc"]));
+            events.push(fixtures::exit(pid, start + 1.0, Some(0)));
+        }
+        events.extend(fixtures::process(Some(1), 5, 3.0, "/usr/bin/ld", &["ld"]));
+        events.push(fixtures::exit(5, 4.0, Some(0)));
+        events.push(fixtures::exit(1, 4.0, Some(0)));
+        Recording::from_events(events)
+    }
+
+    #[test]
+    fn same_executable_highlight() {
+        let rec = compile_recording();
+        let mut app = test_app();
+        app.highlight_same_executable = true;
+
+        let hovered = rec.processes()[&Pid::from_raw(3)].label(app.label_mode);
+        let highlighted = rec
+            .processes()
+            .values()
+            .filter(|info| app.is_same_executable(info.label(app.label_mode), Some(hovered)))
+            .map(|info| info.pid.as_raw())
+            .collect_vec();
+        assert_eq!(highlighted, [2, 3, 4]);
+
+        app.highlight_same_executable = false;
+        assert!(!app.is_same_executable(hovered, Some(hovered)));
+    }
+
+    #[test]
+    fn exec_dividers_between_execs() {
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        for (time, path) in [(1.0, "/usr/bin/env"), (2.0, "/usr/bin/make")] {
+            events.push(TraceEvent::ProcessExec {
+                pid: Pid::from_raw(1),
+                time,
+                cwd: None,
+                path: path.into(),
+                argv: vec![],
+            });
+        }
+        events.push(fixtures::exit(1, 4.0, Some(0)));
+        let rec = Recording::from_events(events);
+
+        let zoom_settings = ZoomSettings::default();
+        let rect_params = ProcRectParams::new(4.0, Vec2::ZERO, zoom_settings, 0.0);
+        let proc = &rec.processes()[&Pid::from_raw(1)];
+        let dividers = rect_params.exec_dividers(&proc.execs).map(|(_, x)| x).collect_vec();
+        assert_eq!(dividers, [zoom_settings.hor, 2.0 * zoom_settings.hor]);
+    }
+
+    #[test]
+    fn min_width_of_short_process() {
+        let time = TimeRange {
+            start: 1.0,
+            end: Some(1.000001),
+        };
+        let rect = ProcRectParams::new(2.0, Vec2::ZERO, ZoomSettings::default(), 0.0).proc_rect(time, 0, 1);
+        assert!(rect.width() < 0.5);
+        let rect = ProcRectParams::new(2.0, Vec2::ZERO, ZoomSettings::default(), 2.0).proc_rect(time, 0, 1);
+        assert_eq!(rect.width(), 2.0);
+    }
+
+    #[test]
+    fn pixels_per_second_follow_multiplier() {
+        let time = TimeRange {
+            start: 0.0,
+            end: Some(1.0),
+        };
+        let zoom_settings = ZoomSettings::default();
+        let rect = ProcRectParams::new(1.0, Vec2::ZERO, zoom_settings, 0.0).proc_rect(time, 0, 1);
+        assert_eq!(rect.width(), ZOOM_MULTIPLIER_HOR);
+
+        let zoom_settings = ZoomSettings {
+            hor: 2.0 * ZOOM_MULTIPLIER_HOR,
+            ..zoom_settings
+        };
+        let rect = ProcRectParams::new(1.0, Vec2::ZERO, zoom_settings, 0.0).proc_rect(time, 0, 1);
+        assert_eq!(rect.width(), 2.0 * ZOOM_MULTIPLIER_HOR);
+        assert_eq!(rect.height(), ZOOM_MULTIPLIER_VER);
+    }
+
+    #[test]
+    fn duration_units_by_magnitude() {
+        assert_eq!(format_duration(0.0), "0µs");
+        assert_eq!(format_duration(12e-6), "12µs");
+        assert_eq!(format_duration(0.0123), "12.3ms");
+        assert_eq!(format_duration(0.5), "500.0ms");
+        assert_eq!(format_duration(1.25), "1.2s");
+        assert_eq!(format_duration(90.0), "90.0s");
+    }
+
+    #[test]
+    fn wheel_action_per_mode() {
+        // a vertical scroll, as from a regular mouse wheel
+        let delta = Vec2::new(0.0, 3.0);
+        let zoom_hor = WheelAction {
+            zoom: Vec2::new(3.0, 0.0),
+            pan: Vec2::ZERO,
+        };
+        let zoom_ver = WheelAction {
+            zoom: delta,
+            pan: Vec2::ZERO,
+        };
+        let pan_hor = WheelAction {
+            zoom: Vec2::ZERO,
+            pan: Vec2::new(3.0, 0.0),
+        };
+        assert_eq!(wheel_action(WheelMode::Zoom, delta, false), zoom_hor);
+        assert_eq!(wheel_action(WheelMode::Zoom, delta, true), zoom_ver);
+        assert_eq!(wheel_action(WheelMode::Pan, delta, false), pan_hor);
+        assert_eq!(wheel_action(WheelMode::Pan, delta, true), zoom_hor);
+    }
+
+    #[test]
+    fn label_that_exactly_fits_is_drawn() {
+        let label = Rect::from_min_size(Pos2::ZERO, Vec2::new(30.5, 12.0));
+        let padding = Vec2::new(2.0, 0.0);
+        let header = |width: f32| Rect::from_min_size(Pos2::new(100.0, 20.0), Vec2::new(width, 12.0));
+
+        let rect = label_rect(header(32.5), label, padding).unwrap();
+        assert_eq!(rect, Rect::from_min_size(Pos2::new(102.0, 20.0), label.size()));
+        assert_eq!(label_rect(header(32.0), label, padding), None);
+        assert_eq!(
+            label_rect(
+                header(100.0),
+                Rect::from_min_size(Pos2::ZERO, Vec2::new(30.5, 13.0)),
+                padding
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn self_bar_spans_entire_trace() {
+        let rec = Recording::from_events(fixtures::build());
+        let rect_params = ProcRectParams::new(2.2, Vec2::new(0.5, 0.0), ZoomSettings::default(), 0.0);
+        let x_range = rect_params.trace_x_range();
+        assert_eq!(x_range.min, 0.0);
+        assert_eq!(
+            x_range.max,
+            rect_params
+                .proc_rect(rec.processes()[&Pid::from_raw(100)].time, 0, 1)
+                .max
+                .x
+        );
+        assert!((rect_params.time_at(x_range.max) - 2.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fuzzy_match_ranking() {
+        let best = |query: &str| {
+            PaletteAction::ALL
+                .iter()
+                .filter_map(|action| Some((fuzzy_score(query, &action.name())?, action.name())))
+                .max_by_key(|&(score, _)| score)
+                .map(|(_, name)| name)
+        };
+        assert_eq!(best("thr").as_deref(), Some("Toggle threads"));
+        assert_eq!(best("zoom").as_deref(), Some("Reset zoom"));
+        assert_eq!(best("rerun").as_deref(), Some("Re-run command"));
+        assert_eq!(best("crit").as_deref(), Some("Toggle critical path highlight"));
+        assert_eq!(best("xyz"), None);
+
+        // contiguous and word-start matches beat scattered ones, case doesn't matter
+        assert!(fuzzy_score("zoom", "Reset zoom") > fuzzy_score("zoom", "Zero of our maps"));
+        assert!(fuzzy_score("zoom", "Reset zoom") > fuzzy_score("zoom", "Resetzoom"));
+        assert_eq!(fuzzy_score("ZOOM", "Reset zoom"), fuzzy_score("zoom", "Reset zoom"));
+        assert_eq!(fuzzy_score("", "Reset zoom"), Some(0));
+        assert_eq!(fuzzy_score("mooz", "Reset zoom"), None);
+    }
+
+    #[test]
+    fn absolute_time_from_anchor() {
+        let anchor = UNIX_EPOCH + Duration::from_secs(13 * 3600 + 5 * 60 + 7);
+        let rec = Recording::from_events(vec![TraceEvent::TraceStart {
+            time: Instant::now(),
+            wall_time: anchor,
+        }]);
+
+        let wall_time = rec.wall_time(1.25).unwrap();
+        assert_eq!(wall_time, anchor + Duration::from_millis(1250));
+        assert_eq!(format_wall_time(wall_time), "13:05:08.250 UTC");
+        assert_eq!(format_wall_time(rec.wall_time(-0.5).unwrap()), "13:05:06.500 UTC");
+    }
+
+    #[test]
+    fn header_font_grows_with_vertical_zoom() {
+        let sizes = (-20..=20)
+            .map(|i| {
+                let zoom_linear = Vec2::new(0.0, i as f32 * 0.25);
+                ProcRectParams::new(1.0, zoom_linear, ZoomSettings::default(), 0.0)
+                    .header_font()
+                    .size
+            })
+            .collect_vec();
+
+        assert!(sizes.iter().tuple_windows().all(|(a, b)| a <= b));
+        assert!(sizes.first().unwrap() < sizes.last().unwrap());
+        assert!(sizes
+            .iter()
+            .all(|&size| (HEADER_FONT_SIZE_MIN..=HEADER_FONT_SIZE_MAX).contains(&size)));
+    }
+
+    #[test]
+    fn follow_tail_scroll_offset() {
+        let mut app = test_app();
+        app.follow_tail = true;
+        let viewport = |x: f32| Rect::from_min_size(Pos2::new(x, 0.0), Vec2::new(200.0, 100.0));
+
+        // the right edge of the content ends up at the right edge of the viewport
+        assert_eq!(app.follow_tail_delta(viewport(300.0), 1000.0), Some(-500.0));
+        assert_eq!(app.follow_tail_delta(viewport(800.0), 1100.0), Some(-100.0));
+
+        // scrolling left stops following
+        assert_eq!(app.follow_tail_delta(viewport(500.0), 1100.0), None);
+        assert!(!app.follow_tail);
+
+        // content narrower than the viewport stays at the start
+        let mut app = test_app();
+        assert_eq!(app.follow_tail_delta(viewport(0.0), 150.0), Some(0.0));
+    }
+
+    #[test]
+    fn scroll_target_for_pid() {
+        let rec = Recording::from_events(fixtures::build());
+        let placed = place_processes(&rec, false, LayoutSettings::default()).unwrap();
+        let zoom = ZoomSettings::default();
+        let rect_params = ProcRectParams::new(2.2, Vec2::ZERO, zoom, 0.0);
+
+        // the linker runs from 1.6 to 2.0
+        let rect = pid_timeline_rect(&rect_params, &rec, &placed, Pid::from_raw(104), false).unwrap();
+        assert_eq!(
+            rect.x_range(),
+            eframe::emath::Rangef::new(1.6 * zoom.hor, 2.0 * zoom.hor)
+        );
+        assert_eq!(rect.height(), zoom.ver);
+
+        let viewport_size = Vec2::new(100.0, 20.0);
+        let target = scroll_target_for_rect(rect, viewport_size);
+        assert_eq!(target + viewport_size / 2.0, rect.center().to_vec2());
+
+        // targets near the start don't scroll past it
+        let rect = pid_timeline_rect(&rect_params, &rec, &placed, Pid::from_raw(100), false).unwrap();
+        assert_eq!(scroll_target_for_rect(rect, Vec2::splat(10000.0)), Vec2::ZERO);
+
+        assert!(pid_timeline_rect(&rect_params, &rec, &placed, Pid::from_raw(999), false).is_none());
+    }
+
+    #[test]
+    fn cpu_utilization_colors() {
+        assert_eq!(get_utilization_hue(0.0), 220.0 / 360.0);
+        assert_eq!(get_utilization_hue(0.5), 110.0 / 360.0);
+        assert_eq!(get_utilization_hue(1.0), 0.0);
+        // multithreaded processes can exceed a single core
+        assert_eq!(get_utilization_hue(3.0), 0.0);
+        assert_eq!(get_utilization_hue(-1.0), 220.0 / 360.0);
+
+        let settings = ColorSettings {
+            mode: ColorMode::CpuUtilization,
+            ..ColorSettings::new()
+        };
+        let hsva = |color: Color32| Hsva::from(color);
+        let busy = get_process_color(&settings, true, "cc", Some(1.0), None);
+        let idle = get_process_color(&settings, true, "cc", Some(0.0), None);
+        assert!(hsva(busy.header).h < hsva(idle.header).h);
+        assert_eq!(hsva(busy.header).v, hsva(idle.header).v);
+
+        // processes without CPU time stay gray
+        let unknown = get_process_color(&settings, true, "cc", None, None);
+        assert_eq!(hsva(unknown.header).s, 0.0);
+    }
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn panel_state_is_persisted() {
+        let mut app = test_app();
+        app.panel_state = PanelState {
+            side_panel_open: false,
+            side_panel_width: 123.5,
+        };
+        let mut storage = MemoryStorage::default();
+        eframe::App::save(&mut app, &mut storage);
+
+        let loaded: PanelState = eframe::get_value(&storage, PANEL_STATE_KEY).unwrap();
+        assert!(!loaded.side_panel_open);
+        assert_eq!(loaded.side_panel_width, 123.5);
+
+        // missing state falls back to the default
+        let loaded: Option<PanelState> = eframe::get_value(&MemoryStorage::default(), PANEL_STATE_KEY);
+        assert!(loaded.unwrap_or_default().side_panel_open);
+    }
+
+    #[test]
+    fn same_hue_per_subtree() {
+        // a shell running two make invocations, each with its own children
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        for (parent, pid, path) in [
+            (1, 2, "/usr/bin/make"),
+            (1, 3, "/usr/bin/make"),
+            (2, 4, "/usr/bin/cc"),
+            (2, 5, "/usr/bin/ld"),
+            (3, 6, "/usr/bin/cc"),
+        ] {
+            events.extend(fixtures::process(Some(parent), pid, 0.0, path, &[]));
+        }
+        let rec = Recording::from_events(events);
+        let mut app = test_app();
+        app.derive_data(&rec);
+
+        let settings = ColorSettings {
+            mode: ColorMode::Subtree,
+            ..ColorSettings::new()
+        };
+        let header = |pid: i32| {
+            let info = &rec.processes()[&Pid::from_raw(pid)];
+            let subtree_index = app.subtree_index(&rec, info.pid);
+            get_process_color(&settings, true, &info.name().to_string_lossy(), None, subtree_index).header
+        };
+
+        assert_eq!(header(4), header(5));
+        assert_eq!(header(2), header(5));
+        assert_ne!(header(4), header(6));
+        assert_eq!(header(3), header(6));
+    }
+
+    #[test]
+    fn failed_spawn_shows_empty_state() {
+        let argv = [OsString::from("/nonexistent/wtf")];
+        let backend = crate::backend::Backend::Poll {
+            period: PollPeriod::Fixed(Duration::from_millis(10)),
+            fields: PollFields {
+                exec: true,
+                exec_always: false,
+                cpu_time: false,
+                fds: false,
+                group: false,
+                namespace: false,
+                memory: false,
+                credentials: false,
+            },
+        };
+        let Err(e) = backend.start(&argv[0], &argv, &Default::default()) else {
+            panic!("expected the spawn to fail");
+        };
+
+        let mut data = DataToGui::new(Recording::new(), LayoutSettings::default());
+        data.error = Some(e.to_string());
+        assert!(data.placed(false).is_none());
+        assert_eq!(
+            empty_state_text(&data.recording, data.error.as_deref()),
+            ("Tracing failed", "command not found".to_owned())
+        );
+
+        // without an error, it depends on whether the recording is still running
+        let mut rec = Recording::new();
+        assert_eq!(empty_state_text(&rec, None).0, "No processes recorded yet");
+        rec.report(TraceEvent::TraceEnd { time: 1.0 });
+        assert_eq!(empty_state_text(&rec, None).0, "No processes recorded");
+    }
+
+    #[test]
+    fn theme_toggle_changes_colors() {
+        let ctx = Context::default();
+        let settings = ColorSettings::new();
+        let header =
+            |ctx: &Context| get_process_color(&settings, ctx.style().visuals.dark_mode, "cc", None, None).header;
+
+        let dark = ctx.style().visuals.dark_mode;
+        let before = header(&ctx);
+        toggle_theme(&ctx);
+        assert_eq!(ctx.style().visuals.dark_mode, !dark);
+        assert_ne!(header(&ctx), before);
+
+        toggle_theme(&ctx);
+        assert_eq!(ctx.style().visuals.dark_mode, dark);
+        assert_eq!(header(&ctx), before);
+    }
+
+    #[test]
+    fn minimap_transform_round_trip() {
+        let transform = MinimapTransform {
+            content: Rect::from_min_size(Pos2::ZERO, Vec2::new(1000.0, 200.0)),
+            minimap: Rect::from_min_size(Pos2::new(10.0, 20.0), Vec2::new(100.0, 40.0)),
+        };
+
+        let viewport = Rect::from_min_size(Pos2::new(500.0, 100.0), Vec2::new(200.0, 50.0));
+        let mapped = transform.to_minimap(viewport);
+        assert_eq!(
+            mapped,
+            Rect::from_min_size(Pos2::new(60.0, 40.0), Vec2::new(20.0, 10.0))
+        );
+        assert_eq!(transform.to_content(mapped.min), viewport.min);
+        assert_eq!(transform.to_content(transform.minimap.max), transform.content.max);
+    }
+}
@@ -1,31 +1,172 @@
 use crate::record::{ProcessKind, Recording, TimeRange};
 use crate::util::MapExt;
 use indexmap::IndexMap;
-use itertools::{Either, Itertools};
+use itertools::Itertools;
 use nix::unistd::Pid;
 use ordered_float::OrderedFloat;
+use serde::Serialize;
 use std::cmp::min;
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::ops::{ControlFlow, Range};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PlacedProcess {
     pub pid: Pid,
+    pub kind: ProcessKind,
     pub time_bound: TimeRange,
 
     pub row_offset: usize,
     pub row_height: usize,
 
     pub children: Vec<PlacedProcess>,
+    /// Descendants hidden because of the depth limit, shown as a single bar in the row below the header.
+    pub folded: Option<FoldedDescendants>,
+    /// The number of processes merged into this one, only more than one in icicle mode.
+    pub merged: usize,
 }
 
-pub fn place_processes(rec: &Recording, include_threads: bool) -> Option<PlacedProcess> {
+#[derive(Debug, Copy, Clone)]
+pub struct FoldedDescendants {
+    pub count: usize,
+    /// The union of the time bounds of all hidden descendants.
+    pub time: TimeRange,
+}
+
+/// The structure of a [PlacedProcess] without the timing, see [PlacedProcess::to_debug_tree].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct PlacedDebugTree {
+    pub pid: i32,
+    pub row_offset: usize,
+    pub row_height: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folded: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PlacedDebugTree>,
+}
+
+/// Children that start and end at a given time.
+type ChildEvents = (Vec<(ProcessKind, Pid)>, Vec<Pid>);
+
+/// Settings that influence the layout, configured from the GUI.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct LayoutSettings {
+    /// Finished processes shorter than this many seconds are skipped.
+    pub min_duration: f64,
+    /// Processes at this depth are placed without their descendants, the root is at depth 0.
+    pub max_depth: Option<usize>,
+    pub mode: LayoutMode,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum LayoutMode {
+    /// Nest children below their parent, packing concurrent children into free rows.
+    #[default]
+    Packed,
+    /// Give each distinct executable its own row below the root, regardless of nesting.
+    Lanes,
+    /// Collapse the time axis: merge children with the same executable and size them by aggregated duration.
+    Icicle,
+}
+
+/// Place all processes in rows.
+/// Finished processes (including their children) that are shorter than `settings.min_duration` are skipped,
+/// but they still count towards the time bound of their parent.
+/// In lane mode, processes deeper than `settings.max_depth` are skipped.
+/// In icicle mode, `time_bound` is not a real time range but the horizontal extent in aggregated seconds.
+pub fn place_processes(rec: &Recording, include_threads: bool, settings: LayoutSettings) -> Option<PlacedProcess> {
     // TODO what about orphans?
     rec.root_pid.and_then(|root_pid| {
         let mut cache = TimeCache::new();
-        place_process(rec, include_threads, &mut cache, root_pid)
+        match settings.mode {
+            LayoutMode::Packed => place_process(
+                rec,
+                include_threads,
+                settings,
+                &mut cache,
+                None,
+                root_pid,
+                ProcessKind::Process,
+                0,
+            ),
+            LayoutMode::Lanes => place_lanes(rec, include_threads, settings, &mut cache, root_pid),
+            LayoutMode::Icicle => place_icicle(rec, include_threads, settings, ProcessKind::Process, vec![root_pid], 0),
+        }
     })
 }
 
+/// The state kept between calls to [place_processes_incremental] for the same growing recording.
+#[derive(Debug, Default)]
+pub struct LayoutCache {
+    key: Option<(bool, LayoutSettings)>,
+    revision: Option<u64>,
+    placed: Option<PlacedProcess>,
+    finished: FinishedCache,
+}
+
+/// Placed subtrees in which all processes have finished, keyed by pid and depth, with their [subtree_size].
+/// These rarely change anymore as the recording grows, so they can be reused as-is if their size didn't change.
+type FinishedCache = HashMap<(Pid, usize), (usize, PlacedProcess)>;
+
+/// Like [place_processes], but reuse as much as possible from the previous call with the same cache.
+/// The result is the same as a full rebuild, as long as the recording only grew since then.
+///
+/// Nothing is placed again if the recording revision didn't change, except for the icicle layout of a running recording,
+/// whose widths grow with the elapsed time.
+/// Otherwise only the packed layout is placed incrementally, reusing all subtrees that had already finished,
+/// the other modes are placed from scratch.
+pub fn place_processes_incremental(
+    rec: &Recording,
+    prev: &mut LayoutCache,
+    include_threads: bool,
+    settings: LayoutSettings,
+) -> Option<PlacedProcess> {
+    let key = (include_threads, settings);
+    if prev.key != Some(key) {
+        *prev = LayoutCache {
+            key: Some(key),
+            ..LayoutCache::default()
+        };
+    }
+    let tracks_elapsed = settings.mode == LayoutMode::Icicle && rec.time_end.is_none();
+    if prev.revision == Some(rec.revision()) && !tracks_elapsed {
+        return prev.placed.clone();
+    }
+
+    let placed = match settings.mode {
+        LayoutMode::Packed => rec.root_pid.and_then(|root_pid| {
+            let mut reuse = Reuse {
+                prev: std::mem::take(&mut prev.finished),
+                next: FinishedCache::new(),
+            };
+            let placed = place_process(
+                rec,
+                include_threads,
+                settings,
+                &mut TimeCache::new(),
+                Some(&mut reuse),
+                root_pid,
+                ProcessKind::Process,
+                0,
+            );
+            prev.finished = reuse.next;
+            placed
+        }),
+        LayoutMode::Lanes | LayoutMode::Icicle => place_processes(rec, include_threads, settings),
+    };
+
+    prev.revision = Some(rec.revision());
+    prev.placed = placed.clone();
+    placed
+}
+
+/// The finished subtrees of the previous layout, and those of the current one.
+/// Only the subtrees that are still part of the current layout are kept.
+struct Reuse {
+    prev: FinishedCache,
+    next: FinishedCache,
+}
+
 impl PlacedProcess {
     pub fn visit<R>(
         &self,
@@ -51,34 +192,269 @@ impl PlacedProcess {
 
         visit_impl(self, 0, &mut f_before, &mut f_after);
     }
+
+    /// The rows assigned to this process and its descendants, as a plain tree that can be compared or serialized,
+    /// eg. to check layout changes against a saved snapshot.
+    pub fn to_debug_tree(&self) -> PlacedDebugTree {
+        PlacedDebugTree {
+            pid: self.pid.as_raw(),
+            row_offset: self.row_offset,
+            row_height: self.row_height,
+            folded: self.folded.map(|folded| folded.count),
+            children: self.children.iter().map(PlacedProcess::to_debug_tree).collect(),
+        }
+    }
 }
 
-fn place_process(rec: &Recording, include_threads: bool, cache: &mut TimeCache, pid: Pid) -> Option<PlacedProcess> {
-    let info = rec.processes.get(&pid)?;
+fn place_lanes(
+    rec: &Recording,
+    include_threads: bool,
+    settings: LayoutSettings,
+    cache: &mut TimeCache,
+    root_pid: Pid,
+) -> Option<PlacedProcess> {
+    rec.processes().get(&root_pid)?;
 
-    // filter/flatten children
-    let children = if include_threads {
-        Either::Left(info.children.iter().map(|&(_, c)| c))
-    } else {
-        let mut children = vec![];
-        rec.for_each_process_child(pid, &mut |kind, child_pid| {
-            match kind {
-                ProcessKind::Process => children.push(child_pid),
-                ProcessKind::Thread => { /* skip threads */ }
+    // collect all visible descendants, skipping the same processes as the packed layout
+    let mut todo = vec![(root_pid, 0)];
+    let mut descendants = vec![];
+    while let Some((pid, depth)) = todo.pop() {
+        if settings.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+        for (kind, child) in visible_children(rec, include_threads, pid) {
+            let cb = process_time_bound(rec, cache, child);
+            if Some(cb.start) == cb.end || cb.end.is_some_and(|end| end - cb.start < settings.min_duration) {
+                continue;
+            }
+            descendants.push((kind, child));
+            todo.push((child, depth + 1));
+        }
+    }
+
+    // assign lanes in order of first appearance
+    descendants.sort_by(|&(_, a), &(_, b)| {
+        rec.processes()[&a]
+            .time
+            .start
+            .total_cmp(&rec.processes()[&b].time.start)
+    });
+    let mut lanes: IndexMap<&OsStr, usize> = IndexMap::new();
+    let mut placed_children = vec![];
+    for (kind, pid) in descendants {
+        let info = &rec.processes()[&pid];
+        let lanes_len = lanes.len();
+        let lane = *lanes.entry(info.name()).or_insert(lanes_len);
+
+        placed_children.push(PlacedProcess {
+            pid,
+            kind,
+            time_bound: info.time,
+            row_offset: 1 + lane,
+            row_height: 1,
+            children: vec![],
+            folded: None,
+            merged: 1,
+        });
+    }
+
+    Some(PlacedProcess {
+        pid: root_pid,
+        kind: ProcessKind::Process,
+        time_bound: process_time_bound(rec, cache, root_pid),
+        row_offset: 0,
+        row_height: 1 + lanes.len(),
+        children: placed_children,
+        folded: None,
+        merged: 1,
+    })
+}
+
+/// Place the given processes, which all share the same executable and ancestry, as a single icicle node.
+/// The width of a node is the summed duration of its processes, widened if needed to fit its children,
+/// which are laid out left to right from widest to narrowest.
+/// Returns `None` for empty or too short nodes.
+fn place_icicle(
+    rec: &Recording,
+    include_threads: bool,
+    settings: LayoutSettings,
+    kind: ProcessKind,
+    pids: Vec<Pid>,
+    depth: usize,
+) -> Option<PlacedProcess> {
+    let &pid = pids.first()?;
+    let time_end = rec.elapsed().unwrap_or(0.0);
+    let self_time: f64 = pids
+        .iter()
+        .filter_map(|p| rec.processes().get(p))
+        .map(|p| p.time.end.unwrap_or(time_end) - p.time.start)
+        .sum();
+
+    // merge the children of all processes by executable, in order of first appearance
+    let mut groups: IndexMap<&OsStr, (ProcessKind, Vec<Pid>)> = IndexMap::new();
+    if settings.max_depth.is_none_or(|max_depth| depth < max_depth) {
+        for &p in &pids {
+            for (kind, child) in visible_children(rec, include_threads, p) {
+                if let Some(child_info) = rec.processes().get(&child) {
+                    groups.entry(child_info.name()).or_insert((kind, vec![])).1.push(child);
+                }
             }
+        }
+    }
+
+    let mut children = groups
+        .into_values()
+        .filter_map(|(kind, group)| place_icicle(rec, include_threads, settings, kind, group, depth + 1))
+        .collect_vec();
+    children.sort_by(|a, b| icicle_width(b).total_cmp(&icicle_width(a)));
+
+    // lay out children next to each other
+    let mut offset = 0.0;
+    let mut row_height = 1;
+    for child in &mut children {
+        let width = icicle_width(child);
+        shift_time(child, offset);
+        offset += width;
+        child.row_offset = 1;
+        row_height = row_height.max(1 + child.row_height);
+    }
+
+    let width = self_time.max(offset);
+    if width <= 0.0 || width < settings.min_duration {
+        return None;
+    }
+
+    Some(PlacedProcess {
+        pid,
+        kind,
+        time_bound: TimeRange {
+            start: 0.0,
+            end: Some(width),
+        },
+        row_offset: 0,
+        row_height,
+        merged: pids.len(),
+        children,
+        folded: None,
+    })
+}
+
+fn icicle_width(placed: &PlacedProcess) -> f64 {
+    placed.time_bound.end.unwrap_or(placed.time_bound.start) - placed.time_bound.start
+}
+
+fn shift_time(placed: &mut PlacedProcess, delta: f64) {
+    placed.time_bound.start += delta;
+    placed.time_bound.end = placed.time_bound.end.map(|end| end + delta);
+    for child in &mut placed.children {
+        shift_time(child, delta);
+    }
+}
+
+/// The children of the given process to show, with threads flattened away if they're not included.
+fn visible_children(rec: &Recording, include_threads: bool, pid: Pid) -> Vec<(ProcessKind, Pid)> {
+    let mut children = vec![];
+    if include_threads {
+        if let Some(info) = rec.processes().get(&pid) {
+            children.extend_from_slice(&info.children);
+        }
+    } else {
+        rec.for_each_process_child(pid, &mut |kind, child_pid| match kind {
+            ProcessKind::Process => children.push((kind, child_pid)),
+            ProcessKind::Thread => { /* skip threads */ }
         });
-        Either::Right(children.into_iter())
+    }
+    children
+}
+
+/// Place a process and its descendants, first trying to reuse a finished subtree if `reuse` is given.
+#[expect(clippy::too_many_arguments, reason = "internal recursive helper")]
+fn place_process(
+    rec: &Recording,
+    include_threads: bool,
+    settings: LayoutSettings,
+    cache: &mut TimeCache,
+    reuse: Option<&mut Reuse>,
+    pid: Pid,
+    kind: ProcessKind,
+    depth: usize,
+) -> Option<PlacedProcess> {
+    let Some(reuse) = reuse else {
+        return place_process_new(rec, include_threads, settings, cache, None, pid, kind, depth);
+    };
+
+    // running subtrees can still change
+    let time_bound = process_time_bound(rec, cache, pid);
+    if time_bound.end.is_none() {
+        return place_process_new(rec, include_threads, settings, cache, Some(reuse), pid, kind, depth);
+    }
+
+    // the time bound also protects against pid reuse,
+    //   the size catches execs and children that were only reported after the subtree finished
+    let size = subtree_size(rec, pid);
+    let placed = match reuse.prev.remove(&(pid, depth)) {
+        Some((prev_size, prev)) if prev.kind == kind && prev.time_bound == time_bound && prev_size == size => prev,
+        _ => place_process_new(rec, include_threads, settings, cache, Some(reuse), pid, kind, depth)?,
+    };
+    reuse.next.insert((pid, depth), (size, placed.clone()));
+    Some(placed)
+}
+
+/// The number of processes, execs and child entries in the subtree of `pid`.
+fn subtree_size(rec: &Recording, pid: Pid) -> usize {
+    let Some(info) = rec.processes().get(&pid) else {
+        return 0;
     };
+    1 + info.execs.len() + info.children.len() + info.children.iter().map(|&(_, c)| subtree_size(rec, c)).sum::<usize>()
+}
+
+#[expect(clippy::too_many_arguments, reason = "internal recursive helper")]
+fn place_process_new(
+    rec: &Recording,
+    include_threads: bool,
+    settings: LayoutSettings,
+    cache: &mut TimeCache,
+    mut reuse: Option<&mut Reuse>,
+    pid: Pid,
+    kind: ProcessKind,
+    depth: usize,
+) -> Option<PlacedProcess> {
+    rec.processes().get(&pid)?;
+
+    // stop descending at the depth limit, summarize the descendants instead
+    if settings.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+        let folded = fold_descendants(rec, include_threads, cache, pid);
+        return Some(PlacedProcess {
+            pid,
+            kind,
+            time_bound: process_time_bound(rec, cache, pid),
+            row_offset: 0,
+            row_height: if folded.is_some() { 2 } else { 1 },
+            children: vec![],
+            folded,
+            merged: 1,
+        });
+    }
+
+    // filter/flatten children
+    let children = visible_children(rec, include_threads, pid);
 
     // collect all relevant time points and the processes that start/end that happen at those times
-    let mut time_to_events: IndexMap<OrderedFloat<f32>, (Vec<Pid>, Vec<Pid>)> = IndexMap::new();
-    for c in children {
+    let mut time_to_events: IndexMap<OrderedFloat<f64>, ChildEvents> = IndexMap::new();
+    for (c_kind, c) in children {
         let cb = process_time_bound(rec, cache, c);
         if Some(cb.start) == cb.end {
             // TODO can we leave these in? they're tricky because they start and stop in the same cycle
             continue;
         }
-        time_to_events.entry(OrderedFloat(cb.start)).or_default().0.push(c);
+        if cb.end.is_some_and(|end| end - cb.start < settings.min_duration) {
+            continue;
+        }
+        time_to_events
+            .entry(OrderedFloat(cb.start))
+            .or_default()
+            .0
+            .push((c_kind, c));
         if let Some(cb_end) = cb.end {
             time_to_events.entry(OrderedFloat(cb_end)).or_default().1.push(c);
         }
@@ -90,42 +466,93 @@ fn place_process(rec: &Recording, include_threads: bool, cache: &mut TimeCache,
         .collect_vec();
 
     // simulate time from left to right
-    let mut free = FreeList::new();
-    let mut children_active: IndexMap<Pid, Range<usize>> = IndexMap::new();
+    // (threads get their own band of rows directly below the header, so the threads of a process form a block)
+    let mut free = [FreeList::new(), FreeList::new()];
+    let band = |kind: ProcessKind| match kind {
+        ProcessKind::Thread => 0,
+        ProcessKind::Process => 1,
+    };
+    let mut children_active: IndexMap<Pid, (ProcessKind, Range<usize>)> = IndexMap::new();
     let mut placed_children = vec![];
 
     for (children_start, children_end) in sorted_events {
         // handle child ends (first to allow immediately reusing rows)
         for child in children_end {
-            if let Some(range) = children_active.swap_remove(&child) {
-                free.release(range)
+            if let Some((child_kind, range)) = children_active.swap_remove(&child) {
+                free[band(child_kind)].release(range)
             }
         }
 
         // handle child starts
-        for child in children_start {
-            if let Some(mut child_placed) = place_process(rec, include_threads, cache, child) {
+        for (child_kind, child) in children_start {
+            if let Some(mut child_placed) = place_process(
+                rec,
+                include_threads,
+                settings,
+                cache,
+                reuse.as_deref_mut(),
+                child,
+                child_kind,
+                depth + 1,
+            ) {
                 assert_eq!(child_placed.row_offset, 0);
 
                 let child_height = child_placed.row_height;
-                let child_row = free.allocate(child_height);
+                let child_row = free[band(child_kind)].allocate(child_height);
                 child_placed.row_offset = 1 + child_row;
-                children_active.insert_first(child, child_row..child_row + child_height);
+                children_active.insert_first(child, (child_kind, child_row..child_row + child_height));
                 placed_children.push(child_placed);
             }
         }
     }
 
+    // move the child processes below the thread band
+    let [free_threads, free_processes] = free;
+    let thread_rows = free_threads.len();
+    for child in &mut placed_children {
+        if child.kind == ProcessKind::Process {
+            child.row_offset += thread_rows;
+        }
+    }
+
     // combine everything
     Some(PlacedProcess {
         pid,
+        kind,
         time_bound: process_time_bound(rec, cache, pid),
         row_offset: 0,
-        row_height: 1 + free.len(),
+        row_height: 1 + thread_rows + free_processes.len(),
         children: placed_children,
+        folded: None,
+        merged: 1,
     })
 }
 
+fn fold_descendants(
+    rec: &Recording,
+    include_threads: bool,
+    cache: &mut TimeCache,
+    pid: Pid,
+) -> Option<FoldedDescendants> {
+    let mut folded: Option<FoldedDescendants> = None;
+
+    let mut todo = vec![pid];
+    while let Some(curr) = todo.pop() {
+        for (_, child) in visible_children(rec, include_threads, curr) {
+            let child_bound = process_time_bound(rec, cache, child);
+            let folded = folded.get_or_insert(FoldedDescendants {
+                count: 0,
+                time: child_bound,
+            });
+            folded.count += 1;
+            folded.time = folded.time.union(child_bound);
+            todo.push(child);
+        }
+    }
+
+    folded
+}
+
 type TimeCache = IndexMap<Pid, TimeRange>;
 
 fn process_time_bound(rec: &Recording, cache: &mut TimeCache, pid: Pid) -> TimeRange {
@@ -133,8 +560,8 @@ fn process_time_bound(rec: &Recording, cache: &mut TimeCache, pid: Pid) -> TimeR
         return res;
     }
 
-    let mut start = f32::MAX;
-    let mut end = Some(f32::MIN);
+    let mut start = f64::MAX;
+    let mut end = Some(f64::MIN);
 
     let mut join_range = |range: TimeRange| {
         start = start.min(range.start);
@@ -144,7 +571,7 @@ fn process_time_bound(rec: &Recording, cache: &mut TimeCache, pid: Pid) -> TimeR
         };
     };
 
-    if let Some(info) = rec.processes.get(&pid) {
+    if let Some(info) = rec.processes().get(&pid) {
         join_range(info.time);
         for exec in &info.execs {
             join_range(TimeRange {
@@ -206,3 +633,249 @@ impl FreeList {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{fixtures, TraceEvent};
+
+    fn settings(mode: LayoutMode) -> LayoutSettings {
+        LayoutSettings {
+            min_duration: 0.0,
+            max_depth: None,
+            mode,
+        }
+    }
+
+    // the pids of all placed processes, depth-first
+    fn placed_pids(placed: &PlacedProcess) -> Vec<i32> {
+        let mut pids = vec![placed.pid.as_raw()];
+        for child in &placed.children {
+            pids.extend(placed_pids(child));
+        }
+        pids
+    }
+
+    #[test]
+    fn debug_tree_snapshot() {
+        let rec = Recording::from_events(fixtures::build());
+        let placed = place_processes(&rec, false, settings(LayoutMode::Packed)).unwrap();
+        let snapshot = serde_json::to_value(placed.to_debug_tree()).unwrap();
+        // ld reuses the row of the first cc, which exited before it started
+        let expected = serde_json::json!({
+            "pid": 100, "row_offset": 0, "row_height": 4, "children": [
+                {"pid": 101, "row_offset": 1, "row_height": 3, "children": [
+                    {"pid": 102, "row_offset": 1, "row_height": 1},
+                    {"pid": 103, "row_offset": 2, "row_height": 1},
+                    {"pid": 104, "row_offset": 1, "row_height": 1},
+                ]},
+            ],
+        });
+        assert_eq!(snapshot, expected);
+    }
+
+    #[test]
+    fn min_duration_hides_short_processes() {
+        let rec = Recording::from_events(fixtures::build());
+        let placed = |min_duration: f64| {
+            let settings = LayoutSettings {
+                min_duration,
+                ..settings(LayoutMode::Packed)
+            };
+            place_processes(&rec, false, settings).unwrap()
+        };
+
+        assert_eq!(placed_pids(&placed(0.0)), [100, 101, 102, 103, 104]);
+        // ld takes 0.4s, the first compiler 1.0s and the second one 1.2s
+        assert_eq!(placed_pids(&placed(0.5)), [100, 101, 102, 103]);
+        assert_eq!(placed_pids(&placed(1.1)), [100, 101, 103]);
+
+        // the hidden processes still fold into the bound of their parent
+        assert_eq!(placed(1.1).time_bound, placed(0.0).time_bound);
+    }
+
+    #[test]
+    fn thread_children_carry_their_kind() {
+        let mut events = fixtures::threads();
+        events.extend(fixtures::process(Some(200), 204, 0.9, "/bin/sh", &["sh"]));
+        let rec = Recording::from_events(events);
+
+        let placed = place_processes(&rec, true, settings(LayoutMode::Packed)).unwrap();
+        assert_eq!(placed.kind, ProcessKind::Process);
+        let kinds = placed
+            .children
+            .iter()
+            .map(|child| (child.pid.as_raw(), child.kind))
+            .sorted_by_key(|&(pid, _)| pid)
+            .collect_vec();
+        assert_eq!(
+            kinds,
+            [
+                (201, ProcessKind::Thread),
+                (202, ProcessKind::Thread),
+                (203, ProcessKind::Thread),
+                (204, ProcessKind::Process),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_depth_folds_grandchildren() {
+        let rec = Recording::from_events(fixtures::build());
+        let settings = LayoutSettings {
+            max_depth: Some(1),
+            ..settings(LayoutMode::Packed)
+        };
+        let placed = place_processes(&rec, false, settings).unwrap();
+
+        assert_eq!(placed_pids(&placed), [100, 101]);
+        assert!(placed.folded.is_none());
+        let folded = placed.children[0].folded.unwrap();
+        assert_eq!(folded.count, 3);
+        assert_eq!(
+            folded.time,
+            TimeRange {
+                start: 0.2,
+                end: Some(2.0)
+            }
+        );
+    }
+
+    #[test]
+    fn lanes_group_same_names() {
+        let rec = Recording::from_events(fixtures::build());
+        let placed = place_processes(&rec, false, settings(LayoutMode::Lanes)).unwrap();
+
+        let rows = placed
+            .children
+            .iter()
+            .map(|child| (child.pid.as_raw(), child.row_offset))
+            .sorted_by_key(|&(pid, _)| pid)
+            .collect_vec();
+        // make, both compilers sharing a lane, and the linker
+        assert_eq!(rows, [(101, 1), (102, 2), (103, 2), (104, 3)]);
+        assert_eq!(placed.row_height, 4);
+    }
+
+    #[test]
+    fn icicle_widths_are_summed_durations() {
+        let rec = Recording::from_events(fixtures::build());
+        let placed = place_processes(&rec, false, settings(LayoutMode::Icicle)).unwrap();
+        let assert_close = |a: f64, b: f64| assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+
+        let make = &placed.children[0];
+        let [cc, ld] = &make.children[..] else {
+            panic!("expected two children, got {}", make.children.len());
+        };
+        // both compilers are merged into a single node
+        assert_eq!((cc.pid.as_raw(), cc.merged), (102, 2));
+        assert_close(icicle_width(cc), 1.0 + 1.2);
+        assert_close(icicle_width(ld), 0.4);
+        // children are laid out next to each other
+        assert_close(ld.time_bound.start, cc.time_bound.end.unwrap());
+
+        // make only ran for 2.0s, but grows to fit its children
+        assert_close(icicle_width(make), 2.6);
+        assert_close(icicle_width(&placed), 2.6);
+    }
+
+    #[test]
+    fn incremental_matches_full_rebuild() {
+        // a second build after the first one, so finished subtrees get reused
+        let mut events = fixtures::build();
+        let tail = events.split_off(events.len() - 2);
+        events.extend(fixtures::process(
+            Some(100),
+            110,
+            2.15,
+            "/usr/bin/make",
+            &["make", "install"],
+        ));
+        events.extend(fixtures::process(
+            Some(110),
+            111,
+            2.16,
+            "/usr/bin/install",
+            &["install"],
+        ));
+        events.push(fixtures::exit(111, 2.17, Some(0)));
+        events.push(fixtures::exit(110, 2.18, Some(0)));
+        events.extend(tail);
+
+        for include_threads in [false, true] {
+            let settings = settings(LayoutMode::Packed);
+            let mut cache = LayoutCache::default();
+            let mut rec = Recording::new();
+            for event in events.clone() {
+                rec.report(event);
+                let incremental = place_processes_incremental(&rec, &mut cache, include_threads, settings);
+                let full = place_processes(&rec, include_threads, settings);
+                assert_eq!(
+                    incremental.map(|placed| placed.to_debug_tree()),
+                    full.map(|placed| placed.to_debug_tree())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn threads_are_placed_right_below_parent() {
+        // a child process that starts after some threads have exited, it could reuse their rows
+        let mut events = fixtures::threads();
+        events.extend(fixtures::process(Some(200), 204, 0.9, "/bin/sh", &["sh"]));
+        let rec = Recording::from_events(events);
+
+        let placed = place_processes(&rec, true, settings(LayoutMode::Packed)).unwrap();
+        let rows = placed
+            .children
+            .iter()
+            .map(|child| (child.pid.as_raw(), child.row_offset))
+            .sorted_by_key(|&(pid, _)| pid)
+            .collect_vec();
+        assert_eq!(rows, [(201, 1), (202, 2), (203, 3), (204, 4)]);
+    }
+
+    #[test]
+    fn late_child_of_finished_process_is_placed() {
+        // stop before make and the shell exit, so the root is still running
+        let mut events = fixtures::build();
+        events.truncate(events.len() - 3);
+        let mut rec = Recording::from_events(events);
+
+        let settings = settings(LayoutMode::Packed);
+        let mut cache = LayoutCache::default();
+        place_processes_incremental(&rec, &mut cache, false, settings);
+
+        // a child of the finished compiler is only reported now, within its time bound
+        let (cc, child) = (Pid::from_raw(102), Pid::from_raw(105));
+        rec.report(TraceEvent::ProcessChild {
+            parent: cc,
+            child,
+            kind: ProcessKind::Process,
+        });
+        rec.report(TraceEvent::ProcessStart { pid: child, time: 0.5 });
+        rec.report(TraceEvent::ProcessExit {
+            pid: child,
+            time: 0.6,
+            exit_code: Some(0),
+            signal: None,
+        });
+
+        let incremental = place_processes_incremental(&rec, &mut cache, false, settings).unwrap();
+        let full = place_processes(&rec, false, settings).unwrap();
+        assert_eq!(incremental.to_debug_tree(), full.to_debug_tree());
+    }
+
+    #[test]
+    fn icicle_of_running_recording_grows() {
+        let rec = Recording::from_events(fixtures::threads());
+        let settings = settings(LayoutMode::Icicle);
+        let mut cache = LayoutCache::default();
+
+        let width = |placed: Option<PlacedProcess>| icicle_width(&placed.unwrap());
+        let first = width(place_processes_incremental(&rec, &mut cache, true, settings));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let second = width(place_processes_incremental(&rec, &mut cache, true, settings));
+        assert!(second > first);
+    }
+}
@@ -2,10 +2,16 @@
 #![allow(clippy::new_without_default)]
 #![allow(clippy::manual_flatten)]
 
+pub mod backend;
+pub mod capture;
+pub mod export;
 pub mod gui;
 pub mod layout;
 pub mod poll;
 pub mod record;
+#[cfg(feature = "tiny_http")]
+pub mod serve;
+pub mod stats;
 pub mod swrite;
 pub mod trace;
 pub mod util;
@@ -1,166 +1,1439 @@
 #![cfg(unix)]
 
 use clap::Parser;
-use crossbeam::channel::{Receiver, RecvError, SendError, TryRecvError};
+use crossbeam::channel::{Receiver, RecvError, TryRecvError};
 use itertools::Itertools;
-use std::ffi::{CString, OsString};
-use std::ops::ControlFlow;
-use std::os::unix::ffi::OsStrExt;
+use nix::unistd::Pid;
+use regex::bytes::Regex;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use wtf::gui::{main_gui, DataToGui, GuiHandle};
-use wtf::layout::place_processes;
-use wtf::poll::record_poll;
-use wtf::record::Recording;
-use wtf::trace::{record_trace, TraceEvent};
+use wtf::backend::{record_started_with, spawn_backend, Backend, CommandOptions};
+use wtf::export::critical_path::write_critical_path;
+use wtf::export::csv::write_csv;
+use wtf::export::events::{write_events_json, write_events_jsonl};
+use wtf::export::otlp::write_otlp_json;
+use wtf::export::redact::{RedactArgs, RedactOptions};
+use wtf::gui::{main_gui, DataLayoutCache, DataToGui, GuiHandle};
+use wtf::layout::LayoutSettings;
+use wtf::poll::{PollField, PollFields, PollPeriod};
+use wtf::record::{EventLog, LabelMode, Recording, RecordingLimits, TimeAlign};
+use wtf::stats::{summary_text, trace_diff, trace_diff_text};
+#[cfg(target_os = "linux")]
+use wtf::trace::check_ptrace_support;
+use wtf::trace::{TraceError, TraceEvent};
+
+const SUMMARY_TOP_EXECUTABLES: usize = 10;
 
 #[derive(Debug, Parser)]
 struct Args {
-    /// Use ptrace instead of polling for tracing.
+    /// Use ptrace instead of polling for tracing. Only supported on linux.
+    #[cfg(target_os = "linux")]
     #[arg(long)]
     ptrace: bool,
+    /// Use ptrace if it's supported by the kernel, and fall back to polling otherwise. Only supported on linux.
+    #[cfg(target_os = "linux")]
+    #[arg(long, conflicts_with = "ptrace")]
+    auto: bool,
+    /// Also record failed exec attempts, eg. a shell trying each `$PATH` entry, to debug "command not found" errors.
+    /// Only used with ptrace.
+    #[cfg(target_os = "linux")]
+    #[arg(long)]
+    record_exec_failures: bool,
     /// The polling frequency in Hz. Only used when polling, the default if `--poll` is not specified.
     #[arg(long, default_value_t = 60.0)]
     poll_freq: f32,
+    /// Poll adaptively: at `--poll-freq` while processes are starting and exiting,
+    /// backing off down to this frequency in Hz while the process tree is stable. Only used when polling.
+    #[arg(long)]
+    poll_freq_min: Option<f32>,
+    /// Which data to sample for each process every polling tick, the process tree itself is always sampled.
+    /// Only used when polling, the other fields cost extra reads for every process every tick.
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [PollField::Exec])]
+    poll_fields: Vec<PollField>,
+    /// Re-read the executable and arguments of each process every polling tick,
+    /// instead of only when it's first seen or its executable changes.
+    /// This also catches an exec of the same executable with different arguments, at a higher polling cost.
+    #[arg(long)]
+    poll_exec_always: bool,
+    /// Count the open file descriptors of each process every polling tick, and record the peak.
+    /// Only used when polling, the same as adding `fds` to `--poll-fields`.
+    #[arg(long)]
+    track_fds: bool,
+    /// Only show processes up to this nesting depth, deeper descendants are summarized in a single bar.
+    /// This can also be changed from the GUI.
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Record the stdout and stderr of the command with timestamps, so they can be shown along the timeline.
+    /// The output is still passed through to the terminal.
+    #[arg(long)]
+    capture_output: bool,
+    /// Run the command in this working directory instead of the current one.
+    #[arg(long)]
+    cwd: Option<PathBuf>,
+    /// Set an environment variable for the command, on top of the inherited environment. Can be repeated.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env)]
+    env: Vec<(OsString, OsString)>,
     /// The layout frequency in Hz.
     #[arg(long, default_value_t = 10.0)]
     layout_freq: f32,
+    /// The maximum frequency in Hz at which tracing heartbeats are forwarded to the collector.
+    #[arg(long, default_value_t = 10.0)]
+    heartbeat_freq: f32,
 
-    #[arg(trailing_var_arg = true, required = true, num_args = 1..)]
+    /// Keep at most this many processes in memory while tracing, by evicting the oldest finished subtrees
+    /// below the root process. Evicted processes are not part of the GUI or the final outputs.
+    #[arg(long)]
+    max_processes: Option<usize>,
+    /// Stop recording new processes once this many were recorded, to protect against fork bombs.
+    /// Unlike `--max-processes` this also limits running processes. Tracing continues until the command finishes.
+    #[arg(long)]
+    process_limit: Option<usize>,
+    /// Don't record processes nested deeper than this, the command itself is at depth 0.
+    /// Unlike `--max-depth` this affects the recording itself, not just what's shown.
+    #[arg(long)]
+    depth_limit: Option<usize>,
+    /// Append evicted processes to this file, as one json object per line.
+    #[arg(long, requires = "max_processes")]
+    evicted_out: Option<PathBuf>,
+    /// Show wtf itself as a bar spanning the whole trace, to account for the tracer overhead.
+    #[arg(long, conflicts_with_all = ["load", "compare", "load_jsonl", "no_gui", "summary"])]
+    show_self: bool,
+    /// Save the final recording to this file.
+    #[arg(long)]
+    save: Option<PathBuf>,
+    /// Write all events reported while tracing to this file, as a single JSON array in the order they were reported.
+    /// This is not affected by `--root-pid` or `--redact`.
+    #[arg(long, conflicts_with_all = ["load", "compare", "load_jsonl", "root_pid", "redact"])]
+    events_json: Option<PathBuf>,
+    /// Like `--events-json`, but write one event per line, which can be loaded again with `--load-jsonl`.
+    #[arg(long, conflicts_with_all = ["load", "compare", "load_jsonl", "root_pid", "redact"])]
+    events_jsonl: Option<PathBuf>,
+    /// Export the process list of the final recording as a CSV file.
+    #[arg(long)]
+    export_csv: Option<PathBuf>,
+    /// Export the final recording as OTLP/JSON spans, one per process.
+    #[arg(long)]
+    export_otlp: Option<PathBuf>,
+    /// Export the critical path of the final recording as a report, with the duration and cumulative time of each step.
+    /// The report is written as JSON if the file has a `.json` extension, and as plain text otherwise.
+    #[arg(long)]
+    export_critical_path: Option<PathBuf>,
+    /// Export the final recording as a native Perfetto protobuf trace, which can be opened at https://ui.perfetto.dev.
+    #[cfg(feature = "prost")]
+    #[arg(long)]
+    export_perfetto: Option<PathBuf>,
+    /// Send the final recording as OTLP/JSON spans to this OTLP/HTTP collector, eg. `http://localhost:4318`.
+    #[cfg(feature = "ureq")]
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+    /// Export the final recording as an SQLite database.
+    #[cfg(feature = "rusqlite")]
+    #[arg(long)]
+    export_sqlite: Option<PathBuf>,
+    /// Only write the subtree starting at this pid to the exported files, eg. to share the relevant part of a huge trace.
+    /// The recording shown in the GUI is not affected.
+    #[arg(long)]
+    root_pid: Option<i32>,
+    /// Redact the exported files for sharing, replacing the home directory with `~`.
+    /// The recording shown in the GUI is not affected.
+    #[arg(long)]
+    redact: bool,
+    /// What to do with process arguments when redacting.
+    #[arg(long, value_enum, default_value_t = RedactArgs::Keep, requires = "redact")]
+    redact_args: RedactArgs,
+    /// An additional regex replacement applied to paths and arguments when redacting, can be repeated.
+    #[arg(long, num_args = 2, value_names = ["REGEX", "REPLACEMENT"], requires = "redact")]
+    redact_rule: Vec<String>,
+    /// Don't open the GUI, instead print a text summary to stdout once tracing is done.
+    #[arg(long)]
+    summary: bool,
+    /// Don't open the GUI or print anything, just run the command to completion and exit with its exit code.
+    /// Combine with `--save` or the export flags to keep the recording.
+    #[arg(long, conflicts_with_all = ["summary", "load", "compare", "load_jsonl"])]
+    no_gui: bool,
+    /// What to do with the final recording, as a comma-separated list of `gui`, `summary` and `save:<path>`,
+    /// eg. `save:trace.json,summary`. This replaces `--save`, `--summary` and `--no-gui`,
+    /// without `gui` the command runs headless and its exit code is passed through unless a summary is printed.
+    #[arg(long, value_delimiter = ',', value_parser = parse_complete_action, conflicts_with_all = ["save", "summary", "no_gui"])]
+    on_complete: Vec<CompleteAction>,
+    /// Serve the live recording as json over HTTP on this address while tracing, eg. `127.0.0.1:8080`.
+    /// The endpoints are `/recording`, `/processes` and `/stats`.
+    #[cfg(feature = "tiny_http")]
+    #[arg(long, conflicts_with_all = ["load", "compare", "load_jsonl"])]
+    serve: Option<String>,
+    /// Load and show previously saved recordings instead of running a command.
+    /// Multiple recordings are stacked vertically in the same timeline. Pass `-` to read a recording from stdin.
+    #[arg(long, num_args = 1.., conflicts_with = "command")]
+    load: Vec<PathBuf>,
+    /// Load two saved recordings of the same command and compare them, showing which executables got slower or faster.
+    /// Both recordings are shown in the same timeline, like `--load`.
+    #[arg(long, num_args = 2, value_names = ["BEFORE", "AFTER"], conflicts_with_all = ["command", "load"])]
+    compare: Vec<PathBuf>,
+    /// Load and show a recording from the events written by `--events-jsonl` instead of running a command.
+    /// Pass `-` to read the events from stdin. A truncated last line, eg. from an interrupted capture, is ignored.
+    #[arg(long, conflicts_with_all = ["command", "load", "compare"])]
+    load_jsonl: Option<PathBuf>,
+    /// How to align the time axes of multiple loaded recordings.
+    #[arg(long, value_enum, default_value_t = TimeAlign::Zero)]
+    align: TimeAlign,
+
+    /// Run this string as a shell command with `$SHELL -c`, or `/bin/sh -c` if `$SHELL` is not set.
+    #[arg(short = 'c', long, conflicts_with_all = ["command", "load", "compare", "load_jsonl"])]
+    shell: Option<OsString>,
+
+    #[arg(
+        trailing_var_arg = true,
+        required_unless_present_any = ["load", "compare", "load_jsonl", "shell"],
+        num_args = 1..
+    )]
     command: Vec<OsString>,
 }
 
 fn main() -> ExitCode {
     // parse args
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(shell) = args.shell.take() {
+        args.command = shell_command(std::env::var_os("SHELL"), shell);
+    }
+    let redact = match redact_options(&args) {
+        Ok(redact) => redact,
+        Err(e) => {
+            eprintln!("Invalid redact rule: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let sinks = Sinks::new(&args);
+
+    if !args.load.is_empty() || !args.compare.is_empty() || args.load_jsonl.is_some() {
+        return main_load(&args, &sinks, redact);
+    }
     assert!(!args.command.is_empty());
+    if let Some(cwd) = &args.cwd
+        && !cwd.is_dir()
+    {
+        eprintln!("Working directory {:?} is not a directory", cwd);
+        return ExitCode::FAILURE;
+    }
 
     let args_poll_period = Duration::from_secs_f32(1.0 / args.poll_freq);
+    let args_poll_period = match args.poll_freq_min {
+        None => PollPeriod::Fixed(args_poll_period),
+        Some(freq_min) => PollPeriod::Adaptive {
+            min: args_poll_period,
+            max: Duration::from_secs_f32(1.0 / freq_min),
+        },
+    };
     let args_layout_period = Duration::from_secs_f32(1.0 / args.layout_freq);
+    let args_heartbeat_period = Duration::from_secs_f32(1.0 / args.heartbeat_freq);
 
     // create shared state and channels
     let stopped = Arc::new(AtomicBool::new(false));
-    let (event_tx, event_rx) = crossbeam::channel::unbounded::<TraceEvent>();
     let (gui_handle_tx, gui_handle_rx) = crossbeam::channel::bounded::<GuiHandle>(1);
 
-    // spawn tracing thread
-    let handle_tracer = {
+    // stop tracing on ctrl-c but keep the gui open
+    install_sigint_handler(stopped.clone());
+
+    // choose the tracing backend
+    let backend_poll = Backend::Poll {
+        period: args_poll_period,
+        fields: PollFields {
+            exec: args.poll_fields.contains(&PollField::Exec),
+            exec_always: args.poll_exec_always,
+            cpu_time: args.poll_fields.contains(&PollField::Cpu),
+            fds: args.track_fds || args.poll_fields.contains(&PollField::Fds),
+            group: args.poll_fields.contains(&PollField::Group),
+            namespace: args.poll_fields.contains(&PollField::Namespace),
+            memory: args.poll_fields.contains(&PollField::Rss),
+            credentials: args.poll_fields.contains(&PollField::Credentials),
+        },
+    };
+    // (ptrace is only supported on linux)
+    #[cfg(target_os = "linux")]
+    let backend = select_backend(&args, backend_poll, check_ptrace_support);
+    #[cfg(not(target_os = "linux"))]
+    let backend = backend_poll;
+
+    let eviction = match create_eviction(&args) {
+        Ok(eviction) => eviction,
+        Err(code) => return code,
+    };
+
+    if !sinks.gui {
+        return main_headless(&args, &sinks, redact, backend, eviction, stopped, &mut io::stdout());
+    }
+
+    // start the command before anything else, so we can exit early if that fails
+    let runner = Runner {
+        backend,
+        command: args.command.clone(),
+        invocation: std::env::args_os().collect(),
+        limits: recording_limits(&args),
+        event_log: keep_event_log(&args),
+        options: command_options(&args),
+        heartbeat_period: args_heartbeat_period,
+    };
+    let run = match runner.start(&stopped) {
+        Ok(run) => run,
+        Err(e) => {
+            eprintln!("Failed to trace {:?}: {}", args.command[0], e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let served = match start_server(&args) {
+        Ok(served) => served,
+        Err(code) => return code,
+    };
+
+    // spawn collector thread, it joins the tracer so it can show errors in the gui
+    let handle_collector = {
         let stopped = stopped.clone();
-        let callback = move |event| {
-            if stopped.load(Ordering::Relaxed) {
-                return ControlFlow::Break(());
+        std::thread::spawn(move || {
+            thread_collector(
+                stopped,
+                runner,
+                run,
+                gui_handle_rx,
+                args_layout_period,
+                eviction,
+                served,
+            )
+        })
+    };
+
+    // start gui (egui wants this to be on the main thread)
+    let self_pid = args.show_self.then(nix::unistd::getpid);
+    let notes = main_gui(
+        gui_handle_tx,
+        initial_layout_settings(&args),
+        HashMap::new(),
+        true,
+        self_pid,
+    )
+    .expect("GUI failed");
+    stopped.store(true, Ordering::Relaxed);
+
+    let (mut recording, trace_result) = match handle_collector.join() {
+        Ok(collected) => collected,
+        Err(_) => return ExitCode::FAILURE,
+    };
+
+    set_notes(&mut recording, notes);
+    if sinks.summary {
+        print!("{}", summary_text(&recording, SUMMARY_TOP_EXECUTABLES));
+    }
+    let mut result = write_outputs(&args, &sinks, redact, &recording);
+    match trace_result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            eprintln!("Failed to trace {:?}: {}", args.command[0], e);
+            result = ExitCode::FAILURE;
+        }
+        Err(_) => result = ExitCode::FAILURE,
+    }
+    result
+}
+
+/// The command that runs `string` with the given shell, falling back to `/bin/sh`.
+fn shell_command(shell: Option<OsString>, string: OsString) -> Vec<OsString> {
+    let shell = shell
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "/bin/sh".into());
+    vec![shell, "-c".into(), string]
+}
+
+static SIGINT_STOPPED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+// the process group of the running command, or 0 if there is none
+static SIGINT_PGID: AtomicI32 = AtomicI32::new(0);
+
+/// Install a SIGINT handler that sets `stopped` and kills the process group of the running command the first time,
+/// and force-exits the second time. The command runs in its own group, so it doesn't get the SIGINT itself.
+fn install_sigint_handler(stopped: Arc<AtomicBool>) {
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+    extern "C" fn handle_sigint(_: nix::libc::c_int) {
+        // only async-signal-safe operations are allowed here
+        if SIGINT_RECEIVED.swap(true, Ordering::Relaxed) {
+            unsafe { nix::libc::_exit(130) };
+        }
+        if let Some(stopped) = SIGINT_STOPPED.get() {
+            stopped.store(true, Ordering::Relaxed);
+        }
+        // the tracer might be blocked waiting for the command, killing it wakes it up
+        let pgid = SIGINT_PGID.load(Ordering::Relaxed);
+        if pgid > 0 {
+            unsafe { nix::libc::kill(-pgid, nix::libc::SIGKILL) };
+        }
+    }
+
+    if SIGINT_STOPPED.set(stopped).is_err() {
+        return;
+    }
+    let action = SigAction::new(SigHandler::Handler(handle_sigint), SaFlags::SA_RESTART, SigSet::empty());
+    if let Err(e) = unsafe { sigaction(Signal::SIGINT, &action) } {
+        eprintln!("Failed to install SIGINT handler: {}", e);
+    }
+}
+
+/// Set the process group that the SIGINT handler kills, `None` once the command is done.
+/// Setting a new group also re-arms the handler, so the next SIGINT stops the new command instead of force-exiting.
+fn set_sigint_pgid(pgid: Option<Pid>) {
+    SIGINT_PGID.store(pgid.map_or(0, Pid::as_raw), Ordering::Relaxed);
+    if pgid.is_some() {
+        SIGINT_RECEIVED.store(false, Ordering::Relaxed);
+    }
+}
+
+fn create_eviction(args: &Args) -> Result<Option<Eviction>, ExitCode> {
+    let Some(max_processes) = args.max_processes else {
+        return Ok(None);
+    };
+    let evicted_out = match &args.evicted_out {
+        None => None,
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Failed to create {:?}: {}", path, e);
+                return Err(ExitCode::FAILURE);
             }
+        },
+    };
+    Ok(Some(Eviction {
+        max_processes,
+        evicted_out,
+    }))
+}
 
-            if let TraceEvent::None = event {
-                ControlFlow::Continue(())
-            } else {
-                match event_tx.send(event) {
-                    Ok(()) => ControlFlow::Continue(()),
-                    Err(SendError(_)) => ControlFlow::Break(()),
+fn initial_layout_settings(args: &Args) -> LayoutSettings {
+    LayoutSettings {
+        max_depth: args.max_depth,
+        ..LayoutSettings::default()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn select_backend(args: &Args, backend_poll: Backend, ptrace_support: impl FnOnce() -> nix::Result<()>) -> Backend {
+    let use_ptrace = args.ptrace
+        || (args.auto
+            && match ptrace_support() {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("ptrace is not supported ({}), falling back to polling", e);
+                    false
                 }
+            });
+
+    match use_ptrace {
+        true => Backend::Ptrace {
+            exec_failures: args.record_exec_failures,
+        },
+        false => backend_poll,
+    }
+}
+
+/// Trace the command without a GUI, and print a summary once it's done if requested.
+/// Without a summary, the exit code of the command is passed through.
+fn main_headless(
+    args: &Args,
+    sinks: &Sinks,
+    redact: Option<RedactOptions>,
+    backend: Backend,
+    mut eviction: Option<Eviction>,
+    stopped: Arc<AtomicBool>,
+    summary_out: &mut dyn Write,
+) -> ExitCode {
+    let started = match backend.start(&args.command[0], &args.command, &command_options(args)) {
+        Ok(started) => started,
+        Err(e) => {
+            eprintln!("Failed to trace {:?}: {}", args.command[0], e);
+            return ExitCode::FAILURE;
+        }
+    };
+    // (the server spawns threads, so it's started after the command)
+    let recording = match start_server(args) {
+        Ok(served) => served.unwrap_or_else(|| Arc::new(Mutex::new(Recording::new()))),
+        Err(code) => return code,
+    };
+    {
+        let mut recording = recording.lock().unwrap();
+        recording.invocation = std::env::args_os().collect();
+        recording.limits = recording_limits(args);
+        recording.event_log = keep_event_log(args).then(EventLog::default);
+    }
+
+    set_sigint_pgid(started.pgid());
+    let record_result = record_started_with(started, stopped, &recording, |recording| {
+        if let Some(eviction) = &mut eviction {
+            eviction.evict(recording);
+        }
+    });
+    set_sigint_pgid(None);
+    match record_result {
+        Ok(()) => {
+            let recording = recording.lock().unwrap();
+            if sinks.summary {
+                let _ = write!(summary_out, "{}", summary_text(&recording, SUMMARY_TOP_EXECUTABLES));
+            }
+            let result = write_outputs(args, sinks, redact, &recording);
+            if !sinks.summary && result == ExitCode::SUCCESS {
+                root_exit_code(&recording)
+            } else {
+                result
             }
+        }
+        Err(e) => {
+            eprintln!("Failed to trace {:?}: {}", args.command[0], e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The exit code of the root process, `128 + signal` if it was killed by a signal like shells report it,
+/// or failure if it's unknown.
+fn root_exit_code(recording: &Recording) -> ExitCode {
+    let Some(info) = recording.root_pid.and_then(|pid| recording.processes().get(&pid)) else {
+        return ExitCode::FAILURE;
+    };
+    match (info.exit_code, info.exit_signal) {
+        // exit codes are truncated to a byte by the os anyway
+        (Some(code), _) => ExitCode::from(code as u8),
+        (None, Some(signal)) => ExitCode::from((128 + signal.signal) as u8),
+        (None, None) => ExitCode::FAILURE,
+    }
+}
+
+/// Start serving the live recording over HTTP if requested, returns the shared recording to keep up to date.
+#[cfg(feature = "tiny_http")]
+fn start_server(args: &Args) -> Result<Option<Arc<Mutex<Recording>>>, ExitCode> {
+    let Some(addr) = &args.serve else {
+        return Ok(None);
+    };
+    let recording = Arc::new(Mutex::new(Recording::new()));
+    match wtf::serve::spawn_server(addr, recording.clone()) {
+        Ok(_) => Ok(Some(recording)),
+        Err(e) => {
+            eprintln!("Failed to serve on {:?}: {}", addr, e);
+            Err(ExitCode::FAILURE)
+        }
+    }
+}
+
+#[cfg(not(feature = "tiny_http"))]
+fn start_server(_: &Args) -> Result<Option<Arc<Mutex<Recording>>>, ExitCode> {
+    Ok(None)
+}
+
+// a path of "-" reads from the given stdin instead
+fn load_recording(path: &Path, jsonl: bool, stdin: &mut dyn BufRead) -> Result<Recording, serde_json::Error> {
+    let load = |reader: &mut dyn BufRead| match jsonl {
+        true => Recording::from_jsonl(reader),
+        false => Recording::from_json(reader),
+    };
+    if path.as_os_str() == "-" {
+        load(stdin)
+    } else {
+        File::open(path)
+            .map_err(serde_json::Error::io)
+            .and_then(|file| load(&mut BufReader::new(file)))
+    }
+}
+
+fn main_load(args: &Args, sinks: &Sinks, redact: Option<RedactOptions>) -> ExitCode {
+    // load and merge recordings
+    let paths = if let Some(path) = &args.load_jsonl {
+        std::slice::from_ref(path)
+    } else if args.compare.is_empty() {
+        &args.load
+    } else {
+        &args.compare
+    };
+    let mut recordings = vec![];
+    for path in paths {
+        match load_recording(path, args.load_jsonl.is_some(), &mut io::stdin().lock()) {
+            Ok(recording) => recordings.push(recording),
+            Err(e) => {
+                eprintln!("Failed to load recording from {:?}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let diff = match recordings.as_slice() {
+        [before, after] if !args.compare.is_empty() => Some(trace_diff(before, after, LabelMode::Basename)),
+        _ => None,
+    };
+    let recording = Recording::merge_all(recordings, args.align);
+
+    if sinks.summary {
+        print!("{}", summary_text(&recording, SUMMARY_TOP_EXECUTABLES));
+        if let Some(diff) = &diff {
+            println!("changes:");
+            for line in trace_diff_text(diff, SUMMARY_TOP_EXECUTABLES).lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    if !sinks.gui {
+        return write_outputs(args, sinks, redact, &recording);
+    }
+
+    // send the data to the gui once it's ready
+    let (gui_handle_tx, gui_handle_rx) = crossbeam::channel::bounded::<GuiHandle>(1);
+    let notes = recording.notes().clone().into_iter().collect();
+    let mut recording_outputs = recording.clone();
+    let handle_sender = std::thread::spawn(move || {
+        let gui_handle = match gui_handle_rx.recv() {
+            Ok(handle) => handle,
+            Err(RecvError) => return,
         };
 
-        if args.ptrace {
-            // TODO does fork/exec work fine with the extra spawned thread?  if not, split this up into start/run
-            let command = args
-                .command
-                .iter()
-                .map(|s| CString::new(s.as_bytes()).expect("Failed to convert command to CString"))
-                .collect_vec();
-
-            std::thread::spawn(move || {
-                let trace_result = unsafe { record_trace(&command[0], &command, callback) };
-                if let Err(e) = &trace_result {
-                    eprintln!("Failed to spawn child process: {}", e.0);
-                }
-            })
-        } else {
-            std::thread::spawn(move || {
-                let poll_result = record_poll(&args.command[0], &args.command, args_poll_period, callback);
-                if let Err(e) = &poll_result {
-                    eprintln!("Failed to spawn child process: {}", e);
-                }
-            })
+        let layout_settings = *gui_handle.layout_settings.lock().unwrap();
+        let mut data = DataToGui::new(recording, layout_settings);
+        data.diff = diff;
+        *gui_handle.data_to_gui.lock().unwrap() = Some(data);
+        gui_handle.ctx.request_repaint();
+    });
+
+    // write the outputs once the gui is closed, so they include the notes added in the gui
+    let notes = main_gui(gui_handle_tx, initial_layout_settings(args), notes, false, None).expect("GUI failed");
+    let _ = handle_sender.join();
+
+    set_notes(&mut recording_outputs, notes);
+    write_outputs(args, sinks, redact, &recording_outputs)
+}
+
+/// Replace the notes of the recording with the ones from the GUI, ordered by pid.
+fn set_notes(recording: &mut Recording, notes: HashMap<Pid, String>) {
+    *recording.notes_mut() = notes.into_iter().sorted_by_key(|&(pid, _)| pid).collect();
+}
+
+/// Write the recording to all output files requested in the args.
+fn write_outputs(args: &Args, sinks: &Sinks, redact: Option<RedactOptions>, recording: &Recording) -> ExitCode {
+    let mut result = ExitCode::SUCCESS;
+    let event_log = recording.event_log.clone();
+
+    let subtree = match args.root_pid.map(Pid::from_raw) {
+        Some(root) if !recording.processes().contains_key(&root) => {
+            eprintln!("Process {} is not part of the recording, nothing was written", root);
+            return ExitCode::FAILURE;
         }
+        Some(root) => Some(recording.subtree(root)),
+        None => None,
     };
+    let recording = subtree.as_ref().unwrap_or(recording);
 
-    // spawn collector thread
-    let handle_collector = {
-        let stopped = stopped.clone();
-        std::thread::spawn(move || thread_collector(stopped, event_rx, gui_handle_rx, args_layout_period))
+    let redacted = redact.map(|opts| recording.redact(opts));
+    let recording = redacted.as_ref().unwrap_or(recording);
+
+    let mut write_output = |what: &str, path: &Option<PathBuf>, f: &dyn Fn(&mut BufWriter<File>) -> io::Result<()>| {
+        if let Some(path) = path {
+            let write_result = File::create(path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                f(&mut writer)?;
+                writer.flush()
+            });
+            if let Err(e) = write_result {
+                eprintln!("Failed to write {} to {:?}: {}", what, path, e);
+                result = ExitCode::FAILURE;
+            }
+        }
     };
 
-    // start gui (egui wants this to be on the main thread)
-    main_gui(gui_handle_tx).expect("GUI failed");
-    stopped.store(true, Ordering::Relaxed);
+    write_output("recording", &sinks.save, &|w| Ok(recording.to_json(w)?));
+    let events = event_log.as_ref().map(|log| log.lock().unwrap());
+    let events = events.as_ref().map_or(&[][..], |events| events.as_slice());
+    write_output("events", &args.events_json, &|w| write_events_json(events, w));
+    write_output("events", &args.events_jsonl, &|w| write_events_jsonl(events, w));
+    write_output("csv", &args.export_csv, &|w| write_csv(recording, w));
+    write_output("otlp", &args.export_otlp, &|w| write_otlp_json(recording, w));
+    let critical_path_json = args
+        .export_critical_path
+        .as_ref()
+        .is_some_and(|path| path.extension().is_some_and(|ext| ext == "json"));
+    write_output("critical path", &args.export_critical_path, &|w| {
+        write_critical_path(recording, w, critical_path_json)
+    });
+    #[cfg(feature = "prost")]
+    write_output("perfetto", &args.export_perfetto, &|w| {
+        wtf::export::perfetto::write_perfetto(recording, w)
+    });
 
-    let _ = handle_tracer.join();
-    let _ = handle_collector.join();
+    #[cfg(feature = "rusqlite")]
+    if let Some(path) = &args.export_sqlite
+        && let Err(e) = write_sqlite_file(path, recording)
+    {
+        eprintln!("Failed to write sqlite to {:?}: {}", path, e);
+        result = ExitCode::FAILURE;
+    }
 
-    ExitCode::SUCCESS
+    #[cfg(feature = "ureq")]
+    if let Some(endpoint) = &args.otlp_endpoint
+        && let Err(e) = wtf::export::otlp::send_otlp_http(recording, endpoint)
+    {
+        eprintln!("Failed to send otlp spans to {:?}: {}", endpoint, e);
+        result = ExitCode::FAILURE;
+    }
+
+    result
+}
+
+fn command_options(args: &Args) -> CommandOptions {
+    CommandOptions {
+        cwd: args.cwd.clone(),
+        env: args.env.clone(),
+        capture_output: args.capture_output,
+    }
+}
+
+fn recording_limits(args: &Args) -> RecordingLimits {
+    RecordingLimits {
+        max_processes: args.process_limit,
+        max_depth: args.depth_limit,
+    }
+}
+
+/// A single action of `--on-complete`.
+#[derive(Debug, Clone)]
+enum CompleteAction {
+    Gui,
+    Summary,
+    Save(PathBuf),
 }
 
+fn parse_complete_action(s: &str) -> Result<CompleteAction, String> {
+    match s {
+        "gui" => Ok(CompleteAction::Gui),
+        "summary" => Ok(CompleteAction::Summary),
+        _ => match s.strip_prefix("save:") {
+            Some(path) if !path.is_empty() => Ok(CompleteAction::Save(path.into())),
+            _ => Err(format!("expected gui, summary or save:<path>, got {:?}", s)),
+        },
+    }
+}
+
+/// Where the final recording goes, from either `--on-complete` or the individual `--save`, `--summary` and `--no-gui`.
+struct Sinks {
+    gui: bool,
+    summary: bool,
+    save: Option<PathBuf>,
+}
+
+impl Sinks {
+    fn new(args: &Args) -> Self {
+        if args.on_complete.is_empty() {
+            return Sinks {
+                gui: !args.summary && !args.no_gui,
+                summary: args.summary,
+                save: args.save.clone(),
+            };
+        }
+
+        let mut sinks = Sinks {
+            gui: false,
+            summary: false,
+            save: None,
+        };
+        for action in &args.on_complete {
+            match action {
+                CompleteAction::Gui => sinks.gui = true,
+                CompleteAction::Summary => sinks.summary = true,
+                CompleteAction::Save(path) => sinks.save = Some(path.clone()),
+            }
+        }
+        sinks
+    }
+}
+
+fn keep_event_log(args: &Args) -> bool {
+    args.events_json.is_some() || args.events_jsonl.is_some()
+}
+
+fn parse_env(s: &str) -> Result<(OsString, OsString), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", s)),
+    }
+}
+
+/// The redaction options requested in the args, or `None` if redaction is disabled.
+fn redact_options(args: &Args) -> Result<Option<RedactOptions>, regex::Error> {
+    if !args.redact {
+        return Ok(None);
+    }
+
+    let mut opts = RedactOptions::current_home();
+    opts.args = args.redact_args;
+    for rule in args.redact_rule.chunks_exact(2) {
+        opts.rules.push((Regex::new(&rule[0])?, rule[1].clone()));
+    }
+    Ok(Some(opts))
+}
+
+/// Limit on the number of processes kept in memory by the collector.
+struct Eviction {
+    max_processes: usize,
+    evicted_out: Option<BufWriter<File>>,
+}
+
+impl Eviction {
+    fn evict(&mut self, recording: &mut Recording) {
+        let evicted = recording.evict_finished(self.max_processes);
+        if evicted.is_empty() {
+            return;
+        }
+
+        if let Some(writer) = &mut self.evicted_out {
+            let result = evicted.iter().try_for_each(|info| {
+                serde_json::to_writer(&mut *writer, info)?;
+                writeln!(writer)
+            });
+            if let Err(e) = result.and_then(|()| writer.flush()) {
+                eprintln!("Failed to write evicted processes: {}", e);
+                self.evicted_out = None;
+            }
+        }
+    }
+}
+
+type TraceResult = std::thread::Result<Result<(), TraceError>>;
+
+/// Starts the traced command, again every time the gui asks for a re-run.
+struct Runner {
+    backend: Backend,
+    command: Vec<OsString>,
+    invocation: Vec<OsString>,
+    limits: RecordingLimits,
+    // whether to keep an event log for each run, see [Recording::event_log]
+    event_log: bool,
+    options: CommandOptions,
+    heartbeat_period: Duration,
+}
+
+/// A single run of the traced command.
+struct Run {
+    event_rx: Receiver<TraceEvent>,
+    tracer: JoinHandle<Result<(), TraceError>>,
+    // the process group the command was spawned in, with the root as leader
+    pgid: Option<Pid>,
+}
+
+/// Why collecting the events of a run stopped.
+enum RunEnd {
+    /// The tracer finished, either because the command exited or because tracing was stopped.
+    Finished(TraceResult),
+    /// The gui asked to re-run the command, the current run is still going.
+    Rerun(Run),
+    /// The gui was closed, the current run is still going.
+    Closed(Run),
+}
+
+impl Runner {
+    fn start(&self, stopped: &Arc<AtomicBool>) -> Result<Run, TraceError> {
+        let started = self.backend.start(&self.command[0], &self.command, &self.options)?;
+        let pgid = started.pgid();
+        set_sigint_pgid(pgid);
+        let (event_tx, event_rx) = crossbeam::channel::unbounded::<TraceEvent>();
+        let tracer = spawn_backend(started, stopped.clone(), event_tx, Some(self.heartbeat_period));
+        Ok(Run { event_rx, tracer, pgid })
+    }
+
+    /// An empty recording for a new run.
+    fn new_recording(&self) -> Recording {
+        let mut recording = Recording::new();
+        recording.invocation = self.invocation.clone();
+        recording.limits = self.limits;
+        recording.event_log = self.event_log.then(EventLog::default);
+        recording
+    }
+}
+
+impl Run {
+    /// Stop tracing early and wait for the tracer to finish.
+    fn stop(self, stopped: &AtomicBool) -> TraceResult {
+        stopped.store(true, Ordering::Relaxed);
+        drop(self.event_rx);
+        // ptrace only checks for stops after the next event, kill the command to make sure there is one
+        if let Some(pgid) = self.pgid {
+            let _ = nix::sys::signal::killpg(pgid, nix::sys::signal::Signal::SIGKILL);
+        }
+        set_sigint_pgid(None);
+        self.tracer.join()
+    }
+}
+
+/// Collect events into a recording and send it to the gui, until the gui is closed.
+/// When the gui asks for a re-run, the current run is stopped and the command starts again with an empty recording.
+/// Returns the recording and the tracer result of the last run.
 fn thread_collector(
     stopped: Arc<AtomicBool>,
-    event_rx: Receiver<TraceEvent>,
+    runner: Runner,
+    mut run: Run,
     gui_handle_rx: Receiver<GuiHandle>,
     period: Duration,
-) {
-    let gui_handle = match gui_handle_rx.recv() {
-        Ok(handle) => handle,
-        Err(RecvError) => return,
+    mut eviction: Option<Eviction>,
+    served: Option<Arc<Mutex<Recording>>>,
+) -> (Recording, TraceResult) {
+    let mut recording = runner.new_recording();
+
+    // keep recording while the gui starts, short commands might already be done by then
+    let mut tracer_done = false;
+    let gui_handle = loop {
+        if tracer_done {
+            match gui_handle_rx.recv() {
+                Ok(handle) => break handle,
+                Err(RecvError) => return (recording, run.tracer.join()),
+            }
+        }
+        crossbeam::channel::select! {
+            recv(gui_handle_rx) -> handle => match handle {
+                Ok(handle) => break handle,
+                Err(RecvError) => return (recording, run.tracer.join()),
+            },
+            recv(run.event_rx) -> event => match event {
+                Ok(event) => recording.report(event),
+                Err(RecvError) => tracer_done = true,
+            },
+        }
     };
     drop(gui_handle_rx);
 
-    let mut recording = Recording::new();
+    // show everything recorded so far immediately, without waiting for the next event
+    if recording.root_pid.is_some() {
+        let layout_settings = *gui_handle.layout_settings.lock().unwrap();
+        *gui_handle.data_to_gui.lock().unwrap() = Some(DataToGui::new(recording.clone(), layout_settings));
+        gui_handle.ctx.request_repaint();
+    }
+
+    loop {
+        match collect_run(run, &gui_handle, period, &mut recording, &mut eviction, &served) {
+            RunEnd::Finished(trace_result) => {
+                set_sigint_pgid(None);
+                // keep showing the finished run until the gui asks for a re-run or closes
+                if let Err(RecvError) = gui_handle.rerun_rx.recv() {
+                    return (recording, trace_result);
+                }
+            }
+            RunEnd::Rerun(run) => {
+                let _ = run.stop(&stopped);
+            }
+            RunEnd::Closed(run) => {
+                let trace_result = run.stop(&stopped);
+                return (recording, trace_result);
+            }
+        }
+
+        // start again from scratch, this also clears a previous ctrl-c
+        run = loop {
+            recording = runner.new_recording();
+            stopped.store(false, Ordering::Relaxed);
+            match runner.start(&stopped) {
+                Ok(run) => break run,
+                Err(e) => {
+                    let layout_settings = *gui_handle.layout_settings.lock().unwrap();
+                    let mut data = DataToGui::new(recording.clone(), layout_settings);
+                    data.error = Some(e.to_string());
+                    *gui_handle.data_to_gui.lock().unwrap() = Some(data);
+                    gui_handle.ctx.request_repaint();
+
+                    if let Err(RecvError) = gui_handle.rerun_rx.recv() {
+                        return (recording, Ok(Err(e)));
+                    }
+                }
+            }
+        };
+    }
+}
 
+/// Collect the events of a single run into the recording, and send it to the gui at most once per `period`.
+fn collect_run(
+    run: Run,
+    gui_handle: &GuiHandle,
+    period: Duration,
+    recording: &mut Recording,
+    eviction: &mut Option<Eviction>,
+    served: &Option<Arc<Mutex<Recording>>>,
+) -> RunEnd {
+    let mut layout_cache = DataLayoutCache::default();
     let mut prev = Instant::now();
 
     loop {
-        if stopped.load(Ordering::Relaxed) {
-            break;
-        }
+        // wait for the next event or re-run request
+        let event = crossbeam::channel::select! {
+            recv(run.event_rx) -> event => event,
+            recv(gui_handle.rerun_rx) -> request => match request {
+                Ok(()) => return RunEnd::Rerun(run),
+                Err(RecvError) => return RunEnd::Closed(run),
+            },
+        };
 
-        // wait for next event
-        match event_rx.recv() {
-            Ok(event) => recording.report(event),
-            Err(RecvError) => break,
-        }
         // batch collect all available events
-        // (we can't exit immediately on disconnect, we want to send the last remaining data first)
-        let disconnected = loop {
-            match event_rx.try_recv() {
-                Ok(event) => recording.report(event),
-                Err(TryRecvError::Empty) => break false,
-                Err(TryRecvError::Disconnected) => break true,
+        // (we can't exit immediately on disconnect, we want to send the last remaining data first,
+        //   or at least the error if the tracer failed without sending anything)
+        let disconnected = match event {
+            Ok(event) => {
+                recording.report(event);
+                loop {
+                    match run.event_rx.try_recv() {
+                        Ok(event) => recording.report(event),
+                        Err(TryRecvError::Empty) => break false,
+                        Err(TryRecvError::Disconnected) => break true,
+                    }
+                }
             }
+            Err(RecvError) => true,
         };
+        if let Some(eviction) = eviction {
+            eviction.evict(recording);
+        }
+        if let Some(served) = served {
+            *served.lock().unwrap() = recording.clone();
+        }
 
         // compute a new mapping
         // TODO make thread inclusion configurable from the GUI
         // TODO avoid deep cloning here?
-        let placed_threads_no = place_processes(&recording, false);
-        let placed_threads_yes = place_processes(&recording, true);
+        let layout_settings = *gui_handle.layout_settings.lock().unwrap();
+        let mut data = DataToGui::new_incremental(recording.clone(), layout_settings, &mut layout_cache);
 
-        let data = DataToGui {
-            recording: recording.clone(),
-            placed_threads_no,
-            placed_threads_yes,
-        };
+        // the tracer drops the sender once it's done, so it can be joined without blocking for long
+        if disconnected {
+            let trace_result = run.tracer.join();
+            data.error = match &trace_result {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some("the tracer thread panicked".to_owned()),
+            };
+            *gui_handle.data_to_gui.lock().unwrap() = Some(data);
+            gui_handle.ctx.request_repaint();
+            return RunEnd::Finished(trace_result);
+        }
 
         *gui_handle.data_to_gui.lock().unwrap() = Some(data);
         gui_handle.ctx.request_repaint();
 
-        if disconnected {
-            break;
-        }
-
         if let Some(remaining) = period.checked_sub(prev.elapsed()) {
             std::thread::sleep(remaining);
         }
         prev = Instant::now();
     }
 }
+
+#[cfg(feature = "rusqlite")]
+fn write_sqlite_file(path: &std::path::Path, recording: &Recording) -> Result<(), Box<dyn std::error::Error>> {
+    // start from an empty database, similar to how File::create truncates
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut conn = rusqlite::Connection::open(path)?;
+    wtf::export::sqlite::write_sqlite(recording, &mut conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wtf::record::ProcessKind;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn auto_falls_back_to_polling() {
+        let args = Args::parse_from(["wtf", "--auto", "/bin/true"]);
+        let backend = select_backend(&args, poll_backend(), || Err(nix::errno::Errno::ENOSYS));
+        assert!(matches!(backend, Backend::Poll { .. }));
+
+        let recording =
+            wtf::backend::record_to_recording(&args.command[0], &args.command, backend, Default::default()).unwrap();
+        let root = &recording.processes()[&recording.root_pid.unwrap()];
+        assert_eq!(root.exit_code, Some(0));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn shell_string_runs_children() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        // end with a builtin, otherwise the shell might exec the last command instead of forking
+        let command = shell_command(Some("/bin/sh".into()), "/bin/true; /bin/true; exit 0".into());
+        let recording = wtf::backend::record_to_recording(
+            &command[0],
+            &command,
+            Backend::Ptrace { exec_failures: false },
+            Arc::new(AtomicBool::new(false)),
+        )
+        .unwrap();
+
+        let root = &recording.processes()[&recording.root_pid.unwrap()];
+        assert_eq!(root.execs.last().unwrap().path, "/bin/sh");
+        let children = root
+            .children
+            .iter()
+            .filter(|&&(kind, _)| kind == ProcessKind::Process)
+            .map(|(_, child)| &recording.processes()[child])
+            .collect::<Vec<_>>();
+        assert_eq!(children.len(), 2);
+        assert!(children
+            .iter()
+            .all(|child| child.execs.last().unwrap().path == "/bin/true"));
+    }
+
+    #[test]
+    fn eviction_bounds_process_count() {
+        use wtf::trace::fixtures;
+
+        // a long-running root with many short subtrees of two processes each
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/usr/bin/make", &["make"]));
+        for i in 0..20 {
+            let (child, grandchild, time) = (10 + 2 * i, 11 + 2 * i, i as f64);
+            events.extend(fixtures::process(Some(1), child, time, "/bin/sh", &["sh"]));
+            events.extend(fixtures::process(Some(child), grandchild, time, "/usr/bin/cc", &["cc"]));
+            events.push(fixtures::exit(grandchild, time + 0.5, Some(0)));
+            events.push(fixtures::exit(child, time + 0.5, Some(0)));
+        }
+
+        let path = std::env::temp_dir().join(format!("wtf-evicted-{}.jsonl", std::process::id()));
+        let mut eviction = Eviction {
+            max_processes: 10,
+            evicted_out: Some(BufWriter::new(File::create(&path).unwrap())),
+        };
+        let mut recording = Recording::new();
+        for event in events {
+            recording.report(event);
+            eviction.evict(&mut recording);
+            assert!(recording.processes().len() <= 10);
+        }
+        drop(eviction);
+
+        // the newest subtrees are kept, the rest is flushed to the output
+        assert!(recording.processes().contains_key(&Pid::from_raw(1)));
+        assert!(recording.processes().contains_key(&Pid::from_raw(49)));
+        assert!(!recording.processes().contains_key(&Pid::from_raw(10)));
+        let evicted = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(evicted.lines().count() + recording.processes().len(), 1 + 40);
+        assert!(evicted.lines().next().unwrap().contains("\"pid\":10"));
+    }
+
+    fn poll_backend() -> Backend {
+        Backend::Poll {
+            period: PollPeriod::Fixed(Duration::from_millis(10)),
+            fields: PollFields {
+                exec: true,
+                exec_always: false,
+                cpu_time: false,
+                fds: false,
+                group: false,
+                namespace: false,
+                memory: false,
+                credentials: false,
+            },
+        }
+    }
+
+    fn test_runner(command: &[&str]) -> Runner {
+        Runner {
+            backend: poll_backend(),
+            command: command.iter().map(OsString::from).collect(),
+            invocation: vec![],
+            limits: RecordingLimits::default(),
+            event_log: false,
+            options: CommandOptions::default(),
+            heartbeat_period: Duration::from_millis(10),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn cwd_is_recorded_for_root() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        let args = Args::parse_from(["wtf", "--cwd", "/tmp", "--env", "WTF_TEST=1", "/bin/true"]);
+        let backend = Backend::Ptrace { exec_failures: false };
+        let started = backend
+            .start(&args.command[0], &args.command, &command_options(&args))
+            .unwrap();
+        let recording = Mutex::new(Recording::new());
+        wtf::backend::record_started(started, Arc::new(AtomicBool::new(false)), &recording).unwrap();
+
+        let recording = recording.into_inner().unwrap();
+        let root = &recording.processes()[&recording.root_pid.unwrap()];
+        assert_eq!(root.execs[0].cwd.as_deref(), Some("/tmp".as_ref()));
+    }
+
+    #[test]
+    fn no_gui_exits_with_child_code() {
+        let run = |command: &str| {
+            let args = Args::parse_from(["wtf", "--no-gui", command]);
+            let stopped = Arc::new(AtomicBool::new(false));
+            main_headless(
+                &args,
+                &Sinks::new(&args),
+                None,
+                poll_backend(),
+                None,
+                stopped,
+                &mut io::sink(),
+            )
+        };
+        assert_eq!(run("false"), ExitCode::from(1));
+        assert_eq!(run("true"), ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn no_gui_evicts_processes() {
+        let path = std::env::temp_dir().join(format!("wtf-headless-evicted-{}.jsonl", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let command = "/bin/sleep 0.1; /bin/sleep 0.1; /bin/sleep 0.1";
+        let args = Args::parse_from([
+            "wtf",
+            "--no-gui",
+            "--max-processes",
+            "1",
+            "--evicted-out",
+            path_str,
+            "/bin/sh",
+            "-c",
+            command,
+        ]);
+        let sinks = Sinks::new(&args);
+        let eviction = create_eviction(&args).unwrap();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let result = main_headless(&args, &sinks, None, poll_backend(), eviction, stopped, &mut io::sink());
+        assert_eq!(result, ExitCode::SUCCESS);
+
+        // the finished sleeps were evicted while the shell was still running
+        let evicted = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(evicted.lines().count() >= 2, "{:?}", evicted);
+    }
+
+    #[test]
+    fn on_complete_saves_and_summarizes() {
+        let path = std::env::temp_dir().join(format!("wtf-on-complete-{}.json", std::process::id()));
+        let on_complete = format!("save:{},summary", path.display());
+        let args = Args::parse_from(["wtf", "--on-complete", &on_complete, "/bin/true"]);
+        let sinks = Sinks::new(&args);
+        assert!(sinks.summary && !sinks.gui);
+        assert_eq!(sinks.save.as_ref(), Some(&path));
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut summary = vec![];
+        let result = main_headless(&args, &sinks, None, poll_backend(), None, stopped, &mut summary);
+        assert_eq!(result, ExitCode::SUCCESS);
+
+        let saved = Recording::from_json(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let summary = String::from_utf8(summary).unwrap();
+        assert_eq!(summary, summary_text(&saved, SUMMARY_TOP_EXECUTABLES));
+        assert!(!summary.is_empty());
+    }
+
+    #[test]
+    fn invocation_is_recorded() {
+        let invocation = ["wtf", "--poll", "/bin/true", "--flag"].map(OsString::from).to_vec();
+        let runner = Runner {
+            invocation: invocation.clone(),
+            ..test_runner(&["/bin/true", "--flag"])
+        };
+        let stopped = Arc::new(AtomicBool::new(false));
+        let run = runner.start(&stopped).unwrap();
+        let mut recording = runner.new_recording();
+        for event in run.event_rx {
+            recording.report(event);
+        }
+        run.tracer.join().unwrap().unwrap();
+
+        assert_eq!(recording.invocation, invocation);
+        assert!(recording.root_pid.is_some());
+    }
+
+    // a collector running in the background, with the gui side of its channels
+    struct TestCollector {
+        data_to_gui: Arc<Mutex<Option<DataToGui>>>,
+        rerun_tx: crossbeam::channel::Sender<()>,
+        collector: JoinHandle<(Recording, TraceResult)>,
+    }
+
+    // start the collector for a run of the given command, only sending it the gui handle after `gui_handle_delay`
+    fn spawn_collector(command: &[&str], gui_handle_delay: Duration) -> TestCollector {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let runner = test_runner(command);
+        let run = runner.start(&stopped).unwrap();
+
+        let (rerun_tx, rerun_rx) = crossbeam::channel::unbounded();
+        let data_to_gui = Arc::new(Mutex::new(None));
+        let gui_handle = GuiHandle {
+            data_to_gui: data_to_gui.clone(),
+            layout_settings: Arc::default(),
+            rerun_rx,
+            ctx: Default::default(),
+        };
+        let (gui_handle_tx, gui_handle_rx) = crossbeam::channel::unbounded();
+        let period = Duration::from_millis(10);
+        let collector =
+            std::thread::spawn(move || thread_collector(stopped, runner, run, gui_handle_rx, period, None, None));
+
+        std::thread::sleep(gui_handle_delay);
+        gui_handle_tx.send(gui_handle).unwrap();
+        TestCollector {
+            data_to_gui,
+            rerun_tx,
+            collector,
+        }
+    }
+
+    // wait for the gui to receive a recording matching `done`
+    fn wait_for_recording(data_to_gui: &Mutex<Option<DataToGui>>, done: impl Fn(&Recording) -> bool) -> Recording {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(data) = data_to_gui.lock().unwrap().take()
+                && done(&data.recording)
+            {
+                return data.recording;
+            }
+            assert!(Instant::now() < deadline, "timeout waiting for the recording");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn root_exited(rec: &Recording) -> bool {
+        rec.root_pid
+            .is_some_and(|root| rec.processes()[&root].time.end.is_some())
+    }
+
+    #[test]
+    fn rerun_starts_a_new_recording() {
+        let TestCollector {
+            data_to_gui,
+            rerun_tx,
+            collector,
+        } = spawn_collector(&["/bin/true"], Duration::ZERO);
+        let first = wait_for_recording(&data_to_gui, root_exited);
+
+        // the re-run replaces the old recording instead of adding to it
+        rerun_tx.send(()).unwrap();
+        let second = wait_for_recording(&data_to_gui, |rec| rec.root_pid != first.root_pid && root_exited(rec));
+        assert_eq!(second.processes().len(), 1);
+
+        // closing the gui returns the latest run
+        drop(rerun_tx);
+        let (recording, trace_result) = collector.join().unwrap();
+        trace_result.unwrap().unwrap();
+        assert_eq!(recording.root_pid, second.root_pid);
+    }
+
+    #[test]
+    fn events_before_gui_handle_are_kept() {
+        // the command is long done by the time the gui starts
+        let TestCollector {
+            data_to_gui,
+            rerun_tx,
+            collector,
+        } = spawn_collector(&["/bin/sh", "-c", "/bin/true; exit 3"], Duration::from_millis(300));
+
+        let shown = wait_for_recording(&data_to_gui, root_exited);
+        let root = &shown.processes()[&shown.root_pid.unwrap()];
+        assert_eq!(root.exit_code, Some(3));
+        assert!(!root.execs.is_empty());
+
+        drop(rerun_tx);
+        let (recording, _) = collector.join().unwrap();
+        assert_eq!(recording.processes().len(), shown.processes().len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sigint_stops_tracing() {
+        // the handler is process-wide and raising SIGINT affects every test, so run this one in its own process
+        if std::env::var_os("WTF_TEST_SIGINT").is_none() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tests::sigint_stops_tracing", "--test-threads=1"])
+                .env("WTF_TEST_SIGINT", "1")
+                .status()
+                .unwrap();
+            assert!(status.success(), "{}", status);
+            return;
+        }
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        install_sigint_handler(stopped.clone());
+
+        // the second run checks that a ctrl-c during an earlier run doesn't make the next one force-exit
+        for _ in 0..2 {
+            stopped.store(false, Ordering::Relaxed);
+
+            // the background sleep is a grandchild that would outlive the shell if only the shell was killed
+            let command = shell_command(None, "sleep 100 & sleep 100; exit 0".into());
+            let started = poll_backend()
+                .start(&command[0], &command, &CommandOptions::default())
+                .unwrap();
+            set_sigint_pgid(started.pgid());
+
+            let interrupt = std::thread::spawn(|| {
+                std::thread::sleep(Duration::from_millis(300));
+                nix::sys::signal::raise(nix::sys::signal::Signal::SIGINT).unwrap();
+            });
+            let (event_tx, event_rx) = crossbeam::channel::unbounded();
+            let tracer = spawn_backend(started, stopped.clone(), event_tx, None);
+            let recording = Recording::from_events(event_rx.iter());
+            interrupt.join().unwrap();
+            set_sigint_pgid(None);
+            assert!(stopped.load(Ordering::Relaxed));
+
+            // the tracer stopped producing events and dropped its sender
+            tracer.join().unwrap().unwrap();
+            assert!(event_rx.try_recv().is_err());
+            assert!(recording.processes().len() >= 2);
+
+            // all processes are gone, they might take a moment to be reaped
+            let alive = |pid: Pid| {
+                std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                    .is_ok_and(|stat| !stat.rsplit(')').next().unwrap().trim_start().starts_with('Z'))
+            };
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while recording.processes().keys().any(|&pid| alive(pid)) {
+                assert!(Instant::now() < deadline, "traced processes leaked");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    #[test]
+    fn shell_fallback() {
+        assert_eq!(shell_command(None, "make".into()), ["/bin/sh", "-c", "make"]);
+        assert_eq!(shell_command(Some("".into()), "make".into()), ["/bin/sh", "-c", "make"]);
+        assert_eq!(
+            shell_command(Some("/bin/zsh".into()), "make".into()),
+            ["/bin/zsh", "-c", "make"]
+        );
+    }
+
+    #[test]
+    fn load_from_stdin() {
+        use wtf::trace::fixtures;
+
+        let recording = Recording::from_events(fixtures::build());
+        let mut saved = vec![];
+        recording.to_json(&mut saved).unwrap();
+
+        let loaded = load_recording(Path::new("-"), false, &mut saved.as_slice()).unwrap();
+        assert_eq!(loaded.root_pid, recording.root_pid);
+        assert_eq!(loaded.processes().len(), 5);
+    }
+}
@@ -1,15 +1,32 @@
-use crate::record::ProcessKind;
-use crate::trace::TraceEvent;
+use crate::record::{Credentials, ExitSignal, NamespaceId, ProcessKind};
+use crate::trace::{TraceError, TraceEvent};
 use nix::unistd::Pid;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
-use std::ffi::{OsStr, OsString};
+use std::ffi::OsString;
 use std::io;
 use std::ops::ControlFlow;
-use std::os::unix::ffi::OsStringExt;
-use std::os::unix::process::CommandExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::process::{Child, Command, ExitStatus};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
+
+// platform-specific process tree inspection
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as sys;
+#[cfg(target_os = "linux")]
+pub(crate) use linux::{process_credentials, process_namespace, process_rss};
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as sys;
+pub(crate) use sys::process_cpu_time;
+
+/// Check whether polling is possible on this system, before starting the command.
+pub fn check_poll_support() -> io::Result<()> {
+    sys::check_poll_support()
+}
 
 macro_rules! try_control {
     ($e:expr) => {
@@ -23,6 +40,130 @@ macro_rules! try_control {
 type ProcSet = HashSet<Pid>;
 type ProcMap = HashMap<Pid, Option<ProcessExecInfo>>;
 
+/// The children of every process found by scanning parent pids, only used on kernels without the children files.
+/// Scanning all of `/proc` is expensive, so it's done at most once per polling tick.
+#[derive(Default)]
+struct ChildrenScan(Option<HashMap<Pid, Vec<Pid>>>);
+
+/// Data that can be sampled for each process every polling tick, see [`PollFields`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum PollField {
+    Exec,
+    Cpu,
+    Fds,
+    Group,
+    Namespace,
+    Rss,
+    Credentials,
+}
+
+/// Which data to sample for each process every polling tick, the process tree itself is always sampled.
+#[derive(Debug, Copy, Clone)]
+pub struct PollFields {
+    /// Read the executable path, arguments and working directory.
+    pub exec: bool,
+    /// Re-read the exec info every tick instead of only until it differs from the info of the parent process.
+    /// Only this catches a second exec, but it's the most expensive part of polling.
+    pub exec_always: bool,
+    pub cpu_time: bool,
+    /// Count the open file descriptors.
+    pub fds: bool,
+    /// Read the process group and session.
+    pub group: bool,
+    /// Read the pid and mount namespaces, only supported on linux.
+    pub namespace: bool,
+    /// Read the resident memory, selected with `rss`.
+    pub memory: bool,
+    /// Read the user and group ids.
+    pub credentials: bool,
+}
+
+/// How long to wait between polling ticks.
+#[derive(Debug, Copy, Clone)]
+pub enum PollPeriod {
+    Fixed(Duration),
+    /// Poll as fast as `min` while processes are starting and exiting, and back off towards `max`
+    /// while the set of active processes is stable.
+    Adaptive {
+        min: Duration,
+        max: Duration,
+    },
+}
+
+/// The factor by which an adaptive period grows every tick without any change in the active processes.
+const ADAPTIVE_BACKOFF: f64 = 1.25;
+
+/// The current period of a [PollPeriod], adjusted every tick.
+#[derive(Debug, Copy, Clone)]
+struct PollRate {
+    period: PollPeriod,
+    curr: Duration,
+}
+
+impl PollRate {
+    fn new(period: PollPeriod) -> Self {
+        // builds tend to start with a burst of processes, so start fast
+        let curr = match period {
+            PollPeriod::Fixed(period) => period,
+            PollPeriod::Adaptive { min, .. } => min,
+        };
+        PollRate { period, curr }
+    }
+
+    /// Update the period after a tick in which `churn` processes started or exited, and return it.
+    fn update(&mut self, churn: usize) -> Duration {
+        if let PollPeriod::Adaptive { min, max } = self.period {
+            self.curr = if churn > 0 {
+                // jump back to the fastest rate, the next processes are probably short-lived too
+                min
+            } else {
+                self.curr.mul_f64(ADAPTIVE_BACKOFF).clamp(min, max.max(min))
+            };
+        }
+        self.curr
+    }
+}
+
+/// State kept across polling ticks.
+struct PollState {
+    fields: PollFields,
+    ever_active: ProcMap,
+    start_times: HashMap<Pid, u64>,
+    /// The last reported process group and session of each process.
+    groups: HashMap<Pid, (Pid, Pid)>,
+    /// The last reported namespaces of each process.
+    namespaces: HashMap<Pid, NamespaceId>,
+    /// The last reported credentials of each process.
+    credentials: HashMap<Pid, Credentials>,
+    /// Processes that still have the same exec info as their parent, they were seen between fork and exec.
+    before_exec: ProcSet,
+    prev_active: ProcSet,
+    curr_active: ProcSet,
+    children_scan: ChildrenScan,
+    // system accessors, replaced in tests
+    read_exec_info: fn(Pid) -> io::Result<ProcessExecInfo>,
+    read_start_time: fn(Pid) -> io::Result<u64>,
+}
+
+impl PollState {
+    fn new(fields: PollFields) -> Self {
+        PollState {
+            fields,
+            ever_active: HashMap::new(),
+            start_times: HashMap::new(),
+            groups: HashMap::new(),
+            namespaces: HashMap::new(),
+            credentials: HashMap::new(),
+            before_exec: HashSet::new(),
+            prev_active: HashSet::new(),
+            curr_active: HashSet::new(),
+            children_scan: ChildrenScan::default(),
+            read_exec_info: sys::get_process_exec_info,
+            read_start_time: sys::process_start_time,
+        }
+    }
+}
+
 struct KillOnDrop(Child);
 
 impl Drop for KillOnDrop {
@@ -32,18 +173,21 @@ impl Drop for KillOnDrop {
     }
 }
 
-pub fn record_poll<B>(
-    child_path: &OsStr,
-    child_argv: &[OsString],
-    period: Duration,
-    mut callback: impl FnMut(TraceEvent) -> ControlFlow<B>,
-) -> io::Result<ControlFlow<B, ExitStatus>> {
-    // build root command
-    let mut cmd = Command::new(child_path);
-    if let Some((child_argv_0, child_argv_rest)) = child_argv.split_first() {
-        cmd.arg0(child_argv_0);
-        cmd.args(child_argv_rest);
-    };
+/// The spawned command to poll, see [spawn_poll]. Dropping this interrupts its process group.
+pub struct PollChild {
+    handle: KillOnDrop,
+    time_start: Instant,
+    time_start_wall: SystemTime,
+}
+
+impl PollChild {
+    pub fn pid(&self) -> Pid {
+        Pid::from_raw(self.handle.0.id() as i32)
+    }
+}
+
+/// Spawn the command in its own process group, with the root as leader. Polling starts in [record_poll].
+pub fn spawn_poll(mut cmd: Command) -> Result<PollChild, TraceError> {
     unsafe {
         // set process group so we can kill all children later
         cmd.pre_exec(|| {
@@ -51,54 +195,116 @@ pub fn record_poll<B>(
         });
     }
 
-    // start root process
     let time_start = Instant::now();
-    let root_handle = cmd.spawn()?;
-    let root_pid = Pid::from_raw(root_handle.id() as i32);
-    let mut root_handle = KillOnDrop(root_handle);
+    let time_start_wall = SystemTime::now();
+    let handle = match cmd.spawn() {
+        Ok(handle) => handle,
+        Err(e) => {
+            // the error doesn't say which step failed, blame the working directory if it's not usable
+            if let Some(cwd) = cmd.get_current_dir()
+                && let Err(cwd_error) = nix::unistd::access(cwd, nix::unistd::AccessFlags::X_OK)
+            {
+                return Err(TraceError::Cwd(cwd.to_owned(), cwd_error));
+            }
+            return Err(TraceError::from_io(TraceError::Exec, e));
+        }
+    };
+    // close our copies of the output pipes, so they close once the command is done
+    drop(cmd);
+    Ok(PollChild {
+        handle: KillOnDrop(handle),
+        time_start,
+        time_start_wall,
+    })
+}
 
-    let mut ever_active: HashMap<Pid, Option<ProcessExecInfo>> = HashMap::new();
-    let mut prev_active: ProcSet = HashSet::new();
-    let mut curr_active: ProcSet = HashSet::new();
+pub fn record_poll<B>(
+    child: PollChild,
+    period: PollPeriod,
+    fields: PollFields,
+    mut callback: impl FnMut(TraceEvent) -> ControlFlow<B>,
+) -> Result<ControlFlow<B, ExitStatus>, TraceError> {
+    let root_pid = child.pid();
+    let PollChild {
+        handle: mut root_handle,
+        time_start,
+        time_start_wall,
+    } = child;
 
-    try_control!(callback(TraceEvent::TraceStart { time: time_start }));
+    let mut rate = PollRate::new(period);
+    let mut state = PollState::new(fields);
+
+    try_control!(callback(TraceEvent::TraceStart {
+        time: time_start,
+        wall_time: time_start_wall,
+    }));
 
     loop {
         let time_now = Instant::now();
-        let time_now_f = (time_now - time_start).as_secs_f32();
+        let time_now_f = (time_now - time_start).as_secs_f64();
 
         try_control!(callback(TraceEvent::None));
 
         // check if the child is done
-        if let Some(status) = root_handle.0.try_wait()? {
-            for &pid in &prev_active {
-                try_control!(callback(TraceEvent::ProcessExit { pid, time: time_now_f }));
+        if let Some(status) = root_handle
+            .0
+            .try_wait()
+            .map_err(|e| TraceError::from_io(TraceError::Wait, e))?
+        {
+            // very short commands can exit before the first poll, still report the root so its exit code is known
+            //   (it was spawned right at the start of tracing)
+            if !state.ever_active.contains_key(&root_pid) {
+                try_control!(callback(TraceEvent::ProcessStart {
+                    pid: root_pid,
+                    time: 0.0,
+                }));
+                state.prev_active.insert(root_pid);
+            }
+
+            // we only know the exit code of the root process
+            let signal = status.signal().map(|signal| ExitSignal {
+                signal,
+                core_dumped: status.core_dumped(),
+            });
+            for &pid in &state.prev_active {
+                let (exit_code, signal) = if pid == root_pid {
+                    (status.code(), signal)
+                } else {
+                    (None, None)
+                };
+                try_control!(callback(TraceEvent::ProcessExit {
+                    pid,
+                    time: time_now_f,
+                    exit_code,
+                    signal,
+                }));
             }
             try_control!(callback(TraceEvent::TraceEnd { time: time_now_f }));
             return Ok(ControlFlow::Continue(status));
         }
 
         // start polling from the root process
-        assert!(curr_active.is_empty());
-        try_control!(poll_proc_all(
-            time_now_f,
-            root_pid,
-            &mut ever_active,
-            &mut curr_active,
-            &mut callback
-        ));
+        assert!(state.curr_active.is_empty());
+        state.children_scan = ChildrenScan::default();
+        try_control!(poll_proc_all(time_now_f, root_pid, None, &mut state, &mut callback));
 
         // report dead processes
-        for &pid in &prev_active {
-            if !curr_active.contains(&pid) {
-                try_control!(callback(TraceEvent::ProcessExit { pid, time: time_now_f }));
+        for &pid in &state.prev_active {
+            if !state.curr_active.contains(&pid) {
+                try_control!(callback(TraceEvent::ProcessExit {
+                    pid,
+                    time: time_now_f,
+                    exit_code: None,
+                    signal: None,
+                }));
             }
         }
-        std::mem::swap(&mut curr_active, &mut prev_active);
-        curr_active.clear();
+        let churn = state.prev_active.symmetric_difference(&state.curr_active).count();
+        std::mem::swap(&mut state.curr_active, &mut state.prev_active);
+        state.curr_active.clear();
 
         // wait for leftover time if any
-        let time_left = period.checked_sub(time_now.elapsed());
+        let time_left = rate.update(churn).checked_sub(time_now.elapsed());
         if let Some(time_left) = time_left {
             std::thread::sleep(time_left);
         }
@@ -106,25 +312,143 @@ pub fn record_poll<B>(
 }
 
 fn poll_proc_all<B>(
-    time: f32,
+    time: f64,
     pid: Pid,
-    ever_active: &mut ProcMap,
-    curr_active: &mut ProcSet,
+    parent: Option<Pid>,
+    state: &mut PollState,
     callback: &mut impl FnMut(TraceEvent) -> ControlFlow<B>,
 ) -> ControlFlow<B> {
-    assert!(!curr_active.contains(&pid));
+    assert!(!state.curr_active.contains(&pid));
 
     // maybe report process start
-    if !ever_active.contains_key(&pid) {
+    if !state.ever_active.contains_key(&pid) {
         callback(TraceEvent::ProcessStart { pid, time })?;
     }
-    curr_active.insert(pid);
+    state.curr_active.insert(pid);
+
+    poll_exec_info(time, pid, parent, state, callback)?;
+    assert!(state.ever_active.contains_key(&pid));
+
+    // the cpu time and group come from the same stat file, only read it once
+    let stat = (state.fields.cpu_time || state.fields.group).then(|| sys::process_stat(pid));
+    if state.fields.cpu_time
+        && let Some(Ok(stat)) = &stat
+    {
+        callback(TraceEvent::ProcessCpuTime {
+            pid,
+            cpu_time: stat.cpu_time,
+        })?;
+    }
+    // permission errors are expected for processes of other users, just skip them
+    if state.fields.fds
+        && let Ok(fd_count) = sys::process_fd_count(pid)
+    {
+        callback(TraceEvent::ProcessFdCount { pid, fd_count })?;
+    }
+    if state.fields.memory
+        && let Ok(rss) = sys::process_rss(pid)
+    {
+        callback(TraceEvent::ProcessMemory { pid, time, rss })?;
+    }
+    if state.fields.group
+        && let Some(Ok(ProcessStat { pgid, sid, .. })) = stat
+        && state.groups.insert(pid, (pgid, sid)) != Some((pgid, sid))
+    {
+        callback(TraceEvent::ProcessGroup { pid, pgid, sid })?;
+    }
+    if state.fields.namespace
+        && let Ok(namespace_id) = sys::process_namespace(pid)
+        && state.namespaces.insert(pid, namespace_id) != Some(namespace_id)
+    {
+        callback(TraceEvent::ProcessNamespace { pid, namespace_id })?;
+    }
+    if state.fields.credentials
+        && let Ok(credentials) = sys::process_credentials(pid)
+        && state.credentials.insert(pid, credentials) != Some(credentials)
+    {
+        callback(TraceEvent::ProcessCredentials { pid, credentials })?;
+    }
+
+    // visit threads
+    if let Ok(tasks) = sys::process_tasks(pid) {
+        for task_pid in tasks {
+            if task_pid != pid {
+                check_pid_reuse(task_pid, state);
+
+                // report child thread
+                if let Entry::Vacant(e) = state.ever_active.entry(task_pid) {
+                    e.insert(None);
+                    state.curr_active.insert(task_pid);
+
+                    callback(TraceEvent::ProcessStart { pid: task_pid, time })?;
+                    callback(TraceEvent::ProcessChild {
+                        parent: pid,
+                        child: task_pid,
+                        kind: ProcessKind::Thread,
+                    })?;
+                }
+
+                // threads are not visited themselves, keep trying to read their info until it's known
+                if state.ever_active.get(&task_pid).is_some_and(Option::is_none) {
+                    poll_exec_info(time, task_pid, None, state, callback)?;
+                }
+            }
+
+            // visit children
+            if let Ok(children) = sys::task_children(pid, task_pid, &mut state.children_scan) {
+                for child_pid in children {
+                    // skip children we've already visited this tick, eg. due to an inconsistent read
+                    if state.curr_active.contains(&child_pid) {
+                        continue;
+                    }
+
+                    check_pid_reuse(child_pid, state);
+
+                    // report child process
+                    if !state.ever_active.contains_key(&child_pid) {
+                        callback(TraceEvent::ProcessChild {
+                            parent: task_pid,
+                            child: child_pid,
+                            kind: ProcessKind::Process,
+                        })?;
+                    }
+
+                    // recurse into child process
+                    poll_proc_all(time, child_pid, Some(pid), state, callback)?;
+                }
+            }
+        }
+    }
 
-    // maybe report process exec change, if there is new good info
-    let new_info = get_process_exec_info(pid);
-    let old_info = ever_active.get(&pid).and_then(Option::as_ref);
+    ControlFlow::Continue(())
+}
+
+/// Maybe report a process exec change, if there is new good info.
+/// By default only processes without good info yet are read, that's the expensive part of polling,
+/// so processes whose info couldn't be read before are retried every tick until it succeeds or they exit.
+/// A process first seen between fork and exec still has the info of its `parent`, it's retried until that changes.
+fn poll_exec_info<B>(
+    time: f64,
+    pid: Pid,
+    parent: Option<Pid>,
+    state: &mut PollState,
+    callback: &mut impl FnMut(TraceEvent) -> ControlFlow<B>,
+) -> ControlFlow<B> {
+    let old_info = state.ever_active.get(&pid).and_then(Option::as_ref);
+    let read_exec =
+        state.fields.exec && (state.fields.exec_always || old_info.is_none() || state.before_exec.contains(&pid));
+    let new_info = read_exec.then(|| (state.read_exec_info)(pid));
     match (old_info, new_info) {
-        (old_info, Ok(new_info)) => {
+        (old_info, Some(Ok(new_info))) => {
+            let parent_info = parent.and_then(|parent| state.ever_active.get(&parent)?.as_ref());
+            if parent_info
+                .is_some_and(|parent_info| parent_info.path == new_info.path && parent_info.argv == new_info.argv)
+            {
+                state.before_exec.insert(pid);
+            } else {
+                state.before_exec.remove(&pid);
+            }
+
             if old_info.is_none_or(|old_info| old_info.path != new_info.path || old_info.argv != new_info.argv) {
                 callback(TraceEvent::ProcessExec {
                     pid,
@@ -136,92 +460,253 @@ fn poll_proc_all<B>(
             }
 
             // replace with new info
-            ever_active.insert(pid, Some(new_info));
+            state.ever_active.insert(pid, Some(new_info));
         }
-        (None, Err(_)) => {
+        (None, _) => {
             // mark as active but without good info yet
-            ever_active.insert(pid, None);
+            state.ever_active.insert(pid, None);
         }
-        (Some(_), Err(_)) => {
+        (Some(_), _) => {
             // leave old info as is, we don't have anything better
         }
-    };
-    assert!(ever_active.contains_key(&pid));
+    }
+    ControlFlow::Continue(())
+}
 
-    // visit threads
-    if let Ok(dirs) = std::fs::read_dir(format!("/proc/{pid}/task")) {
-        for dir in dirs {
-            if let Ok(dir) = dir {
-                let task_pid = Pid::from_raw(dir.file_name().to_str().unwrap().parse::<i32>().unwrap());
-
-                if task_pid != pid {
-                    // report child thread
-                    if let Entry::Vacant(e) = ever_active.entry(task_pid) {
-                        e.insert(None);
-                        curr_active.insert(task_pid);
-
-                        callback(TraceEvent::ProcessStart { pid: task_pid, time })?;
-                        callback(TraceEvent::ProcessChild {
-                            parent: pid,
-                            child: task_pid,
-                            kind: ProcessKind::Thread,
-                        })?;
-                    }
-                }
+/// Detect whether `pid` has been reused by a new process since we last saw it, by comparing start times.
+/// If so, forget about the old process so the new process is reported as a separate process.
+/// Only pids that (re)appear this tick are checked, the old process was already reported as exited when it disappeared.
+fn check_pid_reuse(pid: Pid, state: &mut PollState) {
+    // pids don't wrap around within a single tick in practice, so pids that were active last tick are still the same process
+    if state.prev_active.contains(&pid) {
+        return;
+    }
 
-                // visit children
-                if let Ok(children) = std::fs::read_to_string(format!("/proc/{pid}/task/{task_pid}/children")) {
-                    for child in children.split(" ") {
-                        if child.is_empty() {
-                            continue;
-                        }
-                        let child_pid = Pid::from_raw(child.parse::<i32>().unwrap());
-
-                        // report child process
-                        if !ever_active.contains_key(&child_pid) {
-                            callback(TraceEvent::ProcessChild {
-                                parent: task_pid,
-                                child: child_pid,
-                                kind: ProcessKind::Process,
-                            })?;
-                        }
-
-                        // recurse into child process
-                        poll_proc_all(time, child_pid, ever_active, curr_active, callback)?;
-                    }
-                }
-            }
+    let Ok(start_time) = (state.read_start_time)(pid) else {
+        return;
+    };
+
+    match state.start_times.insert(pid, start_time) {
+        Some(prev_start_time) if prev_start_time != start_time => {
+            state.ever_active.remove(&pid);
+            state.groups.remove(&pid);
+            state.namespaces.remove(&pid);
+            state.credentials.remove(&pid);
+            state.before_exec.remove(&pid);
         }
+        _ => {}
     }
+}
 
-    ControlFlow::Continue(())
+/// The fields of the stat file that are sampled every tick.
+#[derive(Debug, Copy, Clone)]
+struct ProcessStat {
+    /// The total user and system CPU time of all threads, in seconds.
+    cpu_time: f64,
+    pgid: Pid,
+    sid: Pid,
 }
 
 #[derive(Debug)]
 struct ProcessExecInfo {
-    cwd: Option<String>,
-    path: String,
-    argv: Vec<String>,
-}
-
-fn get_process_exec_info(pid: Pid) -> io::Result<ProcessExecInfo> {
-    let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid))?
-        .into_os_string()
-        .to_string_lossy()
-        .into_owned();
-
-    let path = std::fs::read_link(format!("/proc/{}/exe", pid))?
-        .to_string_lossy()
-        .into_owned();
-
-    let argv = std::fs::read(format!("/proc/{}/cmdline", pid))?
-        .split(|&b| b == 0)
-        .map(|s| OsString::from_vec(s.to_owned()).to_string_lossy().into_owned())
-        .collect();
-
-    Ok(ProcessExecInfo {
-        cwd: Some(cwd),
-        path,
-        argv,
-    })
+    cwd: Option<OsString>,
+    path: OsString,
+    argv: Vec<OsString>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+    use std::cell::RefCell;
+    use std::path::Path;
+
+    thread_local! {
+        // the fake executable and arguments of every process, or None if they can't be read yet
+        static FAKE_EXEC: RefCell<HashMap<Pid, Option<(&'static str, &'static str)>>> = RefCell::default();
+        static FAKE_EXEC_READS: RefCell<usize> = const { RefCell::new(0) };
+        static FAKE_START_TIME: RefCell<(u64, usize)> = const { RefCell::new((0, 0)) };
+    }
+
+    fn fake_exec(pid: Pid) -> io::Result<(&'static str, &'static str)> {
+        FAKE_EXEC
+            .with_borrow(|m| m.get(&pid).copied().flatten())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn set_fake_exec(pid: Pid, exec: Option<(&'static str, &'static str)>) {
+        FAKE_EXEC.with_borrow_mut(|m| m.insert(pid, exec));
+    }
+
+    fn fake_state() -> PollState {
+        let fields = PollFields {
+            exec: true,
+            exec_always: false,
+            cpu_time: false,
+            fds: false,
+            group: false,
+            namespace: false,
+            memory: false,
+            credentials: false,
+        };
+        let mut state = PollState::new(fields);
+        state.read_start_time = |_| {
+            Ok(FAKE_START_TIME.with_borrow_mut(|(start_time, reads)| {
+                *reads += 1;
+                *start_time
+            }))
+        };
+        state.read_exec_info = |pid| {
+            FAKE_EXEC_READS.with_borrow_mut(|n| *n += 1);
+            let (path, arg) = fake_exec(pid)?;
+            Ok(ProcessExecInfo {
+                cwd: None,
+                path: path.into(),
+                argv: vec![path.into(), arg.into()],
+            })
+        };
+        state
+    }
+
+    /// Run one tick of exec polling for `pid`, returning the reported argv if any.
+    fn tick(state: &mut PollState, pid: Pid, parent: Option<Pid>) -> Option<Vec<OsString>> {
+        let mut argv = None;
+        let _: ControlFlow<()> = poll_exec_info(0.0, pid, parent, state, &mut |event| {
+            if let TraceEvent::ProcessExec { argv: a, .. } = event {
+                argv = Some(a);
+            }
+            ControlFlow::Continue(())
+        });
+        argv
+    }
+
+    #[test]
+    fn adaptive_rate_follows_churn() {
+        let (min, max) = (Duration::from_millis(10), Duration::from_secs(1));
+        let mut rate = PollRate::new(PollPeriod::Adaptive { min, max });
+
+        // backs off while stable, up to the max period
+        let periods = (0..100).map(|_| rate.update(0)).collect::<Vec<_>>();
+        assert!(periods.windows(2).all(|w| w[0] <= w[1]));
+        assert!(periods[1] > min);
+        assert_eq!(*periods.last().unwrap(), max);
+
+        // a burst of spawns goes back to the fastest rate
+        assert_eq!(rate.update(5), min);
+        assert_eq!(rate.update(3), min);
+
+        let mut rate = PollRate::new(PollPeriod::Fixed(max));
+        assert_eq!(rate.update(5), max);
+        assert_eq!(rate.update(0), max);
+    }
+
+    #[test]
+    fn pid_reuse_detected_on_reappearance() {
+        let pid = Pid::from_raw(102);
+        let mut state = fake_state();
+        let start_time_reads = || FAKE_START_TIME.with_borrow(|&(_, reads)| reads);
+
+        // first appearance records the start time
+        FAKE_START_TIME.set((10, 0));
+        check_pid_reuse(pid, &mut state);
+        state.ever_active.insert(pid, None);
+        state.prev_active.insert(pid);
+        assert_eq!(start_time_reads(), 1);
+
+        // still active, no need to check again
+        check_pid_reuse(pid, &mut state);
+        assert_eq!(start_time_reads(), 1);
+        assert!(state.ever_active.contains_key(&pid));
+
+        // the process exits and a new one with the same pid appears later
+        state.prev_active.clear();
+        FAKE_START_TIME.set((20, 1));
+        check_pid_reuse(pid, &mut state);
+        assert_eq!(start_time_reads(), 2);
+        assert!(!state.ever_active.contains_key(&pid));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn peak_fds_are_recorded() {
+        let fields = PollFields {
+            fds: true,
+            ..fake_state().fields
+        };
+        let backend = crate::backend::Backend::Poll {
+            period: PollPeriod::Fixed(Duration::from_millis(10)),
+            fields,
+        };
+        // open four extra fds, then keep them open for a while
+        let script = "exec 3</dev/null 4</dev/null 5</dev/null 6</dev/null; sleep 0.3; exit 0";
+        let argv = ["/bin/sh", "-c", script].map(OsString::from);
+        let rec = crate::backend::record_to_recording(&argv[0], &argv, backend, Default::default()).unwrap();
+
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        assert!(root.peak_fds.is_some_and(|peak| peak >= 7), "{:?}", root.peak_fds);
+    }
+
+    #[test]
+    fn exec_info_reread_after_exec() {
+        let (parent, pid) = (Pid::from_raw(99), Pid::from_raw(100));
+        let mut state = fake_state();
+        let reads = || FAKE_EXEC_READS.with_borrow(|n| *n);
+
+        set_fake_exec(parent, Some(("/bin/sh", "build.sh")));
+        assert_eq!(
+            tick(&mut state, parent, None),
+            Some(vec!["/bin/sh".into(), "build.sh".into()])
+        );
+
+        // nothing changed, so the info is not read again
+        assert_eq!(tick(&mut state, parent, None), None);
+        assert_eq!(reads(), 1);
+
+        // first seen between fork and exec, still running the parent shell, so it's read until that changes
+        set_fake_exec(pid, Some(("/bin/sh", "build.sh")));
+        assert_eq!(
+            tick(&mut state, pid, Some(parent)),
+            Some(vec!["/bin/sh".into(), "build.sh".into()])
+        );
+        assert_eq!(tick(&mut state, pid, Some(parent)), None);
+        assert_eq!(reads(), 3);
+
+        // after exec the new executable and arguments are reported, and then no longer read
+        set_fake_exec(pid, Some(("/usr/bin/make", "all")));
+        assert_eq!(
+            tick(&mut state, pid, Some(parent)),
+            Some(vec!["/usr/bin/make".into(), "all".into()])
+        );
+        assert_eq!(tick(&mut state, pid, Some(parent)), None);
+        assert_eq!(reads(), 4);
+    }
+
+    #[test]
+    fn missing_cwd_is_reported() {
+        let mut cmd = Command::new("true");
+        cmd.current_dir("/nonexistent/wtf");
+        match spawn_poll(cmd) {
+            Err(TraceError::Cwd(path, Errno::ENOENT)) => assert_eq!(path, Path::new("/nonexistent/wtf")),
+            Err(e) => panic!("unexpected error {:?}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+    #[test]
+    fn exec_info_retried_until_readable() {
+        let pid = Pid::from_raw(101);
+        let mut state = fake_state();
+
+        // the cmdline can't be read yet, the process is remembered without info
+        set_fake_exec(pid, None);
+        assert_eq!(tick(&mut state, pid, None), None);
+        assert!(matches!(state.ever_active.get(&pid), Some(None)));
+
+        // the next tick succeeds and reports the exec
+        set_fake_exec(pid, Some(("/bin/cat", "file")));
+        assert_eq!(
+            tick(&mut state, pid, None),
+            Some(vec!["/bin/cat".into(), "file".into()])
+        );
+        assert!(matches!(state.ever_active.get(&pid), Some(Some(_))));
+    }
 }
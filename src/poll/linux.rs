@@ -0,0 +1,274 @@
+use crate::poll::{ChildrenScan, ProcessExecInfo, ProcessStat};
+use crate::record::{Credentials, NamespaceId};
+use nix::libc;
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// All tasks (threads) of the given process, including the main thread which has the same pid as the process.
+/// Entries that can't be read or parsed are skipped, they might have disappeared in the meantime.
+pub fn process_tasks(pid: Pid) -> io::Result<Vec<Pid>> {
+    let tasks = std::fs::read_dir(format!("/proc/{pid}/task"))?
+        .filter_map(|dir| parse_pid(dir.ok()?.file_name().to_str()?))
+        .collect();
+    Ok(tasks)
+}
+
+/// Polling reads everything from `/proc`, which might not be mounted in minimal containers or sandboxes.
+pub fn check_poll_support() -> io::Result<()> {
+    match std::fs::read_to_string("/proc/self/stat") {
+        Ok(_) => Ok(()),
+        Err(e) => Err(io::Error::new(
+            e.kind(),
+            format!("polling requires /proc ({}), mount procfs or use --ptrace", e),
+        )),
+    }
+}
+
+/// The child processes created by the given task.
+/// Kernels without `CONFIG_PROC_CHILDREN` don't have the children files, see [children_by_ppid].
+pub fn task_children(pid: Pid, task_pid: Pid, scan: &mut ChildrenScan) -> io::Result<Vec<Pid>> {
+    static CHILDREN_SUPPORTED: OnceLock<bool> = OnceLock::new();
+    if !*CHILDREN_SUPPORTED.get_or_init(|| Path::new("/proc/thread-self/children").exists()) {
+        // all children are attributed to the main thread
+        if pid != task_pid {
+            return Ok(vec![]);
+        }
+        let children = match &mut scan.0 {
+            Some(children) => children,
+            None => scan.0.insert(children_by_ppid()?),
+        };
+        return Ok(children.get(&pid).cloned().unwrap_or_default());
+    }
+
+    let children = std::fs::read_to_string(format!("/proc/{pid}/task/{task_pid}/children"))?;
+    Ok(children.split_whitespace().filter_map(parse_pid).collect())
+}
+
+/// The child processes of every process, found by scanning the parent pid of every process.
+/// This is a lot slower than the children files.
+fn children_by_ppid() -> io::Result<HashMap<Pid, Vec<Pid>>> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for child in std::fs::read_dir("/proc")?.filter_map(|dir| parse_pid(dir.ok()?.file_name().to_str()?)) {
+        // processes that can't be read anymore have exited in the meantime
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{child}/stat")) else {
+            continue;
+        };
+        if let Ok(ppid) = stat_field(&stat, 4) {
+            children.entry(Pid::from_raw(ppid as i32)).or_default().push(child);
+        }
+    }
+    Ok(children)
+}
+
+fn parse_pid(s: &str) -> Option<Pid> {
+    s.parse::<i32>().ok().filter(|&pid| pid > 0).map(Pid::from_raw)
+}
+
+/// The start time of the given process in clock ticks since boot, used to detect pid reuse.
+pub fn process_start_time(pid: Pid) -> io::Result<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    stat_field(&stat, 22)
+}
+
+/// The total user and system CPU time used by all threads of the given process, in seconds.
+pub fn process_cpu_time(pid: Pid) -> io::Result<f64> {
+    Ok(process_stat(pid)?.cpu_time)
+}
+
+/// The CPU time, process group and session of the given process, from a single read of its stat file.
+pub fn process_stat(pid: Pid) -> io::Result<ProcessStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    let pgid = stat_field(&stat, 5)?;
+    let sid = stat_field(&stat, 6)?;
+    let utime = stat_field(&stat, 14)?;
+    let stime = stat_field(&stat, 15)?;
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ProcessStat {
+        cpu_time: (utime + stime) as f64 / ticks_per_sec as f64,
+        pgid: Pid::from_raw(pgid as i32),
+        sid: Pid::from_raw(sid as i32),
+    })
+}
+
+/// The resident memory of the given process in bytes.
+pub fn process_rss(pid: Pid) -> io::Result<u64> {
+    // the second field of statm is the resident set size in pages
+    let statm = std::fs::read_to_string(format!("/proc/{pid}/statm"))?;
+    let pages: u64 = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or(io::ErrorKind::InvalidData)?;
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if page_size <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(pages * page_size as u64)
+}
+
+/// The pid and mount namespaces of the given process.
+pub fn process_namespace(pid: Pid) -> io::Result<NamespaceId> {
+    // the ns entries are symlinks to special files, their inode number identifies the namespace
+    let inode = |name: &str| std::fs::metadata(format!("/proc/{pid}/ns/{name}")).map(|meta| meta.ino());
+    Ok(NamespaceId {
+        pid: inode("pid")?,
+        mnt: inode("mnt")?,
+    })
+}
+
+/// The real and effective user and group ids of the given process.
+pub fn process_credentials(pid: Pid) -> io::Result<Credentials> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status"))?;
+    parse_status_credentials(&status)
+}
+
+/// Parse the `Uid` and `Gid` lines of `/proc/pid/status`, which list the real, effective, saved and filesystem ids.
+fn parse_status_credentials(status: &str) -> io::Result<Credentials> {
+    let ids = |key: &str| -> io::Result<(u32, u32)> {
+        let line = status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .ok_or(io::ErrorKind::InvalidData)?;
+        let mut ids = line.split_whitespace().map(|s| s.parse::<u32>());
+        match (ids.next(), ids.next()) {
+            (Some(Ok(real)), Some(Ok(effective))) => Ok((real, effective)),
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    };
+    let (uid, euid) = ids("Uid:")?;
+    let (gid, egid) = ids("Gid:")?;
+    Ok(Credentials { uid, euid, gid, egid })
+}
+
+/// Parse the field with the given 1-based index from the contents of `/proc/pid/stat`.
+fn stat_field(stat: &str, index: usize) -> io::Result<u64> {
+    // the command name is in parentheses and can contain spaces, so only start splitting after it
+    let (_, rest) = stat.rsplit_once(')').ok_or(io::ErrorKind::InvalidData)?;
+
+    // the fields after the command name start at field 3
+    rest.split_whitespace()
+        .nth(index - 3)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| io::ErrorKind::InvalidData.into())
+}
+
+/// The number of open file descriptors of the given process.
+pub fn process_fd_count(pid: Pid) -> io::Result<usize> {
+    Ok(std::fs::read_dir(format!("/proc/{pid}/fd"))?.count())
+}
+
+pub fn get_process_exec_info(pid: Pid) -> io::Result<ProcessExecInfo> {
+    let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid))?.into_os_string();
+    let path = std::fs::read_link(format!("/proc/{}/exe", pid))?.into_os_string();
+
+    // each argument is terminated by a null byte
+    let mut cmdline = std::fs::read(format!("/proc/{}/cmdline", pid))?;
+    if cmdline.last() == Some(&0) {
+        cmdline.pop();
+    }
+    let argv = cmdline
+        .split(|&b| b == 0)
+        .map(|s| OsString::from_vec(s.to_owned()))
+        .collect();
+
+    Ok(ProcessExecInfo {
+        cwd: Some(cwd),
+        path,
+        argv,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn children_found_without_children_files() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let child_pid = Pid::from_raw(child.id() as i32);
+
+        let children = children_by_ppid();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let children = children.unwrap();
+        assert!(children[&Pid::this()].contains(&child_pid));
+        assert!(children[&Pid::parent()].contains(&Pid::this()));
+    }
+
+    #[test]
+    fn namespace_of_self() {
+        let ns = process_namespace(Pid::this()).unwrap();
+
+        // the link target is eg. `pid:[4026531836]`, with the inode in brackets
+        let link_inode = |name: &str| -> u64 {
+            let target = std::fs::read_link(format!("/proc/self/ns/{name}")).unwrap();
+            let target = target.to_str().unwrap();
+            let inode = target
+                .strip_prefix(&format!("{name}:["))
+                .unwrap()
+                .strip_suffix(']')
+                .unwrap();
+            inode.parse().unwrap()
+        };
+        assert_eq!(ns.pid, link_inode("pid"));
+        assert_eq!(ns.mnt, link_inode("mnt"));
+        assert!(process_namespace(Pid::from_raw(i32::MAX)).is_err());
+    }
+
+    #[test]
+    fn garbage_proc_entries_are_errors() {
+        // the command name can contain spaces and parentheses
+        let stat = "42 (a) b (c) S 7 42 42 0 -1 4194560";
+        assert_eq!(stat_field(stat, 4).unwrap(), 7);
+        assert!(stat_field(stat, 40).is_err());
+        assert!(stat_field("", 4).is_err());
+        assert!(stat_field("42 (sh S 7", 4).is_err());
+        assert!(stat_field("42 (sh) S x", 4).is_err());
+
+        assert_eq!(parse_pid("42"), Some(Pid::from_raw(42)));
+        for name in ["self", "", "-1", "0", "4x", "99999999999"] {
+            assert_eq!(parse_pid(name), None);
+        }
+
+        let status = "Name:\tsh\nUid:\t1000\t0\t1000\t1000\nGid:\t100\t100\t100\t100\n";
+        let credentials = parse_status_credentials(status).unwrap();
+        assert_eq!((credentials.uid, credentials.euid), (1000, 0));
+        assert_eq!((credentials.gid, credentials.egid), (100, 100));
+        assert!(parse_status_credentials("").is_err());
+        assert!(parse_status_credentials("Uid:\tx\t0\nGid:\t1\t1\n").is_err());
+        assert!(parse_status_credentials("Uid:\t1000\nGid:\t1\t1\n").is_err());
+    }
+
+    #[test]
+    fn setuid_exec_marks_process() {
+        use crate::record::Recording;
+        use crate::trace::{fixtures, TraceEvent};
+
+        // the status of a process after executing a setuid root binary like `sudo`
+        let status = "Name:\tsudo\nUid:\t1000\t0\t0\t0\nGid:\t100\t100\t100\t100\n";
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/usr/bin/sudo", &["sudo"]));
+        events.push(TraceEvent::ProcessCredentials {
+            pid: Pid::from_raw(1),
+            credentials: parse_status_credentials(status).unwrap(),
+        });
+        let rec = Recording::from_events(events);
+        assert!(rec.processes()[&Pid::from_raw(1)].credentials.unwrap().is_elevated());
+
+        let status = "Name:\tsh\nUid:\t1000\t1000\t1000\t1000\nGid:\t100\t100\t100\t100\n";
+        assert!(!parse_status_credentials(status).unwrap().is_elevated());
+    }
+}
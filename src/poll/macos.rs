@@ -0,0 +1,235 @@
+use crate::poll::{ChildrenScan, ProcessExecInfo, ProcessStat};
+use crate::record::{Credentials, NamespaceId};
+use nix::libc;
+use nix::libc::{c_int, c_void};
+use nix::unistd::Pid;
+use std::ffi::OsString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStringExt;
+use std::ptr::null_mut;
+
+/// Polling uses libproc on macOS, which is always available.
+pub fn check_poll_support() -> io::Result<()> {
+    Ok(())
+}
+
+/// Threads don't have their own pid on macOS, so the process itself is the only task.
+pub fn process_tasks(pid: Pid) -> io::Result<Vec<Pid>> {
+    Ok(vec![pid])
+}
+
+/// The child processes of the given process.
+pub fn task_children(pid: Pid, _task_pid: Pid, _scan: &mut ChildrenScan) -> io::Result<Vec<Pid>> {
+    let mut capacity = 64;
+    loop {
+        let mut buffer: Vec<libc::pid_t> = vec![0; capacity];
+        let buffer_size = (capacity * size_of::<libc::pid_t>()) as c_int;
+        let count = unsafe { libc::proc_listchildpids(pid.as_raw(), buffer.as_mut_ptr() as *mut c_void, buffer_size) };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // if the buffer was filled completely there might be more children, retry with a larger buffer
+        let count = count as usize;
+        if count < capacity {
+            buffer.truncate(count);
+            return Ok(buffer.into_iter().map(Pid::from_raw).collect());
+        }
+        capacity *= 2;
+    }
+}
+
+/// Get the info of the given `flavor` about a process, `T` must be the struct libproc fills in for that flavor.
+fn pidinfo<T>(pid: Pid, flavor: c_int) -> io::Result<T> {
+    let mut info = MaybeUninit::<T>::zeroed();
+    let info_size = size_of::<T>() as c_int;
+    let res = unsafe { libc::proc_pidinfo(pid.as_raw(), flavor, 0, info.as_mut_ptr() as *mut c_void, info_size) };
+    if res != info_size {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { info.assume_init() })
+}
+
+/// The start time of the given process in microseconds since the epoch, used to detect pid reuse.
+pub fn process_start_time(pid: Pid) -> io::Result<u64> {
+    let info = pidinfo::<libc::proc_bsdinfo>(pid, libc::PROC_PIDTBSDINFO)?;
+    Ok(info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec)
+}
+
+/// The real and effective user and group ids of the given process.
+pub fn process_credentials(pid: Pid) -> io::Result<Credentials> {
+    let info = pidinfo::<libc::proc_bsdinfo>(pid, libc::PROC_PIDTBSDINFO)?;
+    Ok(Credentials {
+        uid: info.pbi_ruid,
+        euid: info.pbi_uid,
+        gid: info.pbi_rgid,
+        egid: info.pbi_gid,
+    })
+}
+
+/// The resident memory of the given process in bytes.
+pub fn process_rss(pid: Pid) -> io::Result<u64> {
+    let info = pidinfo::<libc::proc_taskinfo>(pid, libc::PROC_PIDTASKINFO)?;
+    Ok(info.pti_resident_size)
+}
+
+/// The CPU time, process group and session of the given process.
+pub fn process_stat(pid: Pid) -> io::Result<ProcessStat> {
+    Ok(ProcessStat {
+        cpu_time: process_cpu_time(pid)?,
+        pgid: nix::unistd::getpgid(Some(pid))?,
+        sid: nix::unistd::getsid(Some(pid))?,
+    })
+}
+
+/// Namespaces only exist on linux.
+pub fn process_namespace(_: Pid) -> io::Result<NamespaceId> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+// declared here because the libc versions are deprecated in favor of the mach2 crate
+#[repr(C)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+unsafe extern "C" {
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> c_int;
+}
+
+/// The total user and system CPU time used by all threads of the given process, in seconds.
+pub fn process_cpu_time(pid: Pid) -> io::Result<f64> {
+    let info = pidinfo::<libc::proc_taskinfo>(pid, libc::PROC_PIDTASKINFO)?;
+
+    // the times are in mach absolute time units, which are not nanoseconds on all hardware
+    let mut timebase = MachTimebaseInfo { numer: 0, denom: 0 };
+    let res = unsafe { mach_timebase_info(&mut timebase) };
+    if res != 0 || timebase.denom == 0 {
+        return Err(io::Error::other("failed to get mach timebase"));
+    }
+
+    let ticks = (info.pti_total_user + info.pti_total_system) as f64;
+    Ok(ticks * timebase.numer as f64 / timebase.denom as f64 / 1e9)
+}
+
+/// The number of open file descriptors of the given process.
+pub fn process_fd_count(pid: Pid) -> io::Result<usize> {
+    // without a buffer this returns the size needed to list all fds
+    let res = unsafe { libc::proc_pidinfo(pid.as_raw(), libc::PROC_PIDLISTFDS, 0, null_mut(), 0) };
+    if res <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize / size_of::<libc::proc_fdinfo>())
+}
+
+pub fn get_process_exec_info(pid: Pid) -> io::Result<ProcessExecInfo> {
+    Ok(ProcessExecInfo {
+        cwd: get_process_cwd(pid).ok(),
+        path: get_process_path(pid)?,
+        argv: get_process_argv(pid)?,
+    })
+}
+
+fn get_process_path(pid: Pid) -> io::Result<OsString> {
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe { libc::proc_pidpath(pid.as_raw(), buffer.as_mut_ptr() as *mut c_void, buffer.len() as u32) };
+    if len <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    buffer.truncate(len as usize);
+    Ok(OsString::from_vec(buffer))
+}
+
+fn get_process_cwd(pid: Pid) -> io::Result<OsString> {
+    let info = pidinfo::<libc::proc_vnodepathinfo>(pid, libc::PROC_PIDVNODEPATHINFO)?;
+
+    // libc represents the path as a nested array, see the comment on `vnode_info_path`
+    let path = info
+        .pvi_cdir
+        .vip_path
+        .as_flattened()
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect::<Vec<u8>>();
+    Ok(OsString::from_vec(path))
+}
+
+fn get_process_argv(pid: Pid) -> io::Result<Vec<OsString>> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid.as_raw()];
+
+    // query the size first, then the actual data
+    let mut size: libc::size_t = 0;
+    let res = unsafe { libc::sysctl(mib.as_mut_ptr(), mib.len() as _, null_mut(), &mut size, null_mut(), 0) };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut buffer = vec![0u8; size];
+    let res = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as _,
+            buffer.as_mut_ptr() as *mut c_void,
+            &mut size,
+            null_mut(),
+            0,
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buffer.truncate(size);
+
+    // layout: argc, exec path, null padding, argv strings, env strings, ...
+    let Some((argc, rest)) = buffer.split_at_checked(size_of::<c_int>()) else {
+        return Err(io::ErrorKind::InvalidData.into());
+    };
+    let argc = c_int::from_ne_bytes(argc.try_into().unwrap()) as usize;
+
+    let argv: Vec<OsString> = rest
+        .split(|&b| b == 0)
+        .skip(1)
+        .skip_while(|s| s.is_empty())
+        .take(argc)
+        .map(|s| OsString::from_vec(s.to_owned()))
+        .collect();
+    if argv.len() != argc {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    Ok(argv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{record_to_recording, Backend};
+    use crate::poll::{PollFields, PollPeriod};
+    use std::ffi::{OsStr, OsString};
+    use std::time::Duration;
+
+    #[test]
+    fn record_echo() {
+        let backend = Backend::Poll {
+            period: PollPeriod::Fixed(Duration::from_millis(1)),
+            fields: PollFields {
+                exec: true,
+                exec_always: false,
+                cpu_time: true,
+                fds: false,
+                group: false,
+                namespace: false,
+                memory: false,
+                credentials: false,
+            },
+        };
+        let argv: Vec<OsString> = vec!["/bin/echo".into(), "hello".into()];
+        let rec = record_to_recording(OsStr::new("/bin/echo"), &argv, backend, Default::default()).unwrap();
+
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        assert_eq!(root.exit_code, Some(0));
+        assert_eq!(rec.processes().len(), 1);
+    }
+}
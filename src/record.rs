@@ -1,92 +1,515 @@
 use crate::trace::TraceEvent;
-use crate::util::MapExt;
+use crate::util::{MapExt, OsStringAs, PidAs};
 use indexmap::IndexMap;
+use itertools::Itertools;
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
 use nix::unistd::Pid;
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Debug, Clone)]
+/// Pid of the synthetic root process created when merging multiple recordings.
+pub const MERGED_ROOT_PID: Pid = Pid::from_raw(0);
+
+/// Version of the saved json format, bump this when the format changes in a way that field defaults don't cover,
+/// and convert older documents in [`Recording::from_json`]. Version 2 only added the version fields themselves.
+pub const FORMAT_VERSION: u64 = 2;
+
+/// The maximum amount of captured output kept in a recording, older output is dropped first.
+pub const MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recording {
+    #[serde(skip)]
     pub time_start: Option<Instant>,
-    pub time_end: Option<f32>,
+    /// The wall-clock time corresponding to time zero, used to display absolute times.
+    #[serde(default)]
+    pub time_start_wall: Option<SystemTime>,
+    pub time_end: Option<f64>,
+    /// The time of the newest event seen so far, used as the current time for recordings that didn't finish.
+    #[serde(default)]
+    pub time_latest: Option<f64>,
+    /// The full command line of the `wtf` invocation that produced this recording, including the traced command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "Vec<OsStringAs>")]
+    pub invocation: Vec<OsString>,
 
+    #[serde_as(as = "Option<PidAs>")]
     pub root_pid: Option<Pid>,
-    pub processes: IndexMap<Pid, ProcessInfo>,
+    // only mutated through methods that change the revision, see [Recording::processes_mut]
+    #[serde_as(as = "IndexMap<PidAs, _>")]
+    processes: IndexMap<Pid, ProcessInfo>,
+    // notes attached to processes from the GUI, see [Recording::notes_mut]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    #[serde_as(as = "IndexMap<PidAs, _>")]
+    notes: IndexMap<Pid, String>,
+    /// The output of the traced command, if it was captured. Capped at [MAX_OUTPUT_BYTES].
+    #[serde(default, skip_serializing_if = "VecDeque::is_empty")]
+    pub output: VecDeque<OutputChunk>,
+    /// Whether older output was dropped to stay below the cap.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub output_truncated: bool,
+    /// Limits on the processes that are recorded from now on, these are not saved.
+    #[serde(skip)]
+    pub limits: RecordingLimits,
+    /// Whether processes were left out because of the [limits](Recording::limits).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// If set, all reported events except heartbeats are also appended to this log, in order.
+    #[serde(skip)]
+    pub event_log: Option<EventLog>,
+
+    // running size of the output, can undercount for loaded recordings, see [Recording::cap_output]
+    #[serde(skip)]
+    output_len: usize,
+    // running processes that were left out because of the limits, all of their events are ignored
+    #[serde(skip)]
+    dropped: HashSet<Pid>,
+    // parents of children that were reported before they started, mapping child to parent
+    #[serde(skip)]
+    pending_parents: HashMap<Pid, Pid>,
+    // processes that were created because an event referred to them before they were reported as started
+    #[serde(skip)]
+    placeholders: HashSet<Pid>,
+    // changes every time the recording is modified, see [Recording::revision]
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+}
+
+/// The raw events reported to a recording, see [Recording::event_log].
+pub type EventLog = Arc<Mutex<Vec<TraceEvent>>>;
+
+/// Limits on the size of a recording, so fork bombs and runaway builds don't exhaust memory.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RecordingLimits {
+    /// New processes are no longer recorded once the recording contains this many.
+    pub max_processes: Option<usize>,
+    /// Processes nested deeper than this are not recorded, the root is at depth 0.
+    pub max_depth: Option<usize>,
+}
+
+// revisions are unique across all recordings and every method that modifies the processes or notes takes a new one,
+//   so equal revisions imply equal processes and notes, also for clones
+fn next_revision() -> u64 {
+    static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
 }
 
-#[derive(Debug, Clone)]
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessInfo {
+    #[serde_as(as = "PidAs")]
     pub pid: Pid,
+    #[serde(default)]
+    #[serde_as(as = "Option<PidAs>")]
+    pub parent: Option<Pid>,
 
     pub time: TimeRange,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// The signal that killed the process, if known. Polling only knows this for the root process.
+    #[serde(default)]
+    pub exit_signal: Option<ExitSignal>,
+    /// The total CPU time used by all threads of this process, in seconds, if known.
+    #[serde(default)]
+    pub cpu_time: Option<f64>,
+    /// The highest number of simultaneously open file descriptors seen, if tracked.
+    #[serde(default)]
+    pub peak_fds: Option<usize>,
+    /// The process group and session the process was last seen in, if known.
+    #[serde(default)]
+    #[serde_as(as = "Option<PidAs>")]
+    pub pgid: Option<Pid>,
+    #[serde(default)]
+    #[serde_as(as = "Option<PidAs>")]
+    pub sid: Option<Pid>,
+    /// The namespaces the process was last seen in, if known. Only available on linux.
+    #[serde(default)]
+    pub namespace_id: Option<NamespaceId>,
+    /// The user and group ids the process was last seen with, if known.
+    #[serde(default)]
+    pub credentials: Option<Credentials>,
+    /// The periods in which this process was suspended in `vfork` until its child exec-ed or exited.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vfork_blocked: Vec<TimeRange>,
+    /// The failed exec attempts of this process, in order. Only recorded when tracing with ptrace, and only if enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exec_failures: Vec<ExecFailure>,
 
     pub execs: Vec<ProcessExec>,
     // note: children might be reported here before they actually exist as ProcessInfo entries
+    #[serde_as(as = "Vec<(_, PidAs)>")]
     pub children: Vec<(ProcessKind, Pid)>,
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Identifies the pid and mount namespaces of a process by their inode numbers, as listed in `/proc/<pid>/ns`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct NamespaceId {
+    pub pid: u64,
+    pub mnt: u64,
+}
+
+/// A piece of output written by the traced command, see [Recording::output].
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputChunk {
+    pub time: f64,
+    pub stream: OutputStream,
+    #[serde_as(as = "OsStringAs")]
+    pub text: OsString,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// The signal that terminated a process.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ExitSignal {
+    /// The raw signal number, see [ExitSignal::name].
+    pub signal: i32,
+    pub core_dumped: bool,
+}
+
+impl ExitSignal {
+    /// The name of the signal, eg. `SIGKILL`, or its number if it's unknown.
+    pub fn name(&self) -> String {
+        match Signal::try_from(self.signal) {
+            Ok(signal) => signal.as_str().to_owned(),
+            Err(_) => format!("signal {}", self.signal),
+        }
+    }
+}
+
+/// A failed exec attempt, see [ProcessInfo::exec_failures].
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecFailure {
+    pub time: f64,
+    #[serde_as(as = "OsStringAs")]
+    pub path: OsString,
+    /// The raw errno, see [ExecFailure::error].
+    pub errno: i32,
+}
+
+impl ExecFailure {
+    /// The error that made the exec fail, eg. `ENOENT` if the path doesn't exist.
+    pub fn error(&self) -> Errno {
+        Errno::from_raw(self.errno)
+    }
+}
+
+/// The real and effective user and group ids of a process.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+}
+
+impl Credentials {
+    /// Whether the effective ids differ from the real ones, eg. after executing a setuid binary.
+    pub fn is_elevated(&self) -> bool {
+        self.uid != self.euid || self.gid != self.egid
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TimeRange {
-    pub start: f32,
-    pub end: Option<f32>,
+    pub start: f64,
+    pub end: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+/// The executable path, arguments and working directory, exactly as reported by the OS.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessExec {
-    pub time: f32,
-    pub cwd: Option<String>,
-    pub path: String,
-    pub argv: Vec<String>,
+    pub time: f64,
+    #[serde_as(as = "Option<OsStringAs>")]
+    pub cwd: Option<OsString>,
+    #[serde_as(as = "OsStringAs")]
+    pub path: OsString,
+    #[serde_as(as = "Vec<OsStringAs>")]
+    pub argv: Vec<OsString>,
+
+    /// The resident memory in bytes first sampled after this exec, and the highest one before the next exec.
+    #[serde(default)]
+    pub rss_start: Option<u64>,
+    #[serde(default)]
+    pub rss_peak: Option<u64>,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ProcessKind {
     Process,
     Thread,
 }
 
+/// Which part of the last exec to use as the label of a process, for display, grouping and coloring.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum LabelMode {
+    /// The basename of the executable path.
+    #[default]
+    Basename,
+    /// The full executable path, to distinguish eg. `/usr/bin/python` from a venv `python`.
+    FullPath,
+    /// The first argument, as the process was invoked.
+    Argv0,
+}
+
+/// How to align the time axes of recordings when merging them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum TimeAlign {
+    /// Shift each recording so it starts at time zero.
+    Zero,
+    /// Keep the recorded times as they are.
+    Absolute,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct ChildCounts {
     pub processes: usize,
     pub threads: usize,
 }
 
+impl ProcessInfo {
+    /// The basename of the last executable, or `"?"` if there hasn't been any exec yet.
+    pub fn name(&self) -> &OsStr {
+        self.execs.last().map_or(OsStr::new("?"), ProcessExec::name)
+    }
+
+    /// The label of the last executable, or `"?"` if there hasn't been any exec yet.
+    pub fn label(&self, mode: LabelMode) -> &OsStr {
+        self.execs.last().map_or(OsStr::new("?"), |exec| exec.label(mode))
+    }
+
+    /// The time range during which the given exec was the active program.
+    pub fn exec_time(&self, index: usize) -> TimeRange {
+        TimeRange {
+            start: self.execs[index].time,
+            end: self.execs.get(index + 1).map(|next| next.time).or(self.time.end),
+        }
+    }
+}
+
+impl ProcessExec {
+    /// The basename of the executable.
+    pub fn name(&self) -> &OsStr {
+        let bytes = self.path.as_bytes();
+        OsStr::from_bytes(bytes.rsplit(|&b| b == b'/').next().unwrap_or(bytes))
+    }
+
+    /// The label of the executable, falling back to the basename if there is no `argv[0]`.
+    pub fn label(&self, mode: LabelMode) -> &OsStr {
+        match mode {
+            LabelMode::Basename => self.name(),
+            LabelMode::FullPath => &self.path,
+            LabelMode::Argv0 => self.argv.first().map_or_else(|| self.name(), OsString::as_os_str),
+        }
+    }
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> Option<f64> {
+        self.end.map(|end| end - self.start)
+    }
+
+    /// This range as it looked at time `cursor`: `None` if it hadn't started yet, and unfinished if it ended later.
+    pub fn at_time(self, cursor: f64) -> Option<TimeRange> {
+        if self.start > cursor {
+            return None;
+        }
+        Some(TimeRange {
+            start: self.start,
+            end: self.end.filter(|&end| end <= cursor),
+        })
+    }
+
+    /// The smallest range containing both ranges, unfinished ranges stay unfinished.
+    pub fn union(self, other: TimeRange) -> TimeRange {
+        TimeRange {
+            start: self.start.min(other.start),
+            end: match (self.end, other.end) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (_, None) | (None, _) => None,
+            },
+        }
+    }
+}
+
 impl Recording {
     pub fn new() -> Self {
         Self {
             time_start: None,
+            time_start_wall: None,
             time_end: None,
+            time_latest: None,
+            invocation: vec![],
             root_pid: None,
             processes: IndexMap::new(),
+            notes: IndexMap::new(),
+            output: VecDeque::new(),
+            output_truncated: false,
+            limits: RecordingLimits::default(),
+            truncated: false,
+            event_log: None,
+            output_len: 0,
+            dropped: HashSet::new(),
+            pending_parents: HashMap::new(),
+            placeholders: HashSet::new(),
+            revision: next_revision(),
         }
     }
 
+    /// An identifier for the current state of this recording, which changes every time it's modified through its methods.
+    /// Heartbeats don't change anything, so they keep the revision.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// All recorded processes.
+    pub fn processes(&self) -> &IndexMap<Pid, ProcessInfo> {
+        &self.processes
+    }
+
+    /// Modify the processes directly, this changes the [revision](Recording::revision).
+    pub fn processes_mut(&mut self) -> &mut IndexMap<Pid, ProcessInfo> {
+        self.revision = next_revision();
+        &mut self.processes
+    }
+
+    /// Notes attached to processes from the GUI.
+    pub fn notes(&self) -> &IndexMap<Pid, String> {
+        &self.notes
+    }
+
+    /// Modify the notes, this changes the [revision](Recording::revision).
+    pub fn notes_mut(&mut self) -> &mut IndexMap<Pid, String> {
+        self.revision = next_revision();
+        &mut self.notes
+    }
+
+    /// Build a recording from a list of events, as if they were reported by a backend.
+    pub fn from_events(events: impl IntoIterator<Item = TraceEvent>) -> Self {
+        let mut recording = Recording::new();
+        for event in events {
+            recording.report(event);
+        }
+        recording
+    }
+
+    /// Build a recording from newline-delimited json events, as written by `--events-jsonl`, one event at a time.
+    pub fn from_jsonl(mut reader: impl BufRead) -> serde_json::Result<Self> {
+        let mut recording = Recording::new();
+        let mut line = vec![];
+        for line_index in 0.. {
+            line.clear();
+            if reader.read_until(b'\n', &mut line).map_err(serde_json::Error::io)? == 0 {
+                break;
+            }
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            match serde_json::from_slice::<TraceEvent>(&line) {
+                Ok(event) => recording.report(event),
+                Err(_) if !line.ends_with(b"\n") => break,
+                Err(e) => {
+                    return Err(serde::de::Error::custom(format!("line {}: {}", line_index + 1, e)));
+                }
+            }
+        }
+
+        // like recordings loaded from json, this is not a live recording
+        recording.time_start = None;
+        Ok(recording)
+    }
+
     pub fn report(&mut self, event: TraceEvent) {
+        // heartbeats only give the backend a chance to stop, they don't change the recording
+        if matches!(event, TraceEvent::None) {
+            return;
+        }
+        self.revision = next_revision();
+        if let Some(event_log) = &self.event_log {
+            event_log.lock().unwrap().push(event.clone());
+        }
+        if let Some(time) = event.time() {
+            self.time_latest = Some(self.time_latest.map_or(time, |latest| latest.max(time)));
+        }
+        if self.apply_limits(&event) {
+            return;
+        }
+
         match event {
             TraceEvent::None => {}
-            TraceEvent::TraceStart { time } => {
+            TraceEvent::TraceStart { time, wall_time } => {
                 self.time_start = Some(time);
+                self.time_start_wall = Some(wall_time);
             }
             TraceEvent::TraceEnd { time } => {
                 self.time_end = Some(time);
             }
             TraceEvent::ProcessStart { pid, time } => {
-                let info = ProcessInfo {
-                    pid,
-                    time: TimeRange { start: time, end: None },
-                    execs: Vec::new(),
-                    children: Vec::new(),
-                };
-                self.processes.insert_first(pid, info);
+                // events for this process arrived before its start, keep what they reported
+                if self.placeholders.remove(&pid) {
+                    let info = self.processes.get_mut(&pid).unwrap();
+                    // unless it already exited before this start, then the pid was reused
+                    if info.time.end.is_none_or(|end| end >= time) {
+                        info.time.start = info.execs.first().map_or(time, |exec| exec.time.min(time));
+                        return;
+                    }
+                }
 
-                if self.root_pid.is_none() {
-                    self.root_pid = Some(pid);
+                // the pid might have been reused, keep the old process around under a new pid
+                if self.processes.contains_key(&pid) {
+                    self.retire_pid(pid);
                 }
+                self.insert_process(pid, time);
             }
-            TraceEvent::ProcessExit { pid, time } => {
-                self.processes.get_mut(&pid).unwrap().time.end = Some(time);
+            TraceEvent::ProcessExit {
+                pid,
+                time,
+                exit_code,
+                signal,
+            } => {
+                // exits of processes we never saw carry no useful information
+                let Some(info) = self.processes.get_mut(&pid) else {
+                    return;
+                };
+                info.time.end = Some(time);
+                info.exit_code = exit_code;
+                info.exit_signal = signal;
             }
             TraceEvent::ProcessChild { parent, child, kind } => {
-                self.processes.get_mut(&parent).unwrap().children.push((kind, child));
+                self.process_mut(parent, None).children.push((kind, child));
+
+                // the child might not exist yet (or only as an old process with the same pid),
+                //   if so set the parent once it starts
+                // a finished child without a parent is not an old process, it just exited before it was reported,
+                //   eg. a vfork child that exits immediately
+                let root_pid = self.root_pid;
+                match self.processes.get_mut(&child) {
+                    Some(child_info)
+                        if child_info.time.end.is_none()
+                            || (child_info.parent.is_none() && Some(child) != root_pid) =>
+                    {
+                        child_info.parent = Some(parent)
+                    }
+                    _ => {
+                        self.pending_parents.insert(child, parent);
+                    }
+                }
             }
             TraceEvent::ProcessExec {
                 pid,
@@ -95,10 +518,689 @@ impl Recording {
                 cwd,
                 argv,
             } => {
-                let exec = ProcessExec { time, path, cwd, argv };
-                self.processes.get_mut(&pid).unwrap().execs.push(exec);
+                let exec = ProcessExec {
+                    time,
+                    path,
+                    cwd,
+                    argv,
+                    rss_start: None,
+                    rss_peak: None,
+                };
+                self.process_mut(pid, Some(time)).execs.push(exec);
+            }
+            TraceEvent::ExecFailed { pid, time, path, errno } => {
+                let failure = ExecFailure {
+                    time,
+                    path,
+                    errno: errno as i32,
+                };
+                self.process_mut(pid, Some(time)).exec_failures.push(failure);
+            }
+            TraceEvent::ProcessCpuTime { pid, cpu_time } => {
+                self.process_mut(pid, None).cpu_time = Some(cpu_time);
+            }
+            TraceEvent::ProcessFdCount { pid, fd_count } => {
+                let info = self.process_mut(pid, None);
+                info.peak_fds = Some(info.peak_fds.map_or(fd_count, |peak| peak.max(fd_count)));
+            }
+            TraceEvent::ProcessGroup { pid, pgid, sid } => {
+                let info = self.process_mut(pid, None);
+                info.pgid = Some(pgid);
+                info.sid = Some(sid);
+            }
+            TraceEvent::ProcessMemory { pid, time, rss } => {
+                // attribute the sample to the exec that was active at the time, earlier samples are dropped
+                let info = self.process_mut(pid, Some(time));
+                if let Some(exec) = info.execs.iter_mut().rev().find(|exec| exec.time <= time) {
+                    exec.rss_start.get_or_insert(rss);
+                    exec.rss_peak = Some(exec.rss_peak.map_or(rss, |peak| peak.max(rss)));
+                }
+            }
+            TraceEvent::ProcessNamespace { pid, namespace_id } => {
+                self.process_mut(pid, None).namespace_id = Some(namespace_id);
+            }
+            TraceEvent::ProcessCredentials { pid, credentials } => {
+                self.process_mut(pid, None).credentials = Some(credentials);
+            }
+            TraceEvent::ProcessVforkBlocked { pid, start, end } => {
+                let range = TimeRange { start, end: Some(end) };
+                self.process_mut(pid, Some(start)).vfork_blocked.push(range);
+            }
+            TraceEvent::Output { time, stream, bytes } => {
+                self.output_len += bytes.len();
+                self.output.push_back(OutputChunk {
+                    time,
+                    stream,
+                    text: OsString::from_vec(bytes),
+                });
+                self.cap_output();
+            }
+        }
+    }
+
+    pub fn to_json(&self, writer: impl Write) -> serde_json::Result<()> {
+        #[derive(Serialize)]
+        struct Versioned<'a> {
+            format_version: u64,
+            tool_version: &'a str,
+            #[serde(flatten)]
+            recording: &'a Recording,
+        }
+
+        let versioned = Versioned {
+            format_version: FORMAT_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            recording: self,
+        };
+        serde_json::to_writer(writer, &versioned)
+    }
+
+    /// Load a recording saved by this or any older version of this tool.
+    pub fn from_json(reader: impl Read) -> serde_json::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_reader(reader)?;
+        let Some(object) = value.as_object_mut() else {
+            return Err(serde::de::Error::custom("expected a json object"));
+        };
+
+        let version = match object.remove("format_version") {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid format_version {}", version)))?,
+        };
+        let tool_version = object.remove("tool_version");
+        if version > FORMAT_VERSION {
+            let saved_by = tool_version.as_ref().and_then(|v| v.as_str()).unwrap_or("unknown");
+            return Err(serde::de::Error::custom(format!(
+                "unsupported format_version {} (saved by wtf {}), this version supports up to {}",
+                version, saved_by, FORMAT_VERSION,
+            )));
+        }
+
+        // older versions only lack fields that have defaults
+        serde_json::from_value(value)
+    }
+
+    /// Shift all times such that the earliest process starts at time zero.
+    pub fn align_to_zero(&mut self) {
+        self.revision = next_revision();
+        let Some(start) = self
+            .processes
+            .values()
+            .map(|info| info.time.start)
+            .min_by(f64::total_cmp)
+        else {
+            return;
+        };
+
+        for time in self.time_end.iter_mut().chain(&mut self.time_latest) {
+            *time -= start;
+        }
+        self.time_start_wall = self.wall_time(start);
+        for info in self.processes.values_mut() {
+            info.time.start -= start;
+            for time in info.time.end.iter_mut() {
+                *time -= start;
+            }
+            for exec in &mut info.execs {
+                exec.time -= start;
+            }
+            for range in &mut info.vfork_blocked {
+                range.start -= start;
+                for time in range.end.iter_mut() {
+                    *time -= start;
+                }
+            }
+            for failure in &mut info.exec_failures {
+                failure.time -= start;
+            }
+        }
+        for chunk in &mut self.output {
+            chunk.time -= start;
+        }
+    }
+
+    // drop the oldest output until the total size is below [MAX_OUTPUT_BYTES], always keeping the newest chunk
+    fn cap_output(&mut self) {
+        if self.output_len <= MAX_OUTPUT_BYTES {
+            return;
+        }
+        // the running size is only an estimate, recount before dropping anything
+        self.output_len = self.output.iter().map(|chunk| chunk.text.len()).sum();
+        while self.output_len > MAX_OUTPUT_BYTES && self.output.len() > 1 {
+            self.output_len -= self.output.pop_front().unwrap().text.len();
+            self.output_truncated = true;
+        }
+    }
+
+    /// Import the processes of `other` into this recording, with all pids shifted by `pid_offset`.
+    pub fn merge(&mut self, other: Recording, pid_offset: i32) {
+        self.revision = next_revision();
+        let remap = |pid: Pid| Pid::from_raw(pid.as_raw() + pid_offset);
+
+        // absolute times are only meaningful if all recordings share the same wall-clock anchor
+        self.time_start_wall = if self.processes.is_empty() {
+            other.time_start_wall
+        } else if self.time_start_wall == other.time_start_wall {
+            self.time_start_wall
+        } else {
+            None
+        };
+        self.invocation = if self.processes.is_empty() || self.invocation == other.invocation {
+            other.invocation
+        } else {
+            vec![]
+        };
+
+        // create the synthetic root if it doesn't exist yet
+        if self.root_pid != Some(MERGED_ROOT_PID) {
+            let mut root = ProcessInfo {
+                pid: MERGED_ROOT_PID,
+                parent: None,
+                time: TimeRange { start: 0.0, end: None },
+                exit_code: None,
+                exit_signal: None,
+                cpu_time: None,
+                peak_fds: None,
+                pgid: None,
+                sid: None,
+                namespace_id: None,
+                credentials: None,
+                vfork_blocked: vec![],
+                exec_failures: vec![],
+                execs: vec![],
+                children: vec![],
+            };
+            if let Some(prev_root) = self.root_pid {
+                let prev_root_info = self.processes.get_mut(&prev_root).unwrap();
+                prev_root_info.parent = Some(MERGED_ROOT_PID);
+                root.time = prev_root_info.time;
+                root.children.push((ProcessKind::Process, prev_root));
+            }
+            self.processes.insert_first(MERGED_ROOT_PID, root);
+            self.root_pid = Some(MERGED_ROOT_PID);
+        }
+
+        // import processes
+        for (_, mut info) in other.processes {
+            info.pid = remap(info.pid);
+            info.parent = info.parent.map(remap);
+            info.pgid = info.pgid.map(remap);
+            info.sid = info.sid.map(remap);
+            for (_, child) in &mut info.children {
+                *child = remap(*child);
+            }
+            self.processes.insert_first(info.pid, info);
+        }
+        for (pid, note) in other.notes {
+            self.notes.insert_first(remap(pid), note);
+        }
+        // interleave the output of all recordings by time
+        self.output.extend(other.output);
+        self.output.make_contiguous().sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.output_truncated |= other.output_truncated;
+        self.truncated |= other.truncated;
+        self.output_len += other.output_len;
+
+        // attach the other root and extend the synthetic root time range
+        if let Some(other_root) = other.root_pid {
+            let other_root = remap(other_root);
+            let other_root_info = self.processes.get_mut(&other_root).unwrap();
+            other_root_info.parent = Some(MERGED_ROOT_PID);
+            let other_time = other_root_info.time;
+
+            let root = self.processes.get_mut(&MERGED_ROOT_PID).unwrap();
+            let was_empty = root.children.is_empty();
+            root.children.push((ProcessKind::Process, other_root));
+            root.time = if was_empty {
+                other_time
+            } else {
+                TimeRange {
+                    start: root.time.start.min(other_time.start),
+                    end: root.time.end.zip(other_time.end).map(|(a, b)| a.max(b)),
+                }
+            };
+        }
+        self.time_end = match (self.time_end, other.time_end) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.time_latest = match (self.time_latest, other.time_latest) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Merge multiple recordings into a single one, with each recording as a separate child of the synthetic root.
+    /// A single recording is returned unchanged.
+    pub fn merge_all(recordings: impl IntoIterator<Item = Recording>, align: TimeAlign) -> Recording {
+        let mut recordings = recordings.into_iter().collect_vec();
+        if recordings.len() == 1 {
+            return recordings.pop().unwrap();
+        }
+
+        let mut result = Recording::new();
+        for mut rec in recordings {
+            match align {
+                TimeAlign::Zero => rec.align_to_zero(),
+                TimeAlign::Absolute => {}
             }
+
+            // shift pids past all existing ones (real pids are always positive)
+            // retired pids can be negative, make sure they also end up above the existing ones
+            let result_max = result.processes.keys().map(|pid| pid.as_raw()).max().unwrap_or(0);
+            let rec_min = rec.processes.keys().map(|pid| pid.as_raw()).min().unwrap_or(1);
+            let pid_offset = result_max - (rec_min - 1).min(0);
+            result.merge(rec, pid_offset);
         }
+        result
+    }
+
+    /// Evict the oldest finished subtrees below the root until at most `max_processes` processes remain, returning them.
+    pub fn evict_finished(&mut self, max_processes: usize) -> Vec<ProcessInfo> {
+        if self.processes.len() <= max_processes {
+            return vec![];
+        }
+        let Some(root) = self.root_pid else {
+            return vec![];
+        };
+
+        let mut candidates = self.processes[&root]
+            .children
+            .iter()
+            .filter_map(|&(_, child)| Some((child, self.finished_subtree(child)?)))
+            .collect_vec();
+        candidates.sort_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b));
+
+        let mut remaining = self.processes.len();
+        let mut evicted_roots = HashSet::new();
+        let mut evicted = HashSet::new();
+        for (child, (_, pids)) in candidates {
+            if remaining <= max_processes {
+                break;
+            }
+            remaining -= pids.len();
+            evicted_roots.insert(child);
+            evicted.extend(pids);
+        }
+
+        let root_info = self.processes.get_mut(&root).unwrap();
+        root_info.children.retain(|(_, child)| !evicted_roots.contains(child));
+        self.revision = next_revision();
+
+        let (evicted, kept) = std::mem::take(&mut self.processes)
+            .into_iter()
+            .partition::<IndexMap<_, _>, _>(|(pid, _)| evicted.contains(pid));
+        self.processes = kept;
+
+        // forget everything else about the evicted pids, so a reused pid starts fresh
+        self.notes.retain(|pid, _| !evicted.contains_key(pid));
+        self.placeholders.retain(|pid| !evicted.contains_key(pid));
+        self.pending_parents
+            .retain(|child, parent| !evicted.contains_key(child) && !evicted.contains_key(parent));
+
+        evicted.into_values().collect()
+    }
+
+    /// A copy of this recording with only the subtree starting at `root`, which becomes the new root process.
+    pub fn subtree(&self, root: Pid) -> Recording {
+        let mut pids = HashSet::new();
+        let mut todo = vec![root];
+        while let Some(pid) = todo.pop() {
+            if let Some(info) = self.processes.get(&pid)
+                && pids.insert(pid)
+            {
+                todo.extend(info.children.iter().map(|&(_, child)| child));
+            }
+        }
+
+        let mut result = Recording {
+            time_start: self.time_start,
+            time_start_wall: self.time_start_wall,
+            time_end: self.time_end,
+            time_latest: self.time_latest,
+            invocation: self.invocation.clone(),
+            root_pid: pids.contains(&root).then_some(root),
+            processes: self
+                .processes
+                .iter()
+                .filter(|(pid, _)| pids.contains(pid))
+                .map(|(&pid, info)| (pid, info.clone()))
+                .collect(),
+            notes: self
+                .notes
+                .iter()
+                .filter(|(pid, _)| pids.contains(pid))
+                .map(|(&pid, note)| (pid, note.clone()))
+                .collect(),
+            // the output belongs to the root process, with its descendants
+            output: if Some(root) == self.root_pid {
+                self.output.clone()
+            } else {
+                VecDeque::new()
+            },
+            output_truncated: Some(root) == self.root_pid && self.output_truncated,
+            limits: RecordingLimits::default(),
+            truncated: self.truncated,
+            // the raw events can't be split up by process
+            event_log: None,
+            output_len: 0,
+            dropped: HashSet::new(),
+            pending_parents: HashMap::new(),
+            placeholders: HashSet::new(),
+            revision: next_revision(),
+        };
+        if let Some(root_info) = result.processes.get_mut(&root) {
+            root_info.parent = None;
+        }
+        result
+    }
+
+    // the latest end time and all processes of the subtree starting at `pid`, `None` if any of them is still running
+    fn finished_subtree(&self, pid: Pid) -> Option<(f64, Vec<Pid>)> {
+        let mut end = f64::NEG_INFINITY;
+        let mut pids = vec![];
+        let mut todo = vec![pid];
+        while let Some(pid) = todo.pop() {
+            let Some(info) = self.processes.get(&pid) else {
+                continue;
+            };
+            end = end.max(info.time.end?);
+            pids.push(pid);
+            todo.extend(info.children.iter().map(|&(_, child)| child));
+        }
+        Some((end, pids))
+    }
+
+    // check the event against the [limits](Recording::limits), and return whether it should be ignored
+    fn apply_limits(&mut self, event: &TraceEvent) -> bool {
+        match *event {
+            TraceEvent::ProcessStart { pid, .. } => {
+                // children of dropped processes are dropped before they start
+                if self.dropped.contains(&pid) {
+                    return true;
+                }
+                let full = self.limits.max_processes.is_some_and(|max| self.processes.len() >= max);
+                if full || self.exceeds_max_depth(self.pending_parents.get(&pid).copied()) {
+                    self.drop_process(pid);
+                    return true;
+                }
+                false
+            }
+            // once a dropped process exits its pid can be reused
+            TraceEvent::ProcessExit { pid, .. } => self.dropped.remove(&pid),
+            TraceEvent::ProcessChild { parent, child, .. } => {
+                // the child might already have started, without a parent to check its depth against
+                if self.dropped.contains(&parent)
+                    || self.dropped.contains(&child)
+                    || self.exceeds_max_depth(Some(parent))
+                {
+                    self.drop_process(child);
+                    return true;
+                }
+                false
+            }
+            TraceEvent::ProcessExec { pid, .. }
+            | TraceEvent::ExecFailed { pid, .. }
+            | TraceEvent::ProcessCpuTime { pid, .. }
+            | TraceEvent::ProcessFdCount { pid, .. }
+            | TraceEvent::ProcessMemory { pid, .. }
+            | TraceEvent::ProcessGroup { pid, .. }
+            | TraceEvent::ProcessNamespace { pid, .. }
+            | TraceEvent::ProcessCredentials { pid, .. }
+            | TraceEvent::ProcessVforkBlocked { pid, .. } => self.dropped.contains(&pid),
+            TraceEvent::None
+            | TraceEvent::TraceStart { .. }
+            | TraceEvent::TraceEnd { .. }
+            | TraceEvent::Output { .. } => false,
+        }
+    }
+
+    // whether a child of `parent` would be nested deeper than [RecordingLimits::max_depth]
+    fn exceeds_max_depth(&self, parent: Option<Pid>) -> bool {
+        match (self.limits.max_depth, parent) {
+            // the ancestry of the parent includes the root at depth 0 and the parent itself
+            (Some(max_depth), Some(parent)) => self.ancestry(parent).len() > max_depth,
+            _ => false,
+        }
+    }
+
+    fn drop_process(&mut self, pid: Pid) {
+        self.truncated = true;
+        self.dropped.insert(pid);
+        self.pending_parents.remove(&pid);
+
+        // children reported before their parent were already recorded without one
+        if let Some(info) = self.processes.get(&pid)
+            && info.parent.is_none()
+            && info.children.is_empty()
+            && Some(pid) != self.root_pid
+        {
+            self.processes.shift_remove(&pid);
+            self.placeholders.remove(&pid);
+        }
+    }
+
+    // the process with the given pid, or a placeholder for it if it hasn't been reported as started yet
+    fn process_mut(&mut self, pid: Pid, time: Option<f64>) -> &mut ProcessInfo {
+        if !self.processes.contains_key(&pid) {
+            let start = time.or(self.time_latest).unwrap_or(0.0);
+            self.insert_process(pid, start);
+            self.placeholders.insert(pid);
+        }
+
+        let info = self.processes.get_mut(&pid).unwrap();
+        if let Some(time) = time
+            && self.placeholders.contains(&pid)
+        {
+            info.time.start = info.time.start.min(time);
+        }
+        info
+    }
+
+    fn insert_process(&mut self, pid: Pid, start: f64) {
+        let info = ProcessInfo {
+            pid,
+            parent: self.pending_parents.remove(&pid),
+            time: TimeRange { start, end: None },
+            exit_code: None,
+            exit_signal: None,
+            cpu_time: None,
+            peak_fds: None,
+            pgid: None,
+            sid: None,
+            namespace_id: None,
+            credentials: None,
+            vfork_blocked: Vec::new(),
+            exec_failures: Vec::new(),
+            execs: Vec::new(),
+            children: Vec::new(),
+        };
+        self.processes.insert_first(pid, info);
+
+        if self.root_pid.is_none() {
+            self.root_pid = Some(pid);
+        }
+    }
+
+    // move the process with the given pid to a new unused (negative) pid, freeing up the original pid
+    fn retire_pid(&mut self, pid: Pid) {
+        let new_pid = (1..)
+            .map(|i| Pid::from_raw(-i))
+            .find(|p| !self.processes.contains_key(p))
+            .unwrap();
+
+        let mut info = self.processes.shift_remove(&pid).unwrap();
+        info.pid = new_pid;
+
+        // fix up references to the old pid, the new process might already be listed as a child of the same parent
+        if let Some(parent) = info.parent
+            && let Some(parent_info) = self.processes.get_mut(&parent)
+            && let Some(entry) = parent_info.children.iter_mut().find(|(_, c)| *c == pid)
+        {
+            entry.1 = new_pid;
+        }
+        for &(_, child) in &info.children {
+            if let Some(child_info) = self.processes.get_mut(&child) {
+                child_info.parent = Some(new_pid);
+            }
+        }
+        if self.root_pid == Some(pid) {
+            self.root_pid = Some(new_pid);
+        }
+
+        self.processes.insert_first(new_pid, info);
+    }
+
+    /// Convert a time relative to the start of the recording to wall-clock time, if the anchor is known.
+    pub fn wall_time(&self, time: f64) -> Option<SystemTime> {
+        let start = self.time_start_wall?;
+        if time >= 0.0 {
+            Some(start + Duration::from_secs_f64(time))
+        } else {
+            Some(start - Duration::from_secs_f64(-time))
+        }
+    }
+
+    /// Time since the start of the recording, or the total duration once it has finished.
+    pub fn elapsed(&self) -> Option<f64> {
+        let root_end = self.root_pid.and_then(|pid| self.processes.get(&pid)?.time.end);
+        self.time_end
+            .or(root_end)
+            .or_else(|| Some(self.time_start?.elapsed().as_secs_f64()))
+            .or(self.time_latest)
+    }
+
+    /// The time between the start of tracing and the first successful exec of the root process, if any.
+    pub fn spawn_overhead(&self) -> Option<f64> {
+        let root = self.processes.get(&self.root_pid?)?;
+        Some(root.execs.first()?.time)
+    }
+
+    /// The fraction of its lifetime the given process was busy on the CPU, summed over all of its threads.
+    pub fn cpu_utilization(&self, pid: Pid) -> Option<f64> {
+        let info = self.processes.get(&pid)?;
+        let cpu_time = info.cpu_time?;
+        let end = info.time.end.or_else(|| self.elapsed())?;
+        let duration = end - info.time.start;
+        (duration > 0.0).then(|| cpu_time / duration)
+    }
+
+    /// Count all processes and threads in the recording, including the root process.
+    pub fn total_counts(&self) -> ChildCounts {
+        // threads can be reported as children before they start, only count the ones that are recorded
+        let thread_pids = self.thread_pids();
+        let threads = self.processes.keys().filter(|pid| thread_pids.contains(pid)).count();
+        ChildCounts {
+            processes: self.processes.len() - threads,
+            threads,
+        }
+    }
+
+    /// The pids of all processes that were reported as a thread of their parent.
+    pub fn thread_pids(&self) -> HashSet<Pid> {
+        self.processes
+            .values()
+            .flat_map(|info| &info.children)
+            .filter(|&&(kind, _)| kind == ProcessKind::Thread)
+            .map(|&(_, pid)| pid)
+            .collect()
+    }
+
+    /// The number of processes and threads that have not exited yet.
+    pub fn active_count(&self) -> usize {
+        self.processes.values().filter(|info| info.time.end.is_none()).count()
+    }
+
+    /// The chain of ancestors of the given process, starting from the oldest known ancestor and ending with `pid` itself.
+    pub fn ancestry(&self, pid: Pid) -> Vec<Pid> {
+        let mut result = vec![];
+        let mut curr = Some(pid);
+        while let Some(pid) = curr
+            && self.processes.contains_key(&pid)
+            && result.len() < self.processes.len()
+        {
+            result.push(pid);
+            curr = self.processes[&pid].parent;
+        }
+        result.reverse();
+        result
+    }
+
+    /// The child of the root that the given process descends from, or the process itself if it is one.
+    pub fn top_level_child(&self, pid: Pid) -> Option<Pid> {
+        let root = self.root_pid?;
+        let mut curr = pid;
+        for _ in 0..self.processes.len() {
+            let parent = self.processes.get(&curr)?.parent?;
+            if parent == root {
+                return Some(curr);
+            }
+            curr = parent;
+        }
+        None
+    }
+
+    /// Whether the given process runs in different namespaces than the root, eg. because it's inside a container.
+    pub fn in_other_namespace(&self, pid: Pid) -> bool {
+        let namespace_id = |pid| self.processes.get(&pid)?.namespace_id;
+        match (self.root_pid.and_then(namespace_id), namespace_id(pid)) {
+            (Some(root), Some(ns)) => root != ns,
+            _ => false,
+        }
+    }
+
+    /// Whether the given process runs with different credentials than its parent or with elevated effective ids.
+    pub fn credentials_changed(&self, pid: Pid) -> bool {
+        let Some(info) = self.processes.get(&pid) else {
+            return false;
+        };
+        let Some(credentials) = info.credentials else {
+            return false;
+        };
+        let parent_credentials = info.parent.and_then(|parent| self.processes.get(&parent)?.credentials);
+        credentials.is_elevated() || parent_credentials.is_some_and(|parent| parent != credentials)
+    }
+
+    /// The chain of processes, starting from the root, that gates the end of the recording.
+    pub fn critical_path(&self) -> Vec<Pid> {
+        let mut path = vec![];
+        let mut curr = self.root_pid;
+
+        while let Some(pid) = curr {
+            path.push(pid);
+
+            let mut next: Option<(Pid, f64)> = None;
+            self.for_each_process_child(pid, &mut |kind, child_pid| {
+                if kind != ProcessKind::Process {
+                    return;
+                }
+                let Some(child) = self.processes.get(&child_pid) else {
+                    return;
+                };
+                let child_end = child.time.end.unwrap_or(f64::INFINITY);
+                if next.is_none_or(|(_, next_end)| child_end > next_end) {
+                    next = Some((child_pid, child_end));
+                }
+            });
+            curr = next.map(|(next_pid, _)| next_pid);
+        }
+
+        path
+    }
+
+    /// Whether `pid` is `ancestor` itself or one of its descendants, following the parent links.
+    pub fn is_descendant(&self, pid: Pid, ancestor: Pid) -> bool {
+        let mut curr = Some(pid);
+        // bound the number of steps to guard against cycles in broken recordings
+        for _ in 0..=self.processes.len() {
+            match curr {
+                Some(curr_pid) if curr_pid == ancestor => return true,
+                Some(curr_pid) => curr = self.processes.get(&curr_pid).and_then(|info| info.parent),
+                None => return false,
+            }
+        }
+        false
     }
 
     pub fn child_counts(&self, pid: Pid) -> ChildCounts {
@@ -128,3 +1230,523 @@ impl Recording {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+
+    fn pid(pid: i32) -> Pid {
+        Pid::from_raw(pid)
+    }
+
+    #[test]
+    fn parent_set_after_child_event() {
+        // the fixtures report children before they start, so the parent is backfilled on start
+        let mut rec = Recording::from_events(fixtures::build());
+        assert_eq!(rec.processes[&pid(100)].parent, None);
+        assert_eq!(rec.processes[&pid(101)].parent, Some(pid(100)));
+        assert_eq!(rec.processes[&pid(104)].parent, Some(pid(101)));
+
+        // a child that already started gets its parent immediately
+        rec.report(TraceEvent::ProcessStart {
+            pid: pid(105),
+            time: 1.7,
+        });
+        assert_eq!(rec.processes[&pid(105)].parent, None);
+        rec.report(TraceEvent::ProcessChild {
+            parent: pid(104),
+            child: pid(105),
+            kind: ProcessKind::Process,
+        });
+        assert_eq!(rec.processes[&pid(105)].parent, Some(pid(104)));
+    }
+
+    #[test]
+    fn active_count_of_running_processes() {
+        // the server and one of its threads are still running
+        let mut rec = Recording::from_events(fixtures::threads());
+        assert_eq!(rec.active_count(), 2);
+
+        rec.report(fixtures::exit(203, 1.0, None));
+        assert_eq!(rec.active_count(), 1);
+        rec.report(fixtures::exit(200, 1.1, Some(0)));
+        assert_eq!(rec.active_count(), 0);
+
+        assert_eq!(Recording::from_events(fixtures::build()).active_count(), 0);
+    }
+
+    #[test]
+    fn sub_microsecond_durations_are_kept() {
+        // an hour into a trace, where f32 seconds can't even represent milliseconds
+        let start = Duration::from_secs(3600);
+        let end = start + Duration::from_nanos(500);
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, start.as_secs_f64(), "/bin/true", &["true"]));
+        events.push(fixtures::exit(1, end.as_secs_f64(), Some(0)));
+        let rec = Recording::from_events(events);
+
+        let duration = rec.processes[&pid(1)].time.duration().unwrap();
+        assert!(duration > 0.0);
+        assert!((duration - 500e-9).abs() < 1e-9, "{}", duration);
+    }
+
+    #[test]
+    fn critical_path_follows_last_child() {
+        // the linker is the last step of make
+        let rec = Recording::from_events(fixtures::build());
+        assert_eq!(rec.critical_path(), [pid(100), pid(101), pid(104)]);
+
+        // a compiler that outlives the linker gates the build instead
+        let mut events = fixtures::build();
+        let cc_exit = events
+            .iter_mut()
+            .find_map(|event| match event {
+                TraceEvent::ProcessExit { pid: p, time, .. } if *p == pid(103) => Some(time),
+                _ => None,
+            })
+            .unwrap();
+        *cc_exit = 2.05;
+        let rec = Recording::from_events(events);
+        assert_eq!(rec.critical_path(), [pid(100), pid(101), pid(103)]);
+    }
+
+    #[test]
+    fn time_range_at_cursor() {
+        let range = TimeRange {
+            start: 1.0,
+            end: Some(2.0),
+        };
+        let unfinished = TimeRange { start: 1.0, end: None };
+
+        // not started yet
+        assert_eq!(range.at_time(0.5), None);
+        // running at the cursor
+        assert_eq!(range.at_time(1.0), Some(unfinished));
+        assert_eq!(range.at_time(1.5), Some(unfinished));
+        // already finished
+        assert_eq!(range.at_time(2.0), Some(range));
+        assert_eq!(range.at_time(3.0), Some(range));
+
+        assert_eq!(unfinished.at_time(3.0), Some(unfinished));
+    }
+
+    #[test]
+    fn label_modes() {
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(
+            None,
+            1,
+            0.0,
+            "/opt/venv/bin/python3.12",
+            &["python", "build.py"],
+        ));
+        events.extend(fixtures::process(Some(1), 2, 0.1, "/usr/bin/env", &[]));
+        let rec = Recording::from_events(events);
+
+        let python = &rec.processes[&pid(1)];
+        assert_eq!(python.label(LabelMode::Basename), "python3.12");
+        assert_eq!(python.label(LabelMode::FullPath), "/opt/venv/bin/python3.12");
+        assert_eq!(python.label(LabelMode::Argv0), "python");
+
+        // without argv the basename is used instead
+        assert_eq!(rec.processes[&pid(2)].label(LabelMode::Argv0), "env");
+
+        // processes that haven't exec'd yet
+        let mut rec = Recording::new();
+        rec.report(TraceEvent::ProcessStart { pid: pid(1), time: 0.0 });
+        for mode in [LabelMode::Basename, LabelMode::FullPath, LabelMode::Argv0] {
+            assert_eq!(rec.processes[&pid(1)].label(mode), "?");
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_round_trip() {
+        let invalid = |prefix: &str| {
+            let mut bytes = prefix.as_bytes().to_vec();
+            bytes.extend_from_slice(b"\xff\xfe.c");
+            OsString::from_vec(bytes)
+        };
+        let exec = TraceEvent::ProcessExec {
+            pid: pid(1),
+            time: 0.0,
+            cwd: Some(invalid("/src/")),
+            path: invalid("/bin/"),
+            argv: vec!["cc".into(), invalid("")],
+        };
+
+        // through the event format
+        let exec: TraceEvent = serde_json::from_str(&serde_json::to_string(&exec).unwrap()).unwrap();
+        let mut rec = Recording::new();
+        rec.report(TraceEvent::ProcessStart { pid: pid(1), time: 0.0 });
+        rec.report(exec);
+
+        // and through the recording format
+        let mut saved = vec![];
+        rec.to_json(&mut saved).unwrap();
+        let loaded = Recording::from_json(&saved[..]).unwrap();
+
+        let exec = &loaded.processes[&pid(1)].execs[0];
+        assert_eq!(exec.cwd, Some(invalid("/src/")));
+        assert_eq!(exec.path, invalid("/bin/"));
+        assert_eq!(exec.argv, [OsString::from("cc"), invalid("")]);
+    }
+
+    #[test]
+    fn time_latest_tracks_newest_event() {
+        let mut rec = Recording::from_events(fixtures::threads());
+        assert_eq!(rec.time_latest, Some(0.8));
+
+        // events without a time and older events don't move it back
+        rec.report(TraceEvent::None);
+        rec.report(fixtures::exit(201, 0.6, None));
+        assert_eq!(rec.time_latest, Some(0.8));
+
+        rec.report(TraceEvent::ProcessStart {
+            pid: pid(204),
+            time: 1.5,
+        });
+        assert_eq!(rec.time_latest, Some(1.5));
+        assert_eq!(Recording::new().time_latest, None);
+    }
+
+    #[test]
+    fn revision_changes_on_modification() {
+        let mut rec = Recording::from_events(fixtures::build());
+        let revision = rec.revision();
+
+        // heartbeats and reads keep the revision
+        rec.report(TraceEvent::None);
+        let _ = rec.processes().len();
+        assert_eq!(rec.revision(), revision);
+
+        // a clone has the same contents until either one is modified
+        let mut clone = rec.clone();
+        assert_eq!(clone.revision(), revision);
+        clone.notes_mut().insert(pid(1), "slow".into());
+        assert_ne!(clone.revision(), revision);
+        assert_eq!(rec.revision(), revision);
+
+        rec.processes_mut().shift_remove(&pid(1));
+        assert_ne!(rec.revision(), revision);
+        assert_ne!(rec.revision(), clone.revision());
+    }
+
+    #[test]
+    fn ancestry_of_known_chain() {
+        let rec = Recording::from_events(fixtures::build());
+        assert_eq!(rec.ancestry(pid(103)), [pid(100), pid(101), pid(103)]);
+        assert_eq!(rec.ancestry(pid(100)), [pid(100)]);
+        assert!(rec.ancestry(pid(999)).is_empty());
+    }
+
+    #[test]
+    fn subtree_keeps_only_descendants() {
+        let rec = Recording::from_events(fixtures::build());
+        let subtree = rec.subtree(pid(101));
+        assert_eq!(subtree.root_pid, Some(pid(101)));
+        let pids = subtree.processes.keys().copied().sorted().collect_vec();
+        assert_eq!(pids, [pid(101), pid(102), pid(103), pid(104)]);
+
+        let leaf = rec.subtree(pid(102));
+        assert_eq!(leaf.processes.keys().collect_vec(), [&pid(102)]);
+        assert_eq!(rec.subtree(pid(999)).root_pid, None);
+    }
+
+    #[test]
+    fn report_tolerates_any_event_order() {
+        let mut events = fixtures::build();
+        events.extend(fixtures::threads());
+
+        // a small xorshift generator, so failures are reproducible without extra dependencies
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..200 {
+            for i in (1..events.len()).rev() {
+                events.swap(i, (next() % (i as u64 + 1)) as usize);
+            }
+            let rec = Recording::from_events(events.clone());
+
+            // some orders are ambiguous, eg. an exit before the start looks like pid reuse,
+            //   but the parent links that were resolved must agree in both directions
+            for (&pid, info) in &rec.processes {
+                assert_eq!(info.pid, pid);
+                if let Some(parent) = info.parent {
+                    assert!(rec.processes[&parent].children.iter().any(|&(_, child)| child == pid));
+                }
+                for &(_, child) in &info.children {
+                    let resolved = rec.processes.get(&child).is_some_and(|child| child.parent == Some(pid));
+                    assert!(resolved || rec.pending_parents.get(&child) == Some(&pid));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn limits_cap_fork_bomb() {
+        // a root forking many children, each with a grandchild
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        for i in 0..10_000 {
+            let (child, grandchild, time) = (10 + 2 * i, 11 + 2 * i, i as f64 * 1e-3);
+            events.extend(fixtures::process(Some(1), child, time, "/bin/sh", &["sh"]));
+            events.extend(fixtures::process(Some(child), grandchild, time, "/bin/sh", &["sh"]));
+        }
+
+        let record = |limits: RecordingLimits| {
+            let mut rec = Recording::new();
+            rec.limits = limits;
+            for event in events.iter().cloned() {
+                rec.report(event);
+            }
+            rec
+        };
+        let rec = record(RecordingLimits::default());
+        assert_eq!(rec.processes.len(), 1 + 20_000);
+        assert!(!rec.truncated);
+
+        let rec = record(RecordingLimits {
+            max_processes: Some(100),
+            max_depth: None,
+        });
+        assert_eq!(rec.processes.len(), 100);
+        assert!(rec.truncated);
+
+        let rec = record(RecordingLimits {
+            max_processes: None,
+            max_depth: Some(1),
+        });
+        assert_eq!(rec.processes.len(), 1 + 10_000);
+        assert!(rec
+            .processes
+            .values()
+            .all(|info| info.children.iter().all(|(_, c)| rec.processes.contains_key(c))));
+        assert!(rec.truncated);
+    }
+
+    #[test]
+    fn hidden_thread_count() {
+        let counts = Recording::from_events(fixtures::threads()).total_counts();
+        assert_eq!((counts.processes, counts.threads), (1, 3));
+        let counts = Recording::from_events(fixtures::build()).total_counts();
+        assert_eq!((counts.processes, counts.threads), (5, 0));
+
+        // threads reported before anything started are not recorded yet
+        let mut events = fixtures::start();
+        for child in [2, 3] {
+            events.push(TraceEvent::ProcessChild {
+                parent: pid(1),
+                child: pid(child),
+                kind: ProcessKind::Thread,
+            });
+        }
+        let rec = Recording::from_events(events);
+        let counts = rec.total_counts();
+        assert_eq!(counts.processes + counts.threads, rec.processes.len());
+        assert_eq!(counts.threads, 0);
+    }
+
+    #[test]
+    fn descendant_predicate() {
+        let mut rec = Recording::from_events(fixtures::build());
+        assert!(rec.is_descendant(pid(103), pid(103)));
+        assert!(rec.is_descendant(pid(103), pid(101)));
+        assert!(rec.is_descendant(pid(103), pid(100)));
+        assert!(!rec.is_descendant(pid(103), pid(102)));
+        assert!(!rec.is_descendant(pid(100), pid(101)));
+        assert!(!rec.is_descendant(pid(999), pid(100)));
+
+        // a cycle in a broken recording terminates
+        rec.processes.get_mut(&pid(100)).unwrap().parent = Some(pid(103));
+        assert!(!rec.is_descendant(pid(102), pid(999)));
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let events = fixtures::build();
+        let mut jsonl = vec![];
+        crate::export::events::write_events_jsonl(&events, &mut jsonl).unwrap();
+
+        let expected = Recording::from_events(events);
+        let loaded = Recording::from_jsonl(jsonl.as_slice()).unwrap();
+        assert_eq!(loaded.root_pid, expected.root_pid);
+        assert_eq!(loaded.time_end, expected.time_end);
+        assert_eq!(loaded.processes.len(), expected.processes.len());
+        for (pid, info) in &expected.processes {
+            let loaded = &loaded.processes[pid];
+            assert_eq!(
+                (loaded.parent, loaded.time, loaded.exit_code),
+                (info.parent, info.time, info.exit_code)
+            );
+            assert_eq!(loaded.execs.last().unwrap().argv, info.execs.last().unwrap().argv);
+        }
+
+        // a truncated last line is dropped, like a capture that was cut off
+        let truncated = Recording::from_jsonl(&jsonl[..jsonl.len() - 5]).unwrap();
+        assert_eq!(truncated.time_end, None);
+        assert_eq!(truncated.processes.len(), expected.processes.len());
+
+        // but broken lines in the middle are errors
+        let mut broken = b"{\"broken\n".to_vec();
+        broken.extend(&jsonl);
+        assert!(Recording::from_jsonl(broken.as_slice()).is_err());
+    }
+
+    #[test]
+    fn load_v1_document() {
+        // version 1 had no version fields, and none of the fields added later
+        let rec = Recording::from_events(fixtures::build());
+        let mut v1 = serde_json::to_value(&rec).unwrap();
+        let object = v1.as_object_mut().unwrap();
+        assert!(!object.contains_key("format_version"));
+        object.remove("time_start_wall").unwrap();
+        object.remove("time_latest").unwrap();
+
+        let loaded = Recording::from_json(v1.to_string().as_bytes()).unwrap();
+        assert_eq!(loaded.processes.len(), rec.processes.len());
+        assert_eq!(loaded.root_pid, rec.root_pid);
+        assert_eq!(loaded.time_start_wall, None);
+    }
+
+    #[test]
+    fn load_too_new_version_fails() {
+        let doc = r#"{"format_version": 99, "tool_version": "9.9.9", "processes": {}}"#;
+        let e = Recording::from_json(doc.as_bytes()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "unsupported format_version 99 (saved by wtf 9.9.9), this version supports up to {}",
+                FORMAT_VERSION
+            )
+        );
+
+        let e = Recording::from_json(r#"{"format_version": "x"}"#.as_bytes()).unwrap_err();
+        assert_eq!(e.to_string(), "invalid format_version \"x\"");
+        assert!(Recording::from_json("[]".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn notes_are_saved() {
+        let mut rec = Recording::from_events(fixtures::build());
+        rec.notes.insert(pid(103), "why is this one slow?".to_owned());
+        rec.notes.insert(pid(100), "root".to_owned());
+
+        let mut saved = vec![];
+        rec.to_json(&mut saved).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&saved).unwrap();
+        assert_eq!(value["notes"]["103"], "why is this one slow?");
+
+        let loaded = Recording::from_json(&saved[..]).unwrap();
+        assert_eq!(loaded.notes, rec.notes);
+    }
+
+    #[test]
+    fn spawn_overhead_is_first_exec_offset() {
+        let mut rec = Recording::new();
+        assert_eq!(rec.spawn_overhead(), None);
+
+        rec.report(TraceEvent::ProcessStart { pid: pid(1), time: 0.0 });
+        assert_eq!(rec.spawn_overhead(), None);
+        for (time, path) in [(0.003, "/usr/bin/env"), (0.005, "/usr/bin/make")] {
+            rec.report(TraceEvent::ProcessExec {
+                pid: pid(1),
+                time,
+                cwd: None,
+                path: path.into(),
+                argv: vec![],
+            });
+        }
+        // later execs of the root don't count
+        assert_eq!(rec.spawn_overhead(), Some(0.003));
+    }
+
+    #[test]
+    fn memory_per_exec() {
+        // a shell that execs a compiler halfway through
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        events.push(TraceEvent::ProcessExec {
+            pid: pid(1),
+            time: 1.0,
+            cwd: None,
+            path: "/usr/bin/cc".into(),
+            argv: vec![],
+        });
+        let memory = |time: f64, rss: u64| TraceEvent::ProcessMemory { pid: pid(1), time, rss };
+        events.extend([
+            memory(0.2, 2_000),
+            memory(0.6, 3_000),
+            memory(1.2, 50_000),
+            memory(1.5, 400_000),
+            memory(1.8, 100_000),
+        ]);
+        let rec = Recording::from_events(events);
+
+        let rss = |exec: &ProcessExec| (exec.rss_start, exec.rss_peak);
+        let execs = &rec.processes[&pid(1)].execs;
+        assert_eq!(rss(&execs[0]), (Some(2_000), Some(3_000)));
+        assert_eq!(rss(&execs[1]), (Some(50_000), Some(400_000)));
+    }
+
+    #[test]
+    fn evict_removes_side_tables() {
+        let mut rec = Recording::from_events(fixtures::build());
+        // a child of the linker that never got to start
+        rec.report(TraceEvent::ProcessChild {
+            parent: pid(104),
+            child: pid(105),
+            kind: ProcessKind::Process,
+        });
+        rec.notes.insert(pid(102), "slow".to_owned());
+        rec.notes.insert(pid(100), "root".to_owned());
+
+        // the whole make subtree is evicted, only the shell remains
+        let evicted = rec.evict_finished(1);
+        assert_eq!(evicted.len(), 4);
+        assert_eq!(rec.processes.len(), 1);
+        assert!(rec.pending_parents.is_empty());
+        assert!(rec.placeholders.is_empty());
+        assert_eq!(rec.notes.keys().copied().collect_vec(), vec![pid(100)]);
+    }
+
+    #[test]
+    fn merge_two_recordings() {
+        let a = Recording::from_events(fixtures::build());
+        let b = Recording::from_events(fixtures::build());
+        let merged = Recording::merge_all([a.clone(), b], TimeAlign::Zero);
+
+        // both roots are children of the synthetic root, with distinct pids
+        assert_eq!(merged.root_pid, Some(MERGED_ROOT_PID));
+        assert_eq!(merged.processes.len(), 2 * a.processes.len() + 1);
+        let roots = &merged.processes[&MERGED_ROOT_PID].children;
+        assert_eq!(roots.len(), 2);
+        assert_ne!(roots[0].1, roots[1].1);
+        for &(_, root) in roots {
+            assert_eq!(merged.processes[&root].parent, Some(MERGED_ROOT_PID));
+            assert_eq!(merged.processes[&root].name(), "sh");
+        }
+        assert_eq!(
+            merged.processes[&MERGED_ROOT_PID].time,
+            TimeRange {
+                start: 0.0,
+                end: Some(2.2)
+            }
+        );
+    }
+
+    #[test]
+    fn merge_single_recording_is_unchanged() {
+        let mut rec = Recording::from_events(fixtures::build());
+        rec.align_to_zero();
+        let merged = Recording::merge_all([rec.clone()], TimeAlign::Zero);
+
+        assert_eq!(merged.root_pid, Some(pid(100)));
+        assert!(merged.processes.keys().eq(rec.processes.keys()));
+    }
+}
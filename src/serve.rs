@@ -0,0 +1,116 @@
+use crate::record::{LabelMode, Recording};
+use crate::stats::executable_stats;
+use itertools::Itertools;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, Response, Server};
+
+/// Serve the recording as json over HTTP on a new thread, reading the latest state for every request.
+/// The following endpoints are available:
+/// * `GET /recording`: the full recording, in the same format as saved recordings.
+/// * `GET /processes`: all processes as a json array.
+/// * `GET /stats`: the process counts, the elapsed time and the total time per executable.
+pub fn spawn_server(
+    addr: &str,
+    recording: Arc<Mutex<Recording>>,
+) -> Result<JoinHandle<()>, Box<dyn Error + Send + Sync>> {
+    let server = Server::http(addr)?;
+
+    Ok(std::thread::spawn(move || {
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        for request in server.incoming_requests() {
+            let body = match request.method() {
+                Method::Get => response_body(&recording.lock().unwrap(), request.url()),
+                _ => None,
+            };
+            let response = match body {
+                Some(Ok(body)) => Response::from_data(body).with_header(content_type.clone()),
+                Some(Err(e)) => Response::from_string(e.to_string()).with_status_code(500),
+                None => Response::from_string("not found").with_status_code(404),
+            };
+            let _ = request.respond(response);
+        }
+    }))
+}
+
+fn response_body(rec: &Recording, url: &str) -> Option<serde_json::Result<Vec<u8>>> {
+    // ignore the query string
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+
+    let mut body = vec![];
+    let result = match path {
+        "/recording" => rec.to_json(&mut body),
+        "/processes" => serde_json::to_writer(&mut body, &rec.processes().values().collect_vec()),
+        "/stats" => serde_json::to_writer(&mut body, &stats_json(rec)),
+        _ => return None,
+    };
+    Some(result.map(|()| body))
+}
+
+fn stats_json(rec: &Recording) -> serde_json::Value {
+    let counts = rec.total_counts();
+    let executables = executable_stats(rec, LabelMode::Basename)
+        .into_iter()
+        .map(|stats| {
+            serde_json::json!({
+                "name": stats.name.to_string_lossy(),
+                "count": stats.count,
+                "total_time": stats.total_time,
+            })
+        })
+        .collect_vec();
+
+    serde_json::json!({
+        "elapsed": rec.elapsed(),
+        "finished": rec.time_end.is_some(),
+        "processes": counts.processes,
+        "threads": counts.threads,
+        "active": rec.active_count(),
+        "executables": executables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::fixtures;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn stats_of_running_recording() {
+        let rec = Recording::from_events(fixtures::threads());
+        let body = response_body(&rec, "/stats?pretty").unwrap().unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats["finished"], false);
+        assert_eq!(stats["threads"], 3);
+        // the server itself and its last thread are still running
+        assert_eq!(stats["active"], 2);
+        assert!(response_body(&rec, "/missing").is_none());
+    }
+
+    #[test]
+    fn fetch_process_list() {
+        // record a replayed trace into the served recording
+        let recording = Arc::new(Mutex::new(Recording::new()));
+        let backend = crate::backend::StartedBackend::Replay(fixtures::build());
+        crate::backend::record_started(backend, Default::default(), &recording).unwrap();
+
+        // find a free port, there is a small window for someone else to take it
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addr = format!("127.0.0.1:{}", port);
+        spawn_server(&addr, recording.clone()).unwrap();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"GET /processes HTTP/1.0\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.0 200"), "{}", head);
+        let processes: Vec<serde_json::Value> = serde_json::from_str(body).unwrap();
+        let pids = processes.iter().map(|info| info["pid"].as_i64().unwrap()).collect_vec();
+        assert_eq!(pids, [100, 101, 102, 103, 104]);
+    }
+}
@@ -0,0 +1,785 @@
+use crate::record::{LabelMode, ProcessInfo, ProcessKind, Recording, TimeRange};
+use crate::swriteln;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use nix::unistd::Pid;
+use ordered_float::OrderedFloat;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+
+#[derive(Debug, Clone)]
+pub struct ExecutableStats {
+    pub name: OsString,
+    pub count: usize,
+    pub total_time: f64,
+}
+
+/// Group all processes by executable label, sorted by descending total time.
+pub fn executable_stats(rec: &Recording, label: LabelMode) -> Vec<ExecutableStats> {
+    let threads = rec.thread_pids();
+    let processes = rec.processes().values().filter(|info| !threads.contains(&info.pid));
+    group_by_executable(processes, label)
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectionSummary {
+    pub count: usize,
+    pub total_time: f64,
+    pub executables: Vec<ExecutableStats>,
+}
+
+/// Summarize a set of selected processes, unfinished processes don't contribute to the total time.
+pub fn selection_summary<'a>(
+    rec: &Recording,
+    pids: impl IntoIterator<Item = &'a Pid>,
+    label: LabelMode,
+) -> SelectionSummary {
+    let executables = group_by_executable(pids.into_iter().filter_map(|pid| rec.processes().get(pid)), label);
+    SelectionSummary {
+        count: executables.iter().map(|stats| stats.count).sum(),
+        total_time: executables.iter().map(|stats| stats.total_time).sum(),
+        executables,
+    }
+}
+
+// group processes by executable label, sorted by descending total time
+fn group_by_executable<'a>(
+    processes: impl IntoIterator<Item = &'a ProcessInfo>,
+    label: LabelMode,
+) -> Vec<ExecutableStats> {
+    let mut groups: IndexMap<&OsStr, ExecutableStats> = IndexMap::new();
+    for info in processes {
+        let name = info.label(label);
+        let entry = groups.entry(name).or_insert_with(|| ExecutableStats {
+            name: name.to_owned(),
+            count: 0,
+            total_time: 0.0,
+        });
+        entry.count += 1;
+        entry.total_time += info.time.duration().unwrap_or(0.0);
+    }
+
+    groups
+        .into_values()
+        .sorted_by(|a, b| b.total_time.total_cmp(&a.total_time))
+        .collect()
+}
+
+/// A plain text report of the recording: wall time, process counts, the `top` executables and the critical path.
+pub fn summary_text(rec: &Recording, top: usize) -> String {
+    let counts = rec.total_counts();
+    let fmt_duration = |d: Option<f64>| d.map_or("?".to_owned(), |d| format!("{:.3}s", d));
+
+    let mut text = String::new();
+    swriteln!(text, "wall time: {}", fmt_duration(rec.elapsed()));
+    swriteln!(text, "spawn overhead: {}", fmt_duration(rec.spawn_overhead()));
+    swriteln!(text, "processes: {}", counts.processes);
+    swriteln!(text, "threads: {}", counts.threads);
+    if rec.truncated {
+        swriteln!(text, "(truncated, processes past the limits were not recorded)");
+    }
+
+    swriteln!(text, "top executables:");
+    for stats in executable_stats(rec, LabelMode::Basename).iter().take(top) {
+        swriteln!(
+            text,
+            "    {}: {} ({:.3}s)",
+            stats.name.display(),
+            stats.count,
+            stats.total_time
+        );
+    }
+
+    swriteln!(text, "critical path:");
+    for pid in rec.critical_path() {
+        let info = &rec.processes()[&pid];
+        swriteln!(
+            text,
+            "    {} {} ({})",
+            pid,
+            info.name().display(),
+            fmt_duration(info.time.duration())
+        );
+    }
+    text
+}
+
+/// How the executables differ between two recordings of the same command, see [trace_diff].
+#[derive(Debug, Clone)]
+pub struct TraceDiff {
+    /// Sorted by descending absolute delta, executables that only appear in one recording first.
+    pub executables: Vec<ExecutableDiff>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutableDiff {
+    pub name: OsString,
+    pub kind: DiffKind,
+    /// The number of invocations and their total time in both recordings.
+    pub count_before: usize,
+    pub count_after: usize,
+    pub time_before: f64,
+    pub time_after: f64,
+    /// The change in time summed over matched invocations, or the total time for added and removed executables.
+    pub delta: f64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Slower,
+    Faster,
+    Same,
+}
+
+/// Compare two recordings per executable, matching the n-th invocations of each executable by start time.
+pub fn trace_diff(before: &Recording, after: &Recording, label: LabelMode) -> TraceDiff {
+    let invocations = |rec: &Recording| {
+        let threads = rec.thread_pids();
+        let mut result: IndexMap<OsString, Vec<f64>> = IndexMap::new();
+        let processes = rec
+            .processes()
+            .values()
+            .filter(|info| !threads.contains(&info.pid))
+            .sorted_by_key(|info| (OrderedFloat(info.time.start), info.pid));
+        for info in processes {
+            let duration = info.time.duration().unwrap_or(0.0);
+            result.entry(info.label(label).to_owned()).or_default().push(duration);
+        }
+        result
+    };
+    let before = invocations(before);
+    let mut after = invocations(after);
+
+    let mut executables = vec![];
+    for (name, times_before) in before {
+        let times_after = after.shift_remove(&name).unwrap_or_default();
+        executables.push(ExecutableDiff::new(name, &times_before, &times_after));
+    }
+    for (name, times_after) in after {
+        executables.push(ExecutableDiff::new(name, &[], &times_after));
+    }
+
+    let kind_order = |kind: DiffKind| !matches!(kind, DiffKind::Added | DiffKind::Removed);
+    executables.sort_by(|a, b| {
+        kind_order(a.kind)
+            .cmp(&kind_order(b.kind))
+            .then(b.delta.abs().total_cmp(&a.delta.abs()))
+    });
+    TraceDiff { executables }
+}
+
+impl ExecutableDiff {
+    fn new(name: OsString, times_before: &[f64], times_after: &[f64]) -> Self {
+        // (summing an empty list of floats gives -0.0)
+        let time_before = times_before.iter().fold(0.0, |acc, t| acc + t);
+        let time_after = times_after.iter().fold(0.0, |acc, t| acc + t);
+        let delta = if times_before.is_empty() || times_after.is_empty() {
+            time_after - time_before
+        } else {
+            times_before.iter().zip(times_after).map(|(b, a)| a - b).sum()
+        };
+        let kind = if times_before.is_empty() {
+            DiffKind::Added
+        } else if times_after.is_empty() {
+            DiffKind::Removed
+        } else if delta > 0.0 {
+            DiffKind::Slower
+        } else if delta < 0.0 {
+            DiffKind::Faster
+        } else {
+            DiffKind::Same
+        };
+        ExecutableDiff {
+            name,
+            kind,
+            count_before: times_before.len(),
+            count_after: times_after.len(),
+            time_before,
+            time_after,
+            delta,
+        }
+    }
+}
+
+/// A plain text report of the `top` executables of the diff.
+pub fn trace_diff_text(diff: &TraceDiff, top: usize) -> String {
+    let mut text = String::new();
+    for exec in diff.executables.iter().take(top) {
+        let kind = match exec.kind {
+            DiffKind::Added => "added",
+            DiffKind::Removed => "removed",
+            DiffKind::Slower => "slower",
+            DiffKind::Faster => "faster",
+            DiffKind::Same => "same",
+        };
+        swriteln!(
+            text,
+            "{}: {} {:+.3}s ({}x {:.3}s -> {}x {:.3}s)",
+            exec.name.display(),
+            kind,
+            exec.delta,
+            exec.count_before,
+            exec.time_before,
+            exec.count_after,
+            exec.time_after,
+        );
+    }
+    text
+}
+
+/// The line for a single process in [process_tree_text], `pid name (duration)`.
+pub fn process_tree_line(info: &ProcessInfo, label: LabelMode) -> String {
+    let duration = info.time.duration().map_or("?".to_owned(), |d| format!("{:.3}s", d));
+    format!("{} {} ({})", info.pid, info.label(label).display(), duration)
+}
+
+/// The process tree below the root as indented text, like `pstree`.
+pub fn process_tree_text(rec: &Recording, label: LabelMode, include_threads: bool) -> String {
+    fn visit(
+        text: &mut String,
+        rec: &Recording,
+        label: LabelMode,
+        include_threads: bool,
+        visited: &mut HashSet<Pid>,
+        pid: Pid,
+        depth: usize,
+    ) {
+        // guard against cycles in broken recordings
+        if !visited.insert(pid) {
+            return;
+        }
+        let Some(info) = rec.processes().get(&pid) else {
+            return;
+        };
+
+        swriteln!(text, "{}{}", "    ".repeat(depth), process_tree_line(info, label));
+        for &(kind, child) in &info.children {
+            if include_threads || kind == ProcessKind::Process {
+                visit(text, rec, label, include_threads, visited, child, depth + 1);
+            }
+        }
+    }
+
+    let mut text = String::new();
+    if let Some(root) = rec.root_pid {
+        visit(&mut text, rec, label, include_threads, &mut HashSet::new(), root, 0);
+    }
+    text
+}
+
+/// The number of concurrently active processes and threads over time, as `(time, count)` steps.
+#[derive(Debug, Clone, Default)]
+pub struct Concurrency {
+    pub processes: Vec<(f64, usize)>,
+    pub threads: Vec<(f64, usize)>,
+}
+
+pub fn concurrency_over_time(rec: &Recording) -> Concurrency {
+    let threads = rec.thread_pids();
+    let (thread_infos, process_infos): (Vec<_>, Vec<_>) =
+        rec.processes().values().partition(|info| threads.contains(&info.pid));
+
+    Concurrency {
+        processes: sweep_active_count(process_infos.iter().map(|info| info.time)),
+        threads: sweep_active_count(thread_infos.iter().map(|info| info.time)),
+    }
+}
+
+// sweep over the start and end times, with a single step per distinct time
+fn sweep_active_count(ranges: impl IntoIterator<Item = TimeRange>) -> Vec<(f64, usize)> {
+    let mut deltas: Vec<(f64, isize)> = vec![];
+    for range in ranges {
+        deltas.push((range.start, 1));
+        if let Some(end) = range.end {
+            deltas.push((end, -1));
+        }
+    }
+    deltas.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut steps: Vec<(f64, usize)> = vec![];
+    let mut count: isize = 0;
+    for (time, delta) in deltas {
+        count += delta;
+        let count = count.max(0) as usize;
+        match steps.last_mut() {
+            Some(last) if last.0 == time => last.1 = count,
+            _ => steps.push((time, count)),
+        }
+    }
+    steps
+}
+
+/// The intervals within the lifetime of `pid` during which none of its descendants were alive.
+pub fn idle_gaps(rec: &Recording, pid: Pid, time_end: f64) -> Vec<TimeRange> {
+    let Some(info) = rec.processes().get(&pid) else {
+        return vec![];
+    };
+    // collect the time ranges of all descendants
+    let mut ranges = vec![];
+    let mut todo = info.children.iter().map(|&(_, child)| child).collect_vec();
+    while let Some(pid) = todo.pop() {
+        if let Some(child) = rec.processes().get(&pid) {
+            ranges.push(child.time);
+            todo.extend(child.children.iter().map(|&(_, child)| child));
+        }
+    }
+
+    uncovered_ranges(info.time.start, info.time.end.unwrap_or(time_end), ranges)
+}
+
+/// The summed wall time of all threads and of all child processes in a subtree, see [thread_process_time].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ThreadProcessTime {
+    pub threads: f64,
+    pub processes: f64,
+}
+
+impl ThreadProcessTime {
+    /// The thread time relative to the process time, `None` if there were no child processes.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.processes > 0.0).then(|| self.threads / self.processes)
+    }
+}
+
+/// The summed wall time of the threads and child processes in the subtree below `pid`, excluding `pid` itself.
+pub fn thread_process_time(rec: &Recording, pid: Pid, time_end: f64) -> ThreadProcessTime {
+    let mut result = ThreadProcessTime::default();
+    let mut visited = HashSet::new();
+    let mut todo = vec![pid];
+    while let Some(curr) = todo.pop() {
+        // guard against cycles in broken recordings
+        if !visited.insert(curr) {
+            continue;
+        }
+        rec.for_each_process_child(curr, &mut |kind, child| {
+            let Some(info) = rec.processes().get(&child) else {
+                return;
+            };
+            let duration = info.time.end.unwrap_or(time_end) - info.time.start;
+            match kind {
+                ProcessKind::Thread => result.threads += duration,
+                ProcessKind::Process => {
+                    result.processes += duration;
+                    todo.push(child);
+                }
+            }
+        });
+    }
+    result
+}
+
+/// The time `pid` spent without any of its child processes running, as opposed to its wall time.
+pub fn self_time(rec: &Recording, pid: Pid) -> Option<f64> {
+    let info = rec.processes().get(&pid)?;
+    let end = info.time.end?;
+    let children = info
+        .children
+        .iter()
+        .filter(|&&(kind, _)| kind == ProcessKind::Process)
+        .filter_map(|(_, child)| rec.processes().get(child))
+        .map(|child| child.time);
+    let uncovered = uncovered_ranges(info.time.start, end, children);
+    Some(uncovered.iter().filter_map(TimeRange::duration).sum())
+}
+
+// the parts of `bound_start..bound_end` not covered by any of the ranges, unfinished ranges never end
+fn uncovered_ranges(bound_start: f64, bound_end: f64, ranges: impl IntoIterator<Item = TimeRange>) -> Vec<TimeRange> {
+    let mut gaps = vec![];
+    let mut idle_since = Some(bound_start);
+    for (time, count) in sweep_active_count(ranges) {
+        let time = time.clamp(bound_start, bound_end);
+        match idle_since {
+            Some(start) if count > 0 => {
+                if time > start {
+                    gaps.push(TimeRange { start, end: Some(time) });
+                }
+                idle_since = None;
+            }
+            None if count == 0 => idle_since = Some(time),
+            _ => {}
+        }
+    }
+    if let Some(start) = idle_since
+        && start < bound_end
+    {
+        gaps.push(TimeRange {
+            start,
+            end: Some(bound_end),
+        });
+    }
+    gaps
+}
+
+/// Group processes by process group id, in order of first appearance. Processes without a known group are skipped.
+pub fn process_groups(rec: &Recording) -> IndexMap<Pid, Vec<Pid>> {
+    let mut groups: IndexMap<Pid, Vec<Pid>> = IndexMap::new();
+    for info in rec.processes().values() {
+        if let Some(pgid) = info.pgid {
+            groups.entry(pgid).or_default().push(info.pid);
+        }
+    }
+    groups
+}
+
+/// One argument in the diff between two argument lists.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ArgDiff<T> {
+    Same(T),
+    /// Only in the old argument list.
+    Removed(T),
+    /// Only in the new argument list.
+    Added(T),
+}
+
+// the maximum size of the table used to diff arguments, larger differences are shown as a full replacement
+const ARGV_DIFF_MAX_TABLE: usize = 1 << 20;
+
+/// Diff two argument lists, keeping the longest common subsequence of arguments as [`ArgDiff::Same`].
+pub fn argv_diff<'a, T: PartialEq>(old: &'a [T], new: &'a [T]) -> Vec<ArgDiff<&'a T>> {
+    // the common prefix and suffix are usually most of the arguments, keep them out of the table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_rest[..old_rest.len() - suffix];
+    let new_mid = &new_rest[..new_rest.len() - suffix];
+
+    let mut result = old[..prefix].iter().map(ArgDiff::Same).collect_vec();
+    if (old_mid.len() + 1) * (new_mid.len() + 1) > ARGV_DIFF_MAX_TABLE {
+        result.extend(old_mid.iter().map(ArgDiff::Removed));
+        result.extend(new_mid.iter().map(ArgDiff::Added));
+    } else {
+        // lcs[i][j] is the length of the longest common subsequence of old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                result.push(ArgDiff::Same(&old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                result.push(ArgDiff::Removed(&old_mid[i]));
+                i += 1;
+            } else {
+                result.push(ArgDiff::Added(&new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+    result.extend(old_rest[old_rest.len() - suffix..].iter().map(ArgDiff::Same));
+    result
+}
+
+/// The previous invocation of the same executable as the given process, or the next one for the first invocation.
+pub fn previous_invocation(rec: &Recording, pid: Pid, label: LabelMode) -> Option<Pid> {
+    let info = rec.processes().get(&pid)?;
+    let name = info.label(label);
+    let key = |other: &ProcessInfo| (OrderedFloat(other.time.start), other.pid);
+
+    let same = rec
+        .processes()
+        .values()
+        .filter(|other| other.pid != pid && other.label(label) == name);
+    let (before, after): (Vec<_>, Vec<_>) = same.partition(|other| key(other) < key(info));
+    before
+        .into_iter()
+        .max_by_key(|other| key(other))
+        .or_else(|| after.into_iter().min_by_key(|other| key(other)))
+        .map(|other| other.pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{fixtures, TraceEvent};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn executable_stats_aggregates_by_label() {
+        let rec = Recording::from_events(fixtures::build());
+        let stats = executable_stats(&rec, LabelMode::Basename);
+        assert_eq!(stats.len(), 4);
+
+        let cc = stats.iter().find(|s| s.name == "cc").unwrap();
+        assert_eq!(cc.count, 2);
+        assert_close(cc.total_time, 1.0 + 1.2);
+
+        // sorted by descending total time
+        assert!(stats.windows(2).all(|w| w[0].total_time >= w[1].total_time));
+        assert_eq!(stats.last().unwrap().name, "ld");
+
+        let stats = executable_stats(&rec, LabelMode::FullPath);
+        assert!(stats.iter().any(|s| s.name == "/usr/bin/cc"));
+    }
+
+    #[test]
+    fn executable_stats_skips_threads() {
+        let rec = Recording::from_events(fixtures::threads());
+        let stats = executable_stats(&rec, LabelMode::Basename);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "server");
+        assert_eq!(stats[0].count, 1);
+    }
+
+    #[test]
+    fn idle_gaps_between_children() {
+        let rec = Recording::from_events(fixtures::build());
+        let gaps = idle_gaps(&rec, Pid::from_raw(101), 2.2);
+        let expected = [(0.1, 0.2), (1.5, 1.6), (2.0, 2.1)];
+        assert_eq!(gaps.len(), expected.len());
+        for (gap, (start, end)) in gaps.iter().zip(expected) {
+            assert_close(gap.start, start);
+            assert_close(gap.end.unwrap(), end);
+        }
+
+        // a process without children is idle for its entire lifetime
+        let gaps = idle_gaps(&rec, Pid::from_raw(104), 2.2);
+        assert_eq!(gaps.len(), 1);
+        assert_close(gaps[0].start, 1.6);
+        assert_close(gaps[0].end.unwrap(), 2.0);
+    }
+
+    #[test]
+    fn self_time_excludes_children() {
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        events.extend(fixtures::process(Some(1), 2, 0.5, "/bin/sleep", &["sleep"]));
+        events.push(fixtures::exit(2, 1.5, Some(0)));
+        events.push(fixtures::exit(1, 2.0, Some(0)));
+        let rec = Recording::from_events(events);
+
+        // the child covers half of the lifetime of its parent
+        assert_close(self_time(&rec, Pid::from_raw(1)).unwrap(), 1.0);
+        assert_close(self_time(&rec, Pid::from_raw(2)).unwrap(), 1.0);
+        assert_eq!(self_time(&rec, Pid::from_raw(3)), None);
+    }
+
+    #[test]
+    fn process_tree_text_is_indented() {
+        let rec = Recording::from_events(fixtures::build());
+        let expected = "\
+100 sh (2.200s)
+    101 make (2.000s)
+        102 cc (1.000s)
+        103 cc (1.200s)
+        104 ld (0.400s)
+";
+        assert_eq!(process_tree_text(&rec, LabelMode::Basename, true), expected);
+
+        // threads are only included on request
+        let rec = Recording::from_events(fixtures::threads());
+        assert_eq!(process_tree_text(&rec, LabelMode::Basename, false).lines().count(), 1);
+        assert_eq!(process_tree_text(&rec, LabelMode::Basename, true).lines().count(), 4);
+    }
+
+    #[test]
+    fn thread_and_process_time() {
+        // threads of 0.4, 0.6 and 0.7 until the end, a child process of 0.5 with its own thread of 0.2
+        let mut events = fixtures::threads();
+        events.extend(fixtures::process(Some(200), 204, 0.5, "/bin/sh", &["sh"]));
+        events.push(TraceEvent::ProcessChild {
+            parent: Pid::from_raw(204),
+            child: Pid::from_raw(205),
+            kind: ProcessKind::Thread,
+        });
+        events.push(TraceEvent::ProcessStart {
+            pid: Pid::from_raw(205),
+            time: 0.6,
+        });
+        events.push(fixtures::exit(205, 0.8, None));
+        events.push(fixtures::exit(204, 1.0, Some(0)));
+        let rec = Recording::from_events(events);
+
+        let time = thread_process_time(&rec, Pid::from_raw(200), 1.0);
+        assert_close(time.threads, 0.4 + 0.6 + 0.7 + 0.2);
+        assert_close(time.processes, 0.5);
+        assert_close(time.ratio().unwrap(), 1.9 / 0.5);
+
+        let time = thread_process_time(&Recording::from_events(fixtures::threads()), Pid::from_raw(200), 1.0);
+        assert_eq!(time.ratio(), None);
+    }
+
+    #[test]
+    fn concurrency_sweep_steps() {
+        let concurrency = concurrency_over_time(&Recording::from_events(fixtures::build()));
+        let expected = [
+            (0.0, 1),
+            (0.1, 2),
+            (0.2, 3),
+            (0.3, 4),
+            (1.2, 3),
+            (1.5, 2),
+            (1.6, 3),
+            (2.0, 2),
+            (2.1, 1),
+            (2.2, 0),
+        ];
+        assert_eq!(concurrency.processes, expected);
+        assert!(concurrency.threads.is_empty());
+
+        // threads are counted separately, the server itself is still running
+        let concurrency = concurrency_over_time(&Recording::from_events(fixtures::threads()));
+        assert_eq!(concurrency.processes, [(0.0, 1)]);
+        let counts = concurrency.threads.iter().map(|&(_, count)| count).collect_vec();
+        assert_eq!(counts, [1, 2, 3, 2, 1]);
+
+        // simultaneous events result in a single step
+        let steps = sweep_active_count([
+            TimeRange {
+                start: 0.0,
+                end: Some(1.0),
+            },
+            TimeRange { start: 1.0, end: None },
+        ]);
+        assert_eq!(steps, [(0.0, 1), (1.0, 1)]);
+    }
+
+    #[test]
+    fn summary_text_top_executable() {
+        // three parallel compilers dominate the summed time
+        let mut events = fixtures::start();
+        events.extend(fixtures::process(None, 1, 0.0, "/bin/sh", &["sh"]));
+        for pid in 2..=4 {
+            events.extend(fixtures::process(Some(1), pid, 0.0, "/usr/bin/cc", &["cc"]));
+            events.push(fixtures::exit(pid, 2.0, Some(0)));
+        }
+        events.push(fixtures::exit(1, 2.5, Some(0)));
+        let rec = Recording::from_events(events);
+
+        let text = summary_text(&rec, 1);
+        let lines = text.lines().collect_vec();
+        let top = lines.iter().position(|&line| line == "top executables:").unwrap();
+        assert_eq!(lines[top + 1], "    cc: 3 (6.000s)");
+        assert_eq!(lines[top + 2], "critical path:");
+        assert!(lines.contains(&"processes: 4"));
+    }
+
+    #[test]
+    fn trace_diff_per_executable() {
+        // a root running the given tools one after the other
+        let sequential = |tools: &[(&str, f64)]| {
+            let mut events = fixtures::start();
+            events.extend(fixtures::process(None, 1, 0.0, "/usr/bin/make", &["make"]));
+            let mut time = 0.0;
+            for (i, &(name, duration)) in tools.iter().enumerate() {
+                let pid = 10 + i as i32;
+                events.extend(fixtures::process(
+                    Some(1),
+                    pid,
+                    time,
+                    &format!("/usr/bin/{name}"),
+                    &[name],
+                ));
+                time += duration;
+                events.push(fixtures::exit(pid, time, Some(0)));
+            }
+            events.push(fixtures::exit(1, time, Some(0)));
+            Recording::from_events(events)
+        };
+        let before = sequential(&[("cc", 1.0), ("cc", 2.0), ("ld", 1.0), ("strip", 0.5)]);
+        let after = sequential(&[("cc", 1.5), ("cc", 2.0), ("ld", 0.5), ("as", 0.25)]);
+
+        let diff = trace_diff(&before, &after, LabelMode::Basename);
+        let find = |name: &str| diff.executables.iter().find(|e| e.name == name).unwrap();
+        let cc = find("cc");
+        assert_eq!((cc.kind, cc.count_before, cc.count_after), (DiffKind::Slower, 2, 2));
+        assert_close(cc.delta, 0.5);
+        assert_eq!(find("ld").kind, DiffKind::Faster);
+        assert_close(find("ld").delta, -0.5);
+        assert_eq!(find("strip").kind, DiffKind::Removed);
+        assert_eq!(find("as").kind, DiffKind::Added);
+
+        // added and removed executables come first
+        let kinds = diff.executables.iter().map(|e| e.kind).collect_vec();
+        assert!(matches!(
+            kinds[..2],
+            [DiffKind::Removed | DiffKind::Added, DiffKind::Removed | DiffKind::Added]
+        ));
+    }
+
+    #[test]
+    fn argv_diff_shared_prefix() {
+        let old = ["cc", "-c", "-O2", "-g", "a.c", "-o", "a.o"];
+        let new = ["cc", "-c", "-O0", "-g", "-DDEBUG", "b.c", "-o", "b.o"];
+        let diff = argv_diff(&old, &new);
+        assert_eq!(
+            diff,
+            [
+                ArgDiff::Same(&"cc"),
+                ArgDiff::Same(&"-c"),
+                ArgDiff::Removed(&"-O2"),
+                ArgDiff::Added(&"-O0"),
+                ArgDiff::Same(&"-g"),
+                ArgDiff::Removed(&"a.c"),
+                ArgDiff::Added(&"-DDEBUG"),
+                ArgDiff::Added(&"b.c"),
+                ArgDiff::Same(&"-o"),
+                ArgDiff::Removed(&"a.o"),
+                ArgDiff::Added(&"b.o"),
+            ]
+        );
+
+        // identical lists only have shared arguments
+        assert!(argv_diff(&old, &old).iter().all(|d| matches!(d, ArgDiff::Same(_))));
+    }
+
+    #[test]
+    fn process_groups_by_pgid() {
+        // make runs in its own group, the pipeline of the compilers in another
+        let mut rec = Recording::from_events(fixtures::build());
+        for (pid, pgid) in [(100, 100), (101, 101), (102, 102), (103, 102)] {
+            rec.report(TraceEvent::ProcessGroup {
+                pid: Pid::from_raw(pid),
+                pgid: Pid::from_raw(pgid),
+                sid: Pid::from_raw(100),
+            });
+        }
+
+        let groups = process_groups(&rec)
+            .into_iter()
+            .map(|(pgid, pids)| (pgid.as_raw(), pids.iter().map(|pid| pid.as_raw()).collect_vec()))
+            .collect_vec();
+        // the linker has no known group
+        assert_eq!(groups, [(100, vec![100]), (101, vec![101]), (102, vec![102, 103])]);
+    }
+
+    #[test]
+    fn selection_summary_of_multiple_processes() {
+        let rec = Recording::from_events(fixtures::build());
+        let pids = [102, 103, 104].map(Pid::from_raw);
+        let summary = selection_summary(&rec, &pids, LabelMode::Basename);
+
+        assert_eq!(summary.count, 3);
+        assert_close(summary.total_time, 1.0 + 1.2 + 0.4);
+        let executables = summary
+            .executables
+            .iter()
+            .map(|s| (s.name.to_str().unwrap(), s.count))
+            .collect_vec();
+        assert_eq!(executables, [("cc", 2), ("ld", 1)]);
+
+        // unknown pids are ignored
+        let summary = selection_summary(&rec, &[Pid::from_raw(999)], LabelMode::Basename);
+        assert_eq!(summary.count, 0);
+    }
+}
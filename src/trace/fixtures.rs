@@ -0,0 +1,97 @@
+//! Pre-built event streams for the synthetic [`replay`](super::replay) backend.
+//! Times are in seconds since the start of the trace, like the real backends report them.
+
+use crate::record::ProcessKind;
+use crate::trace::TraceEvent;
+use nix::unistd::Pid;
+use std::ffi::OsString;
+use std::time::{Instant, SystemTime};
+
+/// A shell running `make`, which runs two compilers in parallel and then links the result.
+pub fn build() -> Vec<TraceEvent> {
+    let mut events = start();
+    events.extend(process(None, 100, 0.0, "/bin/sh", &["sh", "-c", "make"]));
+    events.extend(process(Some(100), 101, 0.1, "/usr/bin/make", &["make"]));
+    events.extend(process(Some(101), 102, 0.2, "/usr/bin/cc", &["cc", "-c", "a.c"]));
+    events.extend(process(Some(101), 103, 0.3, "/usr/bin/cc", &["cc", "-c", "b.c"]));
+    events.push(exit(102, 1.2, Some(0)));
+    events.push(exit(103, 1.5, Some(0)));
+    events.extend(process(
+        Some(101),
+        104,
+        1.6,
+        "/usr/bin/ld",
+        &["ld", "a.o", "b.o", "-o", "main"],
+    ));
+    events.push(exit(104, 2.0, Some(0)));
+    events.push(exit(101, 2.1, Some(0)));
+    events.push(exit(100, 2.2, Some(0)));
+    events.push(TraceEvent::TraceEnd { time: 2.2 });
+    events
+}
+
+/// A single process that starts a few threads, one of which is still running when the trace ends.
+pub fn threads() -> Vec<TraceEvent> {
+    let mut events = start();
+    events.extend(process(
+        None,
+        200,
+        0.0,
+        "/usr/bin/server",
+        &["server", "--threads", "3"],
+    ));
+    for (i, pid) in (201..=203).enumerate() {
+        events.push(TraceEvent::ProcessChild {
+            parent: Pid::from_raw(200),
+            child: Pid::from_raw(pid),
+            kind: ProcessKind::Thread,
+        });
+        events.push(TraceEvent::ProcessStart {
+            pid: Pid::from_raw(pid),
+            time: 0.1 * (i + 1) as f64,
+        });
+    }
+    events.push(exit(201, 0.5, None));
+    events.push(exit(202, 0.8, None));
+    events
+}
+
+/// The start of a trace, at the current time.
+pub fn start() -> Vec<TraceEvent> {
+    vec![TraceEvent::TraceStart {
+        time: Instant::now(),
+        wall_time: SystemTime::now(),
+    }]
+}
+
+/// The events for a process that starts and immediately execs, as a child of `parent` if any.
+pub fn process(parent: Option<i32>, pid: i32, time: f64, path: &str, argv: &[&str]) -> Vec<TraceEvent> {
+    let pid = Pid::from_raw(pid);
+    let mut events = vec![];
+    if let Some(parent) = parent {
+        events.push(TraceEvent::ProcessChild {
+            parent: Pid::from_raw(parent),
+            child: pid,
+            kind: ProcessKind::Process,
+        });
+    }
+    events.push(TraceEvent::ProcessStart { pid, time });
+    events.push(TraceEvent::ProcessExec {
+        pid,
+        time,
+        cwd: Some(OsString::from("/src")),
+        path: OsString::from(path),
+        argv: argv.iter().map(OsString::from).collect(),
+    });
+    events
+}
+
+/// The exit of a process, without a signal.
+pub fn exit(pid: i32, time: f64, exit_code: Option<i32>) -> TraceEvent {
+    TraceEvent::ProcessExit {
+        pid: Pid::from_raw(pid),
+        time,
+        exit_code,
+        signal: None,
+    }
+}
@@ -0,0 +1,1163 @@
+use crate::record::{ExitSignal, ProcessKind};
+use crate::trace::{TraceError, TraceEvent};
+use crate::util::MapExt;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc;
+use nix::sys::signal::Signal;
+use nix::sys::wait::WaitStatus;
+use nix::sys::{ptrace, wait};
+use nix::unistd::{ForkResult, Pid};
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::ops::ControlFlow;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::time::{Instant, SystemTime};
+use syscalls::Sysno;
+
+/// Start and trace the command, see [`start_trace`] and [`run_trace`].
+pub unsafe fn record_trace(
+    child_path: &CStr,
+    child_argv: &[CString],
+    callback: impl FnMut(TraceEvent) -> ControlFlow<()>,
+) -> Result<(), TraceError> {
+    let child = unsafe { start_trace(child_path, child_argv, None, None, None)? };
+    run_trace(child, false, callback)
+}
+
+/// Trace a child started by [`start_trace`] until it exits or the callback breaks.
+/// If `exec_failures` is set, failed exec attempts are reported as [`TraceEvent::ExecFailed`].
+/// This can be called from any thread, but all ptrace calls must happen on that same thread.
+pub fn run_trace(
+    child: TraceChild,
+    exec_failures: bool,
+    callback: impl FnMut(TraceEvent) -> ControlFlow<()>,
+) -> Result<(), TraceError> {
+    match run_trace_impl(child, exec_failures, callback) {
+        ControlFlow::Continue(r) => r,
+        ControlFlow::Break(()) => Ok(()),
+    }
+}
+
+const CHECK_PTRACE_SYSCALL_INFO_NEW: bool = false;
+
+macro_rules! try_trace {
+    ($e:expr, $variant:expr) => {
+        match ($e) {
+            Ok(v) => v,
+            Err(e) => return ControlFlow::Continue(Err($variant(e))),
+        }
+    };
+}
+
+/// Kill the entire process group on drop.
+/// `PTRACE_O_EXITKILL` only covers processes that are still traced, this also covers detached grandchildren.
+struct KillGroupOnDrop(Pid);
+
+impl Drop for KillGroupOnDrop {
+    fn drop(&mut self) {
+        let _ = nix::sys::signal::killpg(self.0, Signal::SIGKILL);
+    }
+}
+
+/// A forked child process that waits before executing the command until it's being traced.
+/// Dropping this kills the child and its process group.
+pub struct TraceChild {
+    pid: Pid,
+    /// Writing a byte to this pipe lets the child continue to exec.
+    start_tx: OwnedFd,
+    _kill_group: KillGroupOnDrop,
+}
+
+impl TraceChild {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+}
+
+/// Fork the child process that will run the command, without executing it yet.
+/// If `child_envp` is set, it replaces the environment of the command, as `KEY=VALUE` entries.
+/// If `cwd` is set, the command runs in that directory.
+/// If `stdio` is set, those become the stdout and stderr of the command.
+///
+/// Between fork and exec the child only makes async-signal-safe calls, without allocating,
+/// so this can also be called once other threads are running.
+pub unsafe fn start_trace(
+    child_path: &CStr,
+    child_argv: &[CString],
+    child_envp: Option<&[CString]>,
+    cwd: Option<&CStr>,
+    stdio: Option<(&OwnedFd, &OwnedFd)>,
+) -> Result<TraceChild, TraceError> {
+    let stdio = stdio.map(|(stdout, stderr)| (stdout.as_raw_fd(), stderr.as_raw_fd()));
+    let (start_rx, start_tx) = nix::unistd::pipe2(OFlag::O_CLOEXEC).map_err(TraceError::Fork)?;
+    let (setup_rx, setup_tx) = nix::unistd::pipe2(OFlag::O_CLOEXEC).map_err(TraceError::Fork)?;
+    // build the null-terminated argv and envp arrays up front, the child can't allocate
+    let null_terminated = |strings: &[CString]| -> Vec<*const libc::c_char> {
+        strings
+            .iter()
+            .map(|s| s.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect()
+    };
+    let child_argv = null_terminated(child_argv);
+    let child_envp = child_envp.map(null_terminated);
+
+    match unsafe { nix::unistd::fork() }.map_err(TraceError::Fork)? {
+        ForkResult::Parent { child } => {
+            drop(setup_tx);
+            let kill_group = KillGroupOnDrop(child);
+
+            // wait for the child to be set up, if that failed it exits without waiting to be traced
+            let (step, errno) = read_child_setup(&setup_rx);
+            if step != ChildSetup::Done {
+                let _ = wait::waitpid(child, None);
+            }
+            match step {
+                ChildSetup::Done => Ok(TraceChild {
+                    pid: child,
+                    start_tx,
+                    _kill_group: kill_group,
+                }),
+                ChildSetup::Cwd => {
+                    let cwd = cwd.map_or_else(OsString::new, |cwd| OsStr::from_bytes(cwd.to_bytes()).to_owned());
+                    Err(TraceError::Cwd(cwd.into(), errno))
+                }
+                ChildSetup::Other => Err(TraceError::Fork(errno)),
+            }
+        }
+        ForkResult::Child => {
+            drop(start_tx);
+            drop(setup_rx);
+            match unsafe {
+                run_child(
+                    child_path,
+                    &child_argv,
+                    child_envp.as_deref(),
+                    cwd,
+                    stdio,
+                    start_rx,
+                    setup_tx,
+                )
+            } {
+                Ok(()) => unreachable!("after exec"),
+                Err(_) => {
+                    // setup errors are sent to the parent, exec errors it sees anyway because it's recording syscalls
+                    unsafe { libc::_exit(1) }
+                }
+            }
+        }
+    }
+}
+
+/// How far the child got in setting itself up before exec, reported to the parent by [run_child].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(i32)]
+enum ChildSetup {
+    Done = 0,
+    Cwd = 1,
+    Other = 2,
+}
+
+/// Read the setup step and errno reported by the child, a child that died without reporting counts as failed.
+fn read_child_setup(setup_rx: &OwnedFd) -> (ChildSetup, Errno) {
+    let mut buf = [0; 8];
+    let mut len = 0;
+    while len < buf.len() {
+        match nix::unistd::read(setup_rx, &mut buf[len..]) {
+            Ok(0) => return (ChildSetup::Other, Errno::EPIPE),
+            Ok(n) => len += n,
+            Err(Errno::EINTR) => continue,
+            Err(e) => return (ChildSetup::Other, e),
+        }
+    }
+
+    let step = i32::from_ne_bytes(buf[..4].try_into().unwrap());
+    let errno = Errno::from_raw(i32::from_ne_bytes(buf[4..].try_into().unwrap()));
+    let step = match step {
+        0 => ChildSetup::Done,
+        1 => ChildSetup::Cwd,
+        _ => ChildSetup::Other,
+    };
+    (step, errno)
+}
+
+fn run_trace_impl(
+    child: TraceChild,
+    exec_failures: bool,
+    mut callback: impl FnMut(TraceEvent) -> ControlFlow<()>,
+) -> ControlFlow<(), Result<(), TraceError>> {
+    let root_pid = child.pid;
+
+    // attach and wait for the resulting stop, the child is still waiting so it can't have exec-ed yet
+    try_trace!(ptrace::attach(root_pid), TraceError::Ptrace);
+    let s = try_trace!(wait::waitpid(root_pid, None), TraceError::Wait);
+    assert!(matches!(s, WaitStatus::Stopped(pid, Signal::SIGSTOP) if pid == root_pid));
+
+    // start ptrace
+    try_trace!(ptrace::setoptions(root_pid, ptrace_options()), TraceError::Ptrace);
+
+    // let the child continue to exec once it's resumed
+    try_trace!(nix::unistd::write(&child.start_tx, &[0]), TraceError::Exec);
+
+    // report initial process start
+    // TODO is this time info accurate enough?
+    let time_start = Instant::now();
+    callback(TraceEvent::TraceStart {
+        time: time_start,
+        wall_time: SystemTime::now(),
+    })?;
+    callback(TraceEvent::ProcessStart {
+        pid: root_pid,
+        time: 0.0,
+    })?;
+
+    // resume after earlier stop
+    try_trace!(ptrace::syscall(root_pid, None), TraceError::Ptrace);
+
+    // track in-progress syscall per child
+    let mut partial_syscalls: HashMap<Pid, SyscallEntry> = HashMap::new();
+    let mut active_processes: HashSet<Pid> = HashSet::new();
+    active_processes.insert(root_pid);
+
+    // main tracing event loop
+    let mut root_exec = RootExecState::Pending { error: None };
+
+    loop {
+        let status = try_trace!(wait::waitpid(None, None), TraceError::Wait);
+        let time_status = time_start.elapsed().as_secs_f64();
+
+        callback(TraceEvent::None)?;
+
+        let resume: Option<(Pid, Option<Signal>)> = match status {
+            // handle syscall
+            WaitStatus::PtraceSyscall(pid) => {
+                match partial_syscalls.remove(&pid) {
+                    None => {
+                        // syscall entry
+                        let info = ptrace_syscall_info_entry(CHECK_PTRACE_SYSCALL_INFO_NEW, pid)
+                            .expect("failed to get syscall entry info");
+                        let nr = Sysno::new(info.nr as usize);
+
+                        let next_partial_syscall = if let Some(nr) = nr {
+                            match nr {
+                                // handle fork-like
+                                Sysno::clone => {
+                                    let flags = info.args[0];
+                                    SyscallEntry::fork(flags as _, time_status)
+                                }
+                                Sysno::clone3 => {
+                                    let clone_args_ptr = info.args[0];
+                                    let clone_args_size = info.args[1] as usize;
+
+                                    // if this fails the syscall itself will fail too, so the kind doesn't matter
+                                    let flags = ptrace_read_clone_args(pid, clone_args_ptr, clone_args_size)
+                                        .map_or(0, |clone_args| clone_args.flags);
+
+                                    SyscallEntry::fork(flags as _, time_status)
+                                }
+                                Sysno::fork => SyscallEntry::fork(0, time_status),
+                                Sysno::vfork => SyscallEntry::fork(libc::CLONE_VFORK as _, time_status),
+                                // handle exec-like
+                                Sysno::execve => {
+                                    let args_ptr = ExecArgPointers {
+                                        path: info.args[0],
+                                        argv: info.args[1],
+                                        envp: info.args[2],
+                                    };
+                                    let args =
+                                        ptrace_extract_exec_args(pid, args_ptr).expect("failed to extract exec args");
+                                    SyscallEntry::Exec(args)
+                                }
+                                Sysno::execveat => {
+                                    let args_ptr = ExecArgPointers {
+                                        path: info.args[1],
+                                        argv: info.args[2],
+                                        envp: info.args[3],
+                                    };
+                                    let mut args =
+                                        ptrace_extract_exec_args(pid, args_ptr).expect("failed to extract exec args");
+
+                                    // resolve the path now, the dirfd might be closed by the exec itself
+                                    let dirfd = info.args[0] as i32;
+                                    let flags = info.args[4] as i32;
+                                    if let Ok(path) = resolve_execveat_path(pid, dirfd, &args.path, flags) {
+                                        args.path = path;
+                                    }
+
+                                    SyscallEntry::Exec(args)
+                                }
+                                // ignore exit syscalls, we'll record the actual exit on process termination
+                                Sysno::exit | Sysno::exit_group => SyscallEntry::Ignore,
+                                // ignore other syscalls, we're only interested in fork/exec
+                                _ => SyscallEntry::Ignore,
+                            }
+                        } else {
+                            // ignore unknown syscalls
+                            SyscallEntry::Ignore
+                        };
+
+                        partial_syscalls.insert_first(pid, next_partial_syscall);
+                    }
+                    Some(partial) => {
+                        let info = ptrace_syscall_info_exit(CHECK_PTRACE_SYSCALL_INFO_NEW, pid)
+                            .expect("failed to get syscall exit info");
+
+                        match partial {
+                            SyscallEntry::Ignore => {}
+                            SyscallEntry::Fork { kind, vfork_time: None } => {
+                                if info.sval > 0 {
+                                    callback(TraceEvent::ProcessChild {
+                                        parent: pid,
+                                        child: Pid::from_raw(info.sval as i32),
+                                        kind,
+                                    })?;
+                                }
+                            }
+                            SyscallEntry::Fork {
+                                kind: _,
+                                vfork_time: Some(vfork_time),
+                            } => {
+                                // the child was already reported by the vfork event,
+                                //   the syscall only returns once the child exec-ed or exited
+                                if info.sval > 0 {
+                                    callback(TraceEvent::ProcessVforkBlocked {
+                                        pid,
+                                        start: vfork_time,
+                                        end: time_status,
+                                    })?;
+                                }
+                            }
+                            SyscallEntry::Exec(args) => {
+                                // check for errors when spawning the child process
+                                if pid == root_pid {
+                                    root_exec.report(info.sval);
+                                }
+
+                                if info.sval == 0 {
+                                    let cwd = get_process_working_dir(pid).ok();
+                                    callback(TraceEvent::ProcessExec {
+                                        pid,
+                                        time: time_status,
+                                        cwd,
+                                        path: OsString::from_vec(args.path),
+                                        argv: args.argv.into_iter().map(OsString::from_vec).collect(),
+                                    })?;
+
+                                    // shells move children into new groups between fork and exec
+                                    if let Ok(pgid) = nix::unistd::getpgid(Some(pid))
+                                        && let Ok(sid) = nix::unistd::getsid(Some(pid))
+                                    {
+                                        callback(TraceEvent::ProcessGroup { pid, pgid, sid })?;
+                                    }
+                                    // the baseline memory of the new executable
+                                    if let Ok(rss) = crate::poll::process_rss(pid) {
+                                        callback(TraceEvent::ProcessMemory {
+                                            pid,
+                                            time: time_status,
+                                            rss,
+                                        })?;
+                                    }
+                                    // container runtimes enter new namespaces between fork and exec too
+                                    if let Ok(namespace_id) = crate::poll::process_namespace(pid) {
+                                        callback(TraceEvent::ProcessNamespace { pid, namespace_id })?;
+                                    }
+                                    // setuid binaries change credentials on exec, which restricts tracing them
+                                    if let Ok(credentials) = crate::poll::process_credentials(pid) {
+                                        callback(TraceEvent::ProcessCredentials { pid, credentials })?;
+                                    }
+                                } else if exec_failures && info.sval < 0 {
+                                    callback(TraceEvent::ExecFailed {
+                                        pid,
+                                        time: time_status,
+                                        path: OsString::from_vec(args.path),
+                                        errno: Errno::from_raw(-info.sval as i32),
+                                    })?;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Some((pid, None))
+            }
+            // report vfork children immediately, the vfork syscall itself only returns once the child exec-ed or exited,
+            //   which is too late to attach the child to its parent before it's gone
+            WaitStatus::PtraceEvent(pid, _signal, libc::PTRACE_EVENT_VFORK) => {
+                if let Ok(child) = ptrace::getevent(pid) {
+                    callback(TraceEvent::ProcessChild {
+                        parent: pid,
+                        child: Pid::from_raw(child as i32),
+                        kind: ProcessKind::Process,
+                    })?;
+                }
+                Some((pid, None))
+            }
+            // an exec from a thread other than the leader continues as the leader, with the pid of the process,
+            //   move the in-progress exec over and report the thread as gone, it doesn't get an exit notification
+            WaitStatus::PtraceEvent(pid, _signal, libc::PTRACE_EVENT_EXEC) => {
+                if let Ok(former) = ptrace::getevent(pid)
+                    && let former = Pid::from_raw(former as i32)
+                    && former != pid
+                    && let Some(partial) = partial_syscalls.remove(&former)
+                {
+                    partial_syscalls.insert(pid, partial);
+                    active_processes.remove(&former);
+                    callback(TraceEvent::ProcessExit {
+                        pid: former,
+                        time: time_status,
+                        exit_code: None,
+                        signal: None,
+                    })?;
+                }
+                Some((pid, None))
+            }
+            // ignore other events
+            //    these get reported for the parent process when children are created due to the ptrace options,
+            //    but we don't care about them
+            WaitStatus::PtraceEvent(pid, _signal, _event) => Some((pid, None)),
+            // process exited, cleanup and maybe stop tracing
+            WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) => {
+                let (exit_code, signal) = match status {
+                    WaitStatus::Exited(_, code) => (Some(code), None),
+                    WaitStatus::Signaled(_, signal, core_dumped) => (
+                        None,
+                        Some(ExitSignal {
+                            signal: signal as i32,
+                            core_dumped,
+                        }),
+                    ),
+                    _ => (None, None),
+                };
+                callback(TraceEvent::ProcessExit {
+                    pid,
+                    time: time_status,
+                    exit_code,
+                    signal,
+                })?;
+
+                partial_syscalls.remove(&pid);
+                if pid == root_pid {
+                    break;
+                }
+                None
+            }
+            // stopped by some signal, just continue
+            WaitStatus::Stopped(pid, signal) => {
+                if matches!(signal, Signal::SIGSTOP | Signal::SIGTRAP) && !active_processes.contains(&pid) {
+                    // initial stop for new child process, create it
+                    callback(TraceEvent::ProcessStart { pid, time: time_status })?;
+                    active_processes.insert(pid);
+                    Some((pid, None))
+                } else {
+                    // block sigtrap signals
+                    if matches!(signal, Signal::SIGTRAP) {
+                        Some((pid, None))
+                    } else {
+                        Some((pid, Some(signal)))
+                    }
+                }
+            }
+            // cases that shouldn't happen
+            WaitStatus::Continued(_) => unreachable!("we didn't set WaitPidFlag::WCONTINUE"),
+            WaitStatus::StillAlive => unreachable!("we didn't set WaitPidFlag::WNOHANG"),
+        };
+
+        if let Some((resume_pid, resume_signal)) = resume {
+            ptrace::syscall(resume_pid, resume_signal).expect("failed ptrace::syscall");
+        }
+    }
+
+    // check if at least the root process managed to start
+    // (if there wasn't any exec attempt at all the root was probably killed, that's not a spawn failure)
+    if let RootExecState::Pending { error: Some(error) } = root_exec {
+        return ControlFlow::Continue(Err(TraceError::Exec(error)));
+    }
+
+    ControlFlow::Continue(Ok(()))
+}
+
+/// Tracks the initial exec attempts of the root process, to detect whether the command could be started.
+/// Only the attempts before the first successful exec matter, later failures are the command's own business.
+enum RootExecState {
+    Pending { error: Option<Errno> },
+    Started,
+}
+
+impl RootExecState {
+    fn report(&mut self, result: i64) {
+        let RootExecState::Pending { error } = self else {
+            return;
+        };
+
+        if result >= 0 {
+            *self = RootExecState::Started;
+            return;
+        }
+
+        // there can be multiple attempts due to $PATH, like execvp report the most relevant error:
+        //   ENOENT and ENOTDIR only mean the command is not in that directory, any other error is more specific
+        let new_error = Errno::from_raw(-result as i32);
+        let is_not_found = |e: Errno| matches!(e, Errno::ENOENT | Errno::ENOTDIR);
+        if error.is_none_or(|e| is_not_found(e) || !is_not_found(new_error)) {
+            *error = Some(new_error);
+        }
+    }
+}
+
+/// Check whether ptrace is usable with the options we need, by tracing a dummy child process that never execs.
+/// If this fails, the caller can fall back to polling before any command has been started.
+pub fn check_ptrace_support() -> nix::Result<()> {
+    let child = match unsafe { nix::unistd::fork()? } {
+        ForkResult::Parent { child } => child,
+        ForkResult::Child => loop {
+            // wait to be attached to and killed, like run_child waits for the parent
+            unsafe { libc::pause() };
+        },
+    };
+
+    let check = || -> nix::Result<()> {
+        ptrace::attach(child)?;
+        match wait::waitpid(child, None)? {
+            WaitStatus::Stopped(_, Signal::SIGSTOP) => {}
+            _ => return Err(Errno::EPERM),
+        }
+        ptrace::setoptions(child, ptrace_options())?;
+        ptrace::getregs(child)?;
+        if CHECK_PTRACE_SYSCALL_INFO_NEW {
+            ptrace_syscall_info(child)?;
+        }
+        Ok(())
+    };
+    let result = check();
+
+    // cleanup the dummy child
+    let _ = nix::sys::signal::kill(child, Signal::SIGKILL);
+    let _ = wait::waitpid(child, None);
+
+    result
+}
+
+/// The ptrace options used for all traced processes.
+/// * PTRACE_O_TRACESYSGOOD: add mask to syscall stops, allows parsing WaitStatus::PtraceSyscall
+/// * PTRACE_O_EXITKILL: kill traced process if tracer exits to avoid orphaned processes
+/// * PTRACE_O_TRACE*: trace children through fork syscalls?
+fn ptrace_options() -> ptrace::Options {
+    ptrace::Options::PTRACE_O_TRACESYSGOOD
+        | ptrace::Options::PTRACE_O_EXITKILL
+        | ptrace::Options::PTRACE_O_TRACECLONE
+        | ptrace::Options::PTRACE_O_TRACEFORK
+        | ptrace::Options::PTRACE_O_TRACEVFORK
+        | ptrace::Options::PTRACE_O_TRACEEXEC
+}
+
+/// The argv array must be null-terminated.
+unsafe fn run_child(
+    child_path: &CStr,
+    child_argv: &[*const libc::c_char],
+    child_envp: Option<&[*const libc::c_char]>,
+    cwd: Option<&CStr>,
+    stdio: Option<(RawFd, RawFd)>,
+    start_rx: OwnedFd,
+    setup_tx: OwnedFd,
+) -> Result<(), nix::Error> {
+    let setup = || -> Result<(), (ChildSetup, Errno)> {
+        let other = |e| (ChildSetup::Other, e);
+        // set process group so we can kill all children later
+        nix::unistd::setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(other)?;
+        // redirect the output, dup2 clears the close-on-exec flag of the new fds
+        if let Some((stdout, stderr)) = stdio {
+            Errno::result(unsafe { libc::dup2(stdout, libc::STDOUT_FILENO) }).map_err(other)?;
+            Errno::result(unsafe { libc::dup2(stderr, libc::STDERR_FILENO) }).map_err(other)?;
+        }
+        if let Some(cwd) = cwd {
+            nix::unistd::chdir(cwd).map_err(|e| (ChildSetup::Cwd, e))?;
+        }
+        Ok(())
+    };
+    let (step, errno) = match setup() {
+        Ok(()) => (ChildSetup::Done, Errno::UnknownErrno),
+        Err(e) => e,
+    };
+
+    // tell the parent how setup went, pipe writes this small are atomic
+    let mut buf = [0; 8];
+    buf[..4].copy_from_slice(&(step as i32).to_ne_bytes());
+    buf[4..].copy_from_slice(&(errno as i32).to_ne_bytes());
+    nix::unistd::write(&setup_tx, &buf)?;
+    drop(setup_tx);
+    if step != ChildSetup::Done {
+        return Err(errno);
+    }
+
+    // wait until the parent is tracing this process, to avoid missing the exec
+    //   (EOF means the parent dropped the child without tracing it)
+    let mut buf = [0];
+    loop {
+        match nix::unistd::read(&start_rx, &mut buf) {
+            Ok(1) => break,
+            Ok(_) => return Err(Errno::EPIPE),
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    // actually execute the target program
+    // (nix::unistd::execvp would allocate to build the argv array)
+    match child_envp {
+        None => unsafe { libc::execvp(child_path.as_ptr(), child_argv.as_ptr()) },
+        Some(child_envp) => unsafe { libc::execvpe(child_path.as_ptr(), child_argv.as_ptr(), child_envp.as_ptr()) },
+    };
+    Err(Errno::last())
+}
+
+fn get_process_working_dir(pid: Pid) -> std::io::Result<OsString> {
+    let path = format!("/proc/{}/cwd", pid);
+    let cwd = std::fs::read_link(path)?;
+    Ok(cwd.into_os_string())
+}
+
+/// Resolve the path passed to execveat to an absolute path, following the same rules as the kernel:
+/// * absolute paths are used as-is
+/// * empty paths with `AT_EMPTY_PATH` refer to `dirfd` itself
+/// * relative paths are relative to `dirfd`, or to the working directory if `dirfd` is `AT_FDCWD`
+fn resolve_execveat_path(pid: Pid, dirfd: i32, path: &[u8], flags: i32) -> std::io::Result<Vec<u8>> {
+    if path.starts_with(b"/") {
+        return Ok(path.to_owned());
+    }
+
+    let dir = if dirfd == libc::AT_FDCWD {
+        std::fs::read_link(format!("/proc/{}/cwd", pid))?
+    } else {
+        std::fs::read_link(format!("/proc/{}/fd/{}", pid, dirfd))?
+    };
+
+    let resolved = if path.is_empty() && (flags & libc::AT_EMPTY_PATH) != 0 {
+        dir
+    } else {
+        dir.join(OsStr::from_bytes(path))
+    };
+    Ok(resolved.into_os_string().into_vec())
+}
+
+#[derive(Debug)]
+enum SyscallEntry {
+    Ignore,
+    /// For vfork-like syscalls, the time the parent started waiting for the child.
+    Fork {
+        kind: ProcessKind,
+        vfork_time: Option<f64>,
+    },
+    Exec(ExecArgs),
+}
+
+impl SyscallEntry {
+    fn fork(clone_flags: libc::c_long, time: f64) -> Self {
+        let vfork = (clone_flags & libc::CLONE_VFORK as libc::c_long) != 0;
+        SyscallEntry::Fork {
+            kind: process_kind_from_clone_flags(clone_flags),
+            vfork_time: vfork.then_some(time),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ExecArgPointers {
+    path: u64,
+    #[allow(dead_code)]
+    argv: u64,
+    #[allow(dead_code)]
+    envp: u64,
+}
+
+#[derive(Debug)]
+struct ExecArgs {
+    path: Vec<u8>,
+    #[allow(dead_code)]
+    argv: Vec<Vec<u8>>,
+}
+
+fn process_kind_from_clone_flags(flags: libc::c_long) -> ProcessKind {
+    if (flags & libc::CLONE_THREAD as libc::c_long) != 0 {
+        ProcessKind::Thread
+    } else {
+        ProcessKind::Process
+    }
+}
+
+struct PtraceSyscallInfoEntry {
+    nr: u64,
+    args: [u64; 6],
+}
+
+struct PtraceSyscallInfoExit {
+    sval: i64,
+}
+
+fn ptrace_syscall_info_entry(check_using_syscall: bool, pid: Pid) -> nix::Result<PtraceSyscallInfoEntry> {
+    // get info manually
+    let regs = ptrace::getregs(pid)?;
+    let info = PtraceSyscallInfoEntry {
+        nr: regs.orig_rax,
+        args: [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+    };
+
+    // check that info matches the kernel-provided function
+    if check_using_syscall {
+        let info_new = ptrace_syscall_info(pid)?;
+
+        assert_eq!(info_new.op, libc::PTRACE_SYSCALL_INFO_ENTRY);
+        let info_new = unsafe { &info_new.u.entry };
+        assert_eq!(info_new.nr, info.nr);
+        assert_eq!(info_new.args, info.args);
+    }
+
+    Ok(info)
+}
+
+fn ptrace_syscall_info_exit(check_using_syscall: bool, pid: Pid) -> nix::Result<PtraceSyscallInfoExit> {
+    // get info manually
+    let regs = ptrace::getregs(pid)?;
+    let info = PtraceSyscallInfoExit { sval: regs.rax as i64 };
+
+    // check that info matches the kernel-provided function
+    if check_using_syscall {
+        let info_new = ptrace_syscall_info(pid)?;
+
+        assert_eq!(info_new.op, libc::PTRACE_SYSCALL_INFO_EXIT);
+        let info_new = unsafe { &info_new.u.exit };
+        assert_eq!(info_new.sval, info.sval);
+    }
+
+    Ok(info)
+}
+
+/// Fixed version of ptrace::syscall_info.
+/// Based on https://github.com/nix-rust/nix/issues/2660.
+fn ptrace_syscall_info(pid: Pid) -> Result<libc::ptrace_syscall_info, Errno> {
+    let mut data = std::mem::MaybeUninit::<libc::ptrace_syscall_info>::uninit();
+
+    let res = unsafe {
+        libc::ptrace(
+            ptrace::Request::PTRACE_GET_SYSCALL_INFO as libc::c_uint,
+            libc::pid_t::from(pid),
+            size_of::<libc::ptrace_syscall_info>(),
+            data.as_mut_ptr(),
+        )
+    };
+
+    Errno::result(res)?;
+    let info = unsafe { data.assume_init() };
+    Ok(info)
+}
+
+fn ptrace_extract_exec_args(pid: Pid, args: ExecArgPointers) -> nix::Result<ExecArgs> {
+    let ExecArgPointers { path, argv: _, envp: _ } = args;
+
+    let path = ptrace_read_str(pid, path as *mut _)?;
+    let argv = ptrace_read_str_list(pid, args.argv as *mut _)?;
+
+    Ok(ExecArgs { path, argv })
+}
+
+/// The size of the first version of `struct clone_args`, the kernel rejects anything smaller.
+const CLONE_ARGS_SIZE_VER0: usize = 64;
+
+/// Read the `struct clone_args` passed to clone3.
+/// Newer fields that don't fit in `size` are left zero, matching the kernel behavior.
+fn ptrace_read_clone_args(pid: Pid, ptr: u64, size: usize) -> nix::Result<libc::clone_args> {
+    if size < CLONE_ARGS_SIZE_VER0 {
+        return Err(Errno::EINVAL);
+    }
+
+    let bytes = ptrace_read_bytes(pid, ptr, min(size, size_of::<libc::clone_args>()))?;
+    let mut clone_args: libc::clone_args = unsafe { std::mem::zeroed() };
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), &mut clone_args as *mut _ as *mut u8, bytes.len());
+    }
+    Ok(clone_args)
+}
+
+/// Read `len` bytes starting at `start`.
+/// Only reads aligned words, so no single read can span multiple pages.
+fn ptrace_read_bytes(pid: Pid, start: u64, len: usize) -> nix::Result<Vec<u8>> {
+    const WORD: u64 = size_of::<libc::c_long>() as u64;
+    let start_aligned = start - start % WORD;
+    let skip = (start - start_aligned) as usize;
+
+    let mut result = Vec::with_capacity(skip + len + WORD as usize);
+    let mut addr = start_aligned;
+    while result.len() < skip + len {
+        let word = ptrace::read(pid, addr as *mut libc::c_void)?;
+        result.extend_from_slice(&word.to_ne_bytes());
+        addr += WORD;
+    }
+
+    Ok(result[skip..skip + len].to_vec())
+}
+
+fn ptrace_read_str(pid: Pid, start: *mut libc::c_void) -> nix::Result<Vec<u8>> {
+    // TODO is there really no batch memory read?
+    // TODO limit max length?
+    let mut result = Vec::new();
+
+    for offset_word in 0isize.. {
+        let offset_byte = offset_word * size_of::<libc::c_long>() as isize;
+        let word = ptrace::read(pid, unsafe { start.offset(offset_byte) })?;
+        for b in word.to_ne_bytes() {
+            if b == 0 {
+                return Ok(result);
+            }
+            result.push(b);
+        }
+    }
+
+    Ok(result)
+}
+
+fn ptrace_read_str_list(pid: Pid, start: *mut libc::c_void) -> nix::Result<Vec<Vec<u8>>> {
+    let mut result = Vec::new();
+
+    for index in 0isize.. {
+        let ptr_addr = unsafe { start.offset(index * size_of::<*mut libc::c_void>() as isize) };
+        let ptr_value = ptrace::read(pid, ptr_addr)? as *mut libc::c_void;
+        if ptr_value.is_null() {
+            break;
+        }
+        result.push(ptrace_read_str(pid, ptr_value)?);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Recording;
+    use std::path::Path;
+
+    #[test]
+    fn clone_flags_kind() {
+        assert_eq!(
+            process_kind_from_clone_flags(libc::SIGCHLD as libc::c_long),
+            ProcessKind::Process
+        );
+        let thread_flags =
+            libc::CLONE_VM | libc::CLONE_FS | libc::CLONE_FILES | libc::CLONE_SIGHAND | libc::CLONE_THREAD;
+        assert_eq!(
+            process_kind_from_clone_flags(thread_flags as libc::c_long),
+            ProcessKind::Thread
+        );
+    }
+
+    #[test]
+    fn background_grandchild_is_killed() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        // the shell exits right away, leaving the sleep running in the background
+        // trace on this thread, so `PTRACE_O_EXITKILL` doesn't trigger when a tracer thread exits
+        let argv = ["/bin/sh", "-c", "sleep 100 & exit 0"].map(|s| CString::new(s).unwrap());
+        let mut rec = Recording::new();
+        let result = unsafe {
+            record_trace(&argv[0], &argv, |event| {
+                rec.report(event);
+                ControlFlow::Continue(())
+            })
+        };
+        result.unwrap();
+        let root = rec.root_pid.unwrap();
+        assert_eq!(rec.processes()[&root].exit_code, Some(0));
+        let sleep = rec.processes()[&root].children[0].1;
+
+        // the grandchild might take a moment to be reaped
+        let alive = |pid: Pid| {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .is_ok_and(|stat| !stat.rsplit(')').next().unwrap().trim_start().starts_with('Z'))
+        };
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        while alive(sleep) {
+            assert!(Instant::now() < deadline, "grandchild leaked");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn vfork_blocks_parent() {
+        // dash spawns simple commands with vfork, other shells fork instead
+        let is_dash = std::fs::canonicalize("/bin/sh").is_ok_and(|path| path.ends_with("dash"));
+        if check_ptrace_support().is_err() || !is_dash {
+            return;
+        }
+
+        // end with a builtin, otherwise the shell might exec the command instead of spawning it
+        let argv = ["/bin/sh", "-c", "/bin/true; exit 0"].map(|s| CString::new(s).unwrap());
+        let mut rec = Recording::new();
+        let result = unsafe {
+            record_trace(&argv[0], &argv, |event| {
+                rec.report(event);
+                ControlFlow::Continue(())
+            })
+        };
+        result.unwrap();
+        let root_pid = rec.root_pid.unwrap();
+        let root = &rec.processes()[&root_pid];
+        let [(ProcessKind::Process, child)] = root.children[..] else {
+            panic!("expected a single child process, got {:?}", root.children);
+        };
+        let child = &rec.processes()[&child];
+        assert_eq!(child.parent, Some(root_pid));
+        assert_eq!(child.execs[0].path, "/bin/true");
+
+        // the shell is blocked from the vfork until the child execs
+        //   (the end is only observed when the shell resumes, which can be before we see the exec finish)
+        let [blocked] = root.vfork_blocked[..] else {
+            panic!("expected a single blocked range, got {:?}", root.vfork_blocked);
+        };
+        assert!(blocked.start <= child.execs[0].time && blocked.start <= blocked.end.unwrap());
+        assert!(child.vfork_blocked.is_empty());
+    }
+
+    #[test]
+    fn abort_signal_is_recorded() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        let argv = ["/bin/sh", "-c", "kill -ABRT $$"].map(|s| CString::new(s).unwrap());
+        let mut rec = Recording::new();
+        let result = unsafe {
+            record_trace(&argv[0], &argv, |event| {
+                rec.report(event);
+                ControlFlow::Continue(())
+            })
+        };
+        result.unwrap();
+
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        assert_eq!(root.exit_code, None);
+        let signal = root.exit_signal.unwrap();
+        assert_eq!(signal.signal, libc::SIGABRT);
+        assert_eq!(signal.name(), "SIGABRT");
+    }
+
+    #[test]
+    fn failed_path_lookups_are_recorded() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        // env searches the path for the command, trying each entry in order
+        let argv = ["/usr/bin/env", "PATH=/nonexistent/a:/nonexistent/b:/bin", "true"].map(OsString::from);
+        let record = |exec_failures: bool| {
+            let backend = crate::backend::Backend::Ptrace { exec_failures };
+            crate::backend::record_to_recording(&argv[0], &argv, backend, Default::default()).unwrap()
+        };
+
+        // failures are only recorded on request
+        let rec = record(false);
+        assert!(rec.processes()[&rec.root_pid.unwrap()].exec_failures.is_empty());
+
+        let rec = record(true);
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        let failures = root
+            .exec_failures
+            .iter()
+            .map(|failure| (failure.path.to_str().unwrap(), failure.errno))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failures,
+            [
+                ("/nonexistent/a/true", libc::ENOENT),
+                ("/nonexistent/b/true", libc::ENOENT)
+            ]
+        );
+        assert_eq!(root.execs.last().unwrap().path, "/bin/true");
+    }
+
+    #[test]
+    fn missing_command_is_not_found() {
+        if check_ptrace_support().is_err() {
+            return;
+        }
+        let backend = || crate::backend::Backend::Ptrace { exec_failures: false };
+
+        let argv = ["wtf-nonexistent-binary"].map(OsString::from);
+        let result = crate::backend::record_to_recording(&argv[0], &argv, backend(), Default::default());
+        assert!(
+            matches!(result, Err(TraceError::Exec(Errno::ENOENT))),
+            "{:?}",
+            result.err()
+        );
+
+        // the shell itself starts fine, only its child fails
+        let argv = ["/bin/sh", "-c", "wtf-nonexistent-binary"].map(OsString::from);
+        let rec = crate::backend::record_to_recording(&argv[0], &argv, backend(), Default::default()).unwrap();
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        assert_eq!(root.exit_code, Some(127));
+        assert_eq!(root.execs.last().unwrap().path, "/bin/sh");
+    }
+
+    #[test]
+    fn threads_are_detected() {
+        const NAME: &str = "trace::linux::tests::threads_are_detected";
+
+        // when traced, start some threads and list them while they're all running
+        //   (the test harness runs each test on a new thread too, created with clone3 by recent libc versions)
+        if std::env::var_os("WTF_TEST_THREADS_TARGET").is_some() {
+            let barrier = std::sync::Barrier::new(4);
+            std::thread::scope(|s| {
+                for _ in 0..3 {
+                    s.spawn(|| {
+                        barrier.wait();
+                        barrier.wait();
+                    });
+                }
+                barrier.wait();
+                let tasks = std::fs::read_dir("/proc/self/task")
+                    .unwrap()
+                    .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                    .collect::<Vec<_>>();
+                println!("tasks: {}", tasks.join(" "));
+                barrier.wait();
+            });
+            return;
+        }
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        // the tracer waits for any child, which would also reap the children of other tests, so trace from a separate process
+        let exe = std::env::current_exe().unwrap();
+        if std::env::var_os("WTF_TEST_THREADS_TRACER").is_none() {
+            let status = std::process::Command::new(&exe)
+                .args(["--exact", NAME, "--test-threads=1"])
+                .env("WTF_TEST_THREADS_TRACER", "1")
+                .status()
+                .unwrap();
+            assert!(status.success(), "{}", status);
+            return;
+        }
+
+        let argv = [
+            exe.clone().into_os_string(),
+            "--exact".into(),
+            NAME.into(),
+            "--nocapture".into(),
+        ];
+        let options = crate::backend::CommandOptions {
+            env: vec![("WTF_TEST_THREADS_TARGET".into(), "1".into())],
+            capture_output: true,
+            ..Default::default()
+        };
+        let backend = crate::backend::Backend::Ptrace { exec_failures: false };
+        let started = backend.start(exe.as_os_str(), &argv, &options).unwrap();
+        let rec = std::sync::Mutex::new(Recording::new());
+        crate::backend::record_started(started, Default::default(), &rec).unwrap();
+        let rec = rec.into_inner().unwrap();
+
+        let root_pid = rec.root_pid.unwrap();
+        assert_eq!(rec.processes()[&root_pid].exit_code, Some(0));
+
+        // every task that was running at the same time was recorded as a thread of the process
+        let output = rec
+            .output
+            .iter()
+            .map(|chunk| chunk.text.to_str().unwrap())
+            .collect::<String>();
+        // the harness prints the test name on the same line
+        let (_, tasks) = output.split_once("tasks: ").unwrap();
+        let tasks = tasks
+            .lines()
+            .next()
+            .unwrap()
+            .split(' ')
+            .map(|task| Pid::from_raw(task.parse().unwrap()))
+            .collect::<HashSet<_>>();
+        let mut threads = rec.thread_pids();
+        threads.insert(root_pid);
+        assert!(tasks.len() >= 5, "{:?}", tasks);
+        assert!(tasks.is_subset(&threads), "{:?} not in {:?}", tasks, threads);
+    }
+
+    #[test]
+    fn execveat_paths_are_resolved() {
+        use std::os::fd::AsRawFd;
+
+        let pid = Pid::this();
+        let dir = std::fs::File::open("/usr").unwrap();
+        let dirfd = dir.as_raw_fd();
+        let cwd = std::env::current_dir().unwrap().into_os_string().into_vec();
+
+        let resolve = |dirfd, path: &[u8], flags| resolve_execveat_path(pid, dirfd, path, flags).unwrap();
+        assert_eq!(resolve(dirfd, b"/bin/sh", 0), b"/bin/sh");
+        assert_eq!(resolve(dirfd, b"bin/sh", 0), b"/usr/bin/sh");
+        assert_eq!(resolve(dirfd, b"", libc::AT_EMPTY_PATH), b"/usr");
+        assert_eq!(
+            resolve(libc::AT_FDCWD, b"run.sh", 0),
+            [cwd, b"/run.sh".to_vec()].concat()
+        );
+    }
+
+    #[test]
+    fn execveat_empty_path_is_recorded() {
+        use std::os::fd::AsRawFd;
+
+        // when run under the tracer below, exec through a file descriptor instead of a path
+        if std::env::var_os("WTF_TEST_EXECVEAT").is_some() {
+            let file = std::fs::File::open("/bin/true").unwrap();
+            let argv = [c"true".as_ptr(), std::ptr::null()];
+            let envp: [*const libc::c_char; 1] = [std::ptr::null()];
+            unsafe {
+                libc::syscall(
+                    libc::SYS_execveat,
+                    file.as_raw_fd(),
+                    c"".as_ptr(),
+                    argv.as_ptr(),
+                    envp.as_ptr(),
+                    libc::AT_EMPTY_PATH,
+                )
+            };
+            panic!("execveat failed: {}", std::io::Error::last_os_error());
+        }
+        if check_ptrace_support().is_err() {
+            return;
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        let argv = [
+            exe.clone().into_os_string(),
+            "--exact".into(),
+            "trace::linux::tests::execveat_empty_path_is_recorded".into(),
+        ];
+        let options = crate::backend::CommandOptions {
+            env: vec![("WTF_TEST_EXECVEAT".into(), "1".into())],
+            ..Default::default()
+        };
+        let backend = crate::backend::Backend::Ptrace { exec_failures: false };
+        let started = backend.start(exe.as_os_str(), &argv, &options).unwrap();
+        let rec = std::sync::Mutex::new(Recording::new());
+        crate::backend::record_started(started, Default::default(), &rec).unwrap();
+        let rec = rec.into_inner().unwrap();
+
+        let root = &rec.processes()[&rec.root_pid.unwrap()];
+        assert_eq!(root.exit_code, Some(0));
+        let exec = root.execs.last().unwrap();
+        assert_eq!(exec.path, std::fs::canonicalize("/bin/true").unwrap().into_os_string());
+        assert_eq!(exec.argv, ["true"]);
+    }
+
+    #[test]
+    fn missing_cwd_is_reported() {
+        let cwd = CString::new("/nonexistent/wtf").unwrap();
+        let argv = [CString::new("true").unwrap()];
+        let result = unsafe { start_trace(&argv[0], &argv, None, Some(&cwd), None) };
+        match result {
+            Err(TraceError::Cwd(path, Errno::ENOENT)) => assert_eq!(path, Path::new("/nonexistent/wtf")),
+            Err(e) => panic!("unexpected error {:?}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
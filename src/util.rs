@@ -1,5 +1,13 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
 use indexmap::IndexMap;
+use nix::errno::Errno;
+use nix::unistd::Pid;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 
 pub trait MapExt<K, V> {
     fn insert_first(&mut self, key: K, value: V);
@@ -24,3 +32,86 @@ where
         assert!(prev.is_none());
     }
 }
+
+/// Adapter to (de)serialize [Pid] as its raw integer value, for use with [serde_with::serde_as].
+pub struct PidAs;
+
+impl SerializeAs<Pid> for PidAs {
+    fn serialize_as<S: Serializer>(source: &Pid, serializer: S) -> Result<S::Ok, S::Error> {
+        source.as_raw().serialize(serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, Pid> for PidAs {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Pid, D::Error> {
+        i32::deserialize(deserializer).map(Pid::from_raw)
+    }
+}
+
+/// Adapter to (de)serialize [OsString] without losing data, for use with [serde_with::serde_as].
+/// Valid UTF-8 is stored as a plain string, anything else as `{"base64": "..."}` containing the raw bytes.
+pub struct OsStringAs;
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum OsStringRepr {
+    Str(String),
+    Base64 { base64: String },
+}
+
+impl OsStringRepr {
+    fn new(source: &OsStr) -> Self {
+        match source.to_str() {
+            Some(s) => OsStringRepr::Str(s.to_owned()),
+            None => OsStringRepr::Base64 {
+                base64: BASE64_STANDARD.encode(source.as_bytes()),
+            },
+        }
+    }
+}
+
+impl SerializeAs<OsString> for OsStringAs {
+    fn serialize_as<S: Serializer>(source: &OsString, serializer: S) -> Result<S::Ok, S::Error> {
+        OsStringRepr::new(source).serialize(serializer)
+    }
+}
+
+/// Raw bytes are stored the same way as an [OsString] containing them.
+impl SerializeAs<Vec<u8>> for OsStringAs {
+    fn serialize_as<S: Serializer>(source: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        OsStringRepr::new(OsStr::from_bytes(source)).serialize(serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, OsString> for OsStringAs {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<OsString, D::Error> {
+        match OsStringRepr::deserialize(deserializer)? {
+            OsStringRepr::Str(s) => Ok(OsString::from(s)),
+            OsStringRepr::Base64 { base64 } => {
+                let bytes = BASE64_STANDARD.decode(base64).map_err(D::Error::custom)?;
+                Ok(OsString::from_vec(bytes))
+            }
+        }
+    }
+}
+
+impl<'de> DeserializeAs<'de, Vec<u8>> for OsStringAs {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        <OsStringAs as DeserializeAs<OsString>>::deserialize_as(deserializer).map(OsString::into_vec)
+    }
+}
+
+/// Adapter to (de)serialize [Errno] as its raw integer value, for use with [serde_with::serde_as].
+pub struct ErrnoAs;
+
+impl SerializeAs<Errno> for ErrnoAs {
+    fn serialize_as<S: Serializer>(source: &Errno, serializer: S) -> Result<S::Ok, S::Error> {
+        (*source as i32).serialize(serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, Errno> for ErrnoAs {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Errno, D::Error> {
+        i32::deserialize(deserializer).map(Errno::from_raw)
+    }
+}